@@ -0,0 +1,125 @@
+//! Listing a full routing table at once and in chunks, with the peak memory of both. Needs a
+//! table of about a million routes, e.g. loaded into a network namespace:
+//!
+//! ```sh
+//! ip netns add bench
+//! ip -n bench link add dummy0 type dummy
+//! ip -n bench link set dummy0 up
+//! seq 0 999999 \
+//!     | awk '{ printf "route add %d.%d.%d.0/24 dev dummy0\n", 1 + int($1 / 65536), int($1 / 256) % 256, $1 % 256 }' \
+//!     | ip -n bench -batch -
+//! NET_ROUTE_BENCH_NETNS=/run/netns/bench cargo bench --bench large_table
+//! ```
+//!
+//! Without `NET_ROUTE_BENCH_NETNS` the table of the current namespace is listed.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use criterion::{black_box, Criterion, Throughput};
+    use futures::StreamExt;
+    use net_route::{DumpFilter, HandleBuilder, RouteManager};
+
+    const CHUNK_SIZE: usize = 10_000;
+
+    // Tracks the bytes allocated at most, to compare the memory of the two ways of listing.
+    pub struct PeakAlloc;
+
+    static CURRENT: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for PeakAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+                PEAK.fetch_max(current, Ordering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+    }
+
+    // The bytes allocated at most while running `f`, beyond what was allocated before.
+    fn peak_of<T>(f: impl FnOnce() -> T) -> usize {
+        let before = CURRENT.load(Ordering::Relaxed);
+        PEAK.store(before, Ordering::Relaxed);
+        black_box(f());
+        PEAK.load(Ordering::Relaxed) - before
+    }
+
+    async fn count_chunked(handle: &RouteManager) -> usize {
+        let chunks = handle.list_chunks(&DumpFilter::default(), CHUNK_SIZE, None);
+        chunks
+            .fold(0, |count, chunk| async move {
+                count + chunk.expect("listing in chunks failed").routes.len()
+            })
+            .await
+    }
+
+    pub fn large_table(c: &mut Criterion) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let handle = runtime.block_on(async {
+            let mut builder = HandleBuilder::new();
+            if let Some(netns) = std::env::var_os("NET_ROUTE_BENCH_NETNS") {
+                builder = builder.with_netns(netns);
+            }
+            builder.build_manager().unwrap()
+        });
+
+        let routes = runtime.block_on(count_chunked(&handle));
+        if routes < 1_000_000 {
+            eprintln!(
+                "only {} routes, see the top of benches/large_table.rs",
+                routes
+            );
+        }
+        let at_once = peak_of(|| runtime.block_on(handle.list()).unwrap().len());
+        let chunked = peak_of(|| runtime.block_on(count_chunked(&handle)));
+        eprintln!(
+            "peak memory for {} routes: {} KiB at once, {} KiB in chunks of {}",
+            routes,
+            at_once / 1024,
+            chunked / 1024,
+            CHUNK_SIZE
+        );
+
+        let mut group = c.benchmark_group("large table");
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(routes as u64));
+        group.bench_function("at once", |b| {
+            b.iter(|| runtime.block_on(handle.list()).unwrap().len())
+        });
+        group.bench_function("in chunks", |b| {
+            b.iter(|| runtime.block_on(count_chunked(&handle)))
+        });
+        group.bench_function("first chunk", |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let chunks = handle.list_chunks(&DumpFilter::default(), CHUNK_SIZE, None);
+                    futures::pin_mut!(chunks);
+                    chunks.next().await.map(|chunk| chunk.unwrap().routes.len())
+                })
+            })
+        });
+        group.finish();
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[global_allocator]
+static ALLOC: linux::PeakAlloc = linux::PeakAlloc;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+criterion::criterion_group!(benches, linux::large_table);
+#[cfg(any(target_os = "linux", target_os = "android"))]
+criterion::criterion_main!(benches);
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
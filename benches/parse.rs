@@ -0,0 +1,95 @@
+//! Converting dumped route messages, which dominates `Handle::list` on routers with full BGP
+//! tables. Run with `cargo bench --bench parse`.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use criterion::{black_box, Criterion, Throughput};
+    use net_route::Route;
+    use netlink_packet_route::{
+        route::{RouteAddress, RouteAttribute, RouteMessage, RouteNextHop},
+        AddressFamily,
+    };
+
+    // About the size of a full IPv4 BGP table.
+    const ROUTES: u32 = 1_000_000;
+
+    fn v4_table() -> Vec<RouteMessage> {
+        (0..ROUTES)
+            .map(|i| {
+                let mut msg = RouteMessage::default();
+                msg.header.address_family = AddressFamily::Inet;
+                msg.header.destination_prefix_length = 24;
+                msg.attributes = vec![
+                    RouteAttribute::Table(254),
+                    RouteAttribute::Priority(20),
+                    RouteAttribute::Destination(RouteAddress::Inet(Ipv4Addr::from(i << 8))),
+                    RouteAttribute::Gateway(RouteAddress::Inet(Ipv4Addr::new(10, 0, 0, 1))),
+                    RouteAttribute::Oif(2),
+                ];
+                msg
+            })
+            .collect()
+    }
+
+    fn v6_multipath_table() -> Vec<RouteMessage> {
+        (0..ROUTES / 10)
+            .map(|i| {
+                let mut msg = RouteMessage::default();
+                msg.header.address_family = AddressFamily::Inet6;
+                msg.header.destination_prefix_length = 48;
+                let hops = (1..=2)
+                    .map(|n| {
+                        let mut hop = RouteNextHop::default();
+                        hop.interface_index = n;
+                        hop.attributes = vec![RouteAttribute::Gateway(RouteAddress::Inet6(
+                            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, n as u16),
+                        ))];
+                        hop
+                    })
+                    .collect();
+                msg.attributes = vec![
+                    RouteAttribute::Table(254),
+                    RouteAttribute::Destination(RouteAddress::Inet6(Ipv6Addr::new(
+                        0x2001, 0xdb8, i as u16, 0, 0, 0, 0, 0,
+                    ))),
+                    RouteAttribute::MultiPath(hops),
+                ];
+                msg
+            })
+            .collect()
+    }
+
+    pub fn parse(c: &mut Criterion) {
+        let mut group = c.benchmark_group("parse");
+        group.sample_size(10);
+        for (name, table) in [("v4", v4_table()), ("v6 multipath", v6_multipath_table())] {
+            group.throughput(Throughput::Elements(table.len() as u64));
+            group.bench_function(format!("{} by reference", name), |b| {
+                b.iter(|| {
+                    for msg in &table {
+                        black_box(Route::try_from(black_box(msg)).ok());
+                    }
+                })
+            });
+            // what dumps did before, and what the owned conversion still costs
+            group.bench_function(format!("{} cloned", name), |b| {
+                b.iter(|| {
+                    for msg in &table {
+                        black_box(Route::try_from(black_box(msg.clone())).ok());
+                    }
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+criterion::criterion_group!(benches, linux::parse);
+#[cfg(any(target_os = "linux", target_os = "android"))]
+criterion::criterion_main!(benches);
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn main() {}
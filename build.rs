@@ -2,13 +2,18 @@ fn main() {
     // detect docs rs builder so we don't try to link to macos libs while cross compiling
     let docs_builder = std::env::var("DOCS_RS").is_ok();
     let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
-    
-    if target_os == "macos" && !docs_builder {
-        build_macos();
+
+    // the BSDs share the routing socket interface of macOS
+    if matches!(
+        target_os.as_str(),
+        "macos" | "freebsd" | "netbsd" | "openbsd"
+    ) && !docs_builder
+    {
+        build_route_bindings();
     }
 }
 
-fn build_macos() {
+fn build_route_bindings() {
     use std::env;
     use std::path::PathBuf;
     // Tell cargo to look for shared libraries in the specified directory
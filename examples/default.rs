@@ -2,7 +2,7 @@
 use net_route::Handle;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> net_route::Result<()> {
     let handle = Handle::new()?;
 
     if let Some(route) = handle.default_route().await? {
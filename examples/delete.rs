@@ -1,7 +1,7 @@
 use net_route::{Route, Handle};
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> net_route::Result<()> {
     let handle = Handle::new()?;
     // warning: this may break network connecitivity
     let route = Route::new("192.168.2.0".parse().unwrap(), 26);
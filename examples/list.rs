@@ -1,7 +1,7 @@
 use net_route::Handle;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> net_route::Result<()> {
     let handle = Handle::new()?;
     let routes = handle.list().await?;
 
@@ -2,7 +2,7 @@ use futures::StreamExt;
 use net_route::Handle;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> net_route::Result<()> {
     let handle = Handle::new()?;
     let stream = handle.route_listen_stream();
 
@@ -1,10 +1,9 @@
 use std::net::{IpAddr, Ipv4Addr};
 
 use net_route::{Handle, Rule};
-use netlink_packet_route::rule::RuleAttribute;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> net_route::Result<()> {
     // list all rules on linux
     #[cfg(target_os = "linux")]
     {
@@ -21,11 +20,8 @@ async fn main() -> std::io::Result<()> {
         assert!(rules
             .iter()
             .find(|rule| {
-                rule.attributes
-                    .contains(&RuleAttribute::Destination(IpAddr::V4(Ipv4Addr::new(
-                        8, 8, 8, 8,
-                    ))))
-                    && rule.attributes.contains(&RuleAttribute::Table(2001))
+                rule.dst == Some((IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 32))
+                    && rule.table_id == Some(2001)
             })
             .is_some(),);
         handle.delete_rules(vec![rule.clone()]).await.unwrap();
@@ -0,0 +1,217 @@
+//! A command line tool to inspect and change the routing table, built with the `cli` feature.
+//!
+//! ```text
+//! net-route list [-4|-6] [--json]
+//! net-route get <address> [--json]
+//! net-route add <network> [via <gateway>] [dev <ifindex>] [table <id>] [metric <n>]
+//! net-route del <network> [via <gateway>] [dev <ifindex>] [table <id>] [metric <n>]
+//! net-route monitor [--json]
+//! net-route rules [--json]
+//! ```
+//!
+//! Networks are written as `address/prefix`, `default` stands for `0.0.0.0/0` and `default6`
+//! for `::/0`.
+
+use std::io::IsTerminal;
+use std::net::IpAddr;
+use std::process::ExitCode;
+
+use futures::StreamExt;
+use net_route::fmt::RouteTable;
+use net_route::{DumpFilter, Handle, IpVersion, Route, RouteChange};
+
+const USAGE: &str = "usage: net-route <list|get|add|del|monitor|rules> [args] [--json]";
+
+fn parse_network(network: &str) -> Result<(IpAddr, u8), String> {
+    match network {
+        "default" => return Ok((IpAddr::from([0u8; 4]), 0)),
+        "default6" => return Ok((IpAddr::from([0u8; 16]), 0)),
+        _ => {}
+    }
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (network, None),
+    };
+    let addr: IpAddr = addr
+        .parse()
+        .map_err(|_| format!("invalid address {}", addr))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(|| format!("invalid prefix length {}", prefix))?,
+        None => max,
+    };
+    Ok((addr, prefix))
+}
+
+fn parse_route(args: &[String]) -> Result<Route, String> {
+    let (network, mut rest) = args.split_first().ok_or("missing network")?;
+    let (destination, prefix) = parse_network(network)?;
+    let mut route = Route::new(destination, prefix);
+    while let [key, value, tail @ ..] = rest {
+        let number = || {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("invalid {} {}", key, value))
+        };
+        route = match key.as_str() {
+            "via" => route.with_gateway(
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid gateway {}", value))?,
+            ),
+            "dev" => route.with_ifindex(number()?),
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+            "table" => route.with_table(number()?),
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "linux",
+                target_os = "android",
+                target_os = "openbsd"
+            ))]
+            "metric" => route.with_metric(number()?),
+            _ => return Err(format!("unknown argument {}", key)),
+        };
+        rest = tail;
+    }
+    match rest {
+        [] => Ok(route),
+        [key] => Err(format!("missing value for {}", key)),
+        _ => unreachable!(),
+    }
+}
+
+fn route_text(route: &Route) -> String {
+    let mut text = format!("{}/{}", route.destination, route.prefix);
+    if let Some(gateway) = route.gateway {
+        text += &format!(" via {}", gateway);
+    }
+    if let Some(ifindex) = route.ifindex {
+        text += &format!(" dev {}", ifindex);
+    }
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        text += &format!(" table {}", route.table);
+    }
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    if let Some(metric) = route.metric {
+        text += &format!(" metric {}", metric);
+    }
+    text
+}
+
+fn print_routes(routes: &[Route], json: bool) {
+    if json {
+        let routes: Vec<_> = routes.iter().map(Route::to_json).collect();
+        println!("[{}]", routes.join(","));
+    } else {
+        let color = std::io::stdout().is_terminal();
+        print!("{}", RouteTable::new(routes).with_color(color));
+    }
+}
+
+async fn run(args: &[String], json: bool) -> Result<(), String> {
+    let handle = Handle::new().map_err(|e| e.to_string())?;
+    let (command, args) = args.split_first().ok_or(USAGE)?;
+    match (command.as_str(), args) {
+        ("list", args) => {
+            let mut filter = DumpFilter::new();
+            for arg in args {
+                filter.family = match arg.as_str() {
+                    "-4" => Some(IpVersion::V4),
+                    "-6" => Some(IpVersion::V6),
+                    _ => return Err(format!("unknown argument {}", arg)),
+                };
+            }
+            let routes = handle
+                .list_filtered(&filter)
+                .await
+                .map_err(|e| e.to_string())?;
+            print_routes(&routes, json);
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "fuchsia"
+        ))]
+        ("get", [destination]) => {
+            let destination = destination
+                .parse()
+                .map_err(|_| format!("invalid address {}", destination))?;
+            let route = handle
+                .get_route(destination)
+                .await
+                .map_err(|e| e.to_string())?;
+            print_routes(&[route], json);
+        }
+        ("add", args) => {
+            let route = parse_route(args)?;
+            handle.add(&route).await.map_err(|e| e.to_string())?;
+        }
+        ("del", args) => {
+            let route = parse_route(args)?;
+            handle.delete(&route).await.map_err(|e| e.to_string())?;
+        }
+        ("monitor", []) => {
+            let stream = handle.route_listen_stream();
+            futures::pin_mut!(stream);
+            while let Some(change) = stream.next().await {
+                if json {
+                    println!("{}", change.to_json());
+                    continue;
+                }
+                let (kind, route) = match &change {
+                    RouteChange::Add(route) => ("add", route),
+                    RouteChange::Delete(route) => ("delete", route),
+                    RouteChange::Change(route) => ("change", route),
+                };
+                println!("{} {}", kind, route_text(route));
+            }
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        ("rules", []) => {
+            let rules = handle.list_rules().await.map_err(|e| e.to_string())?;
+            if json {
+                let rules: Vec<_> = rules.iter().map(net_route::Rule::to_json).collect();
+                println!("[{}]", rules.join(","));
+            } else {
+                let color = std::io::stdout().is_terminal();
+                print!(
+                    "{}",
+                    net_route::fmt::RuleTable::new(&rules).with_color(color)
+                );
+            }
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+
+    match run(&args, json).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
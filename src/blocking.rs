@@ -0,0 +1,196 @@
+//! A synchronous interface for programs that don't use async, e.g. command line tools.
+//!
+//! ```no_run
+//! # fn main() -> net_route::Result<()> {
+//! let handle = net_route::blocking::Handle::new()?;
+//! for route in handle.list()? {
+//!     println!("{:?}", route);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+
+#[cfg(feature = "events")]
+use crate::RouteChange;
+use crate::{Capabilities, DumpFilter, HandleBuilder, ListCursor, Result, Route, RouteChunk};
+
+/// A [`Handle`](crate::Handle) whose operations block the calling thread.
+///
+/// With the `runtime-tokio` feature it owns a tokio runtime with a single worker thread that runs
+/// the background tasks, with `runtime-smol` they run on smol's executor and with only
+/// `runtime-thread` on threads of their own.
+pub struct Handle {
+    pub(crate) inner: crate::Handle,
+    #[cfg(feature = "runtime-tokio")]
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Handle {
+    pub fn new() -> Result<Self> {
+        Self::with_builder(&HandleBuilder::new())
+    }
+
+    /// Create the handle with the configuration of `builder`.
+    pub fn with_builder(builder: &HandleBuilder) -> Result<Self> {
+        #[cfg(feature = "runtime-tokio")]
+        {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("net-route")
+                .enable_all()
+                .build()?;
+            let inner = builder
+                .clone()
+                .with_runtime(runtime.handle().clone())
+                .build()?;
+            Ok(Self { inner, runtime })
+        }
+        #[cfg(not(feature = "runtime-tokio"))]
+        Ok(Self {
+            inner: builder.build()?,
+        })
+    }
+
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "runtime-tokio")]
+        return self.runtime.block_on(future);
+        #[cfg(all(not(feature = "runtime-tokio"), feature = "runtime-smol"))]
+        return smol::block_on(future);
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        return futures::executor::block_on(future);
+    }
+
+    /// See [`Handle::capabilities`](crate::Handle::capabilities).
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        self.inner.capabilities()
+    }
+
+    /// Add route to the system's routing table.
+    pub fn add(&self, route: &Route) -> Result<()> {
+        self.block_on(self.inner.add(route))
+    }
+
+    /// Add several routes with their requests pipelined, see
+    /// [`Handle::add_routes`](crate::Handle::add_routes).
+    pub fn add_routes(&self, routes: &[Route]) -> Result<()> {
+        self.block_on(self.inner.add_routes(routes))
+    }
+
+    /// Remove a route from the system's routing table, see
+    /// [`Handle::delete`](crate::Handle::delete).
+    pub fn delete(&self, route: &Route) -> Result<()> {
+        self.block_on(self.inner.delete(route))
+    }
+
+    /// Returns a `Vec<Route>` containing a list of both ipv4 and v6 routes on the system.
+    pub fn list(&self) -> Result<Vec<Route>> {
+        self.block_on(self.inner.list())
+    }
+
+    /// Returns a `Vec<Route>` containing the routes selected by `filter`.
+    pub fn list_filtered(&self, filter: &DumpFilter) -> Result<Vec<Route>> {
+        self.block_on(self.inner.list_filtered(filter))
+    }
+
+    /// Hand the routes selected by `filter` to `visit` one at a time, see
+    /// [`Handle::for_each_route`](crate::Handle::for_each_route).
+    pub fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.block_on(self.inner.for_each_route(filter, visit))
+    }
+
+    /// Returns an iterator over the routes selected by `filter` in chunks, see
+    /// [`Handle::list_chunks`](crate::Handle::list_chunks).
+    pub fn list_chunks(
+        &self,
+        filter: &DumpFilter,
+        chunk_size: usize,
+        resume: Option<ListCursor>,
+    ) -> RouteChunks<'_> {
+        RouteChunks {
+            handle: self,
+            stream: Box::pin(self.inner.list_chunks(filter, chunk_size, resume)),
+        }
+    }
+
+    /// Get one of the default routes on the system if there is at least one.
+    pub fn default_route(&self) -> Result<Option<Route>> {
+        self.block_on(self.inner.default_route())
+    }
+
+    /// Get the route the system would use to reach `destination`, see
+    /// [`Handle::get_route`](crate::Handle::get_route).
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia"
+    ))]
+    pub fn get_route(&self, destination: std::net::IpAddr) -> Result<Route> {
+        self.block_on(self.inner.get_route(destination))
+    }
+
+    /// Returns an iterator which blocks until the next route is added, removed, or changed in
+    /// the system's routing table. It ends when the handle is closed.
+    #[cfg(feature = "events")]
+    pub fn route_listen_iter(&self) -> RouteChanges<'_> {
+        RouteChanges {
+            handle: self,
+            stream: Box::pin(self.inner.route_listen_stream()),
+        }
+    }
+
+    /// Shut the handle down, see [`Handle::close`](crate::Handle::close).
+    pub fn close(self) -> Result<()> {
+        #[cfg(feature = "runtime-tokio")]
+        return self.runtime.block_on(self.inner.close());
+        #[cfg(all(not(feature = "runtime-tokio"), feature = "runtime-smol"))]
+        return smol::block_on(self.inner.close());
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        return futures::executor::block_on(self.inner.close());
+    }
+}
+
+/// The chunks of a listing, see [`Handle::list_chunks`].
+pub struct RouteChunks<'a> {
+    handle: &'a Handle,
+    stream: Pin<Box<dyn Stream<Item = Result<RouteChunk>> + Send>>,
+}
+
+impl Iterator for RouteChunks<'_> {
+    type Item = Result<RouteChunk>;
+
+    fn next(&mut self) -> Option<Result<RouteChunk>> {
+        self.handle.block_on(self.stream.next())
+    }
+}
+
+#[cfg(feature = "events")]
+/// The changes of the routing table, see [`Handle::route_listen_iter`].
+pub struct RouteChanges<'a> {
+    handle: &'a Handle,
+    stream: Pin<Box<dyn Stream<Item = RouteChange> + Send + 'a>>,
+}
+
+#[cfg(feature = "events")]
+impl Iterator for RouteChanges<'_> {
+    type Item = RouteChange;
+
+    fn next(&mut self) -> Option<RouteChange> {
+        self.handle.block_on(self.stream.next())
+    }
+}
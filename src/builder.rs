@@ -0,0 +1,354 @@
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::RouteProtocol;
+#[cfg(feature = "runtime-tokio")]
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "events")]
+use crate::RouteMonitor;
+use crate::{
+    journal::{Journal, JournalConfig},
+    platform_impl::PlatformHandle,
+    rate_limit::RateLimiter,
+    undo::UndoStack,
+    Handle, Result, RetryPolicy, RouteManager,
+};
+
+/// Configures a [`Handle`] before it is created, see [`Handle::builder`].
+#[derive(Debug, Clone)]
+pub struct HandleBuilder {
+    pub(crate) event_capacity: usize,
+    // whether route changes are received, see RouteManager
+    pub(crate) listen: bool,
+    pub(crate) journal: Option<JournalConfig>,
+    pub(crate) undo_limit: Option<usize>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) rate_limit: Option<u32>,
+    pub(crate) pipeline_depth: usize,
+    #[cfg(feature = "runtime-tokio")]
+    pub(crate) runtime: Option<tokio::runtime::Handle>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) multicast_groups: u32,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) strict_checking: bool,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) recv_buffer_size: Option<usize>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) send_buffer_size: Option<usize>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) owner_protocol: Option<RouteProtocol>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) ack: bool,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) echo: bool,
+    #[cfg(target_os = "windows")]
+    pub(crate) compartment: Option<u32>,
+    #[cfg(target_os = "openbsd")]
+    pub(crate) rdomain: Option<u32>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) netns: Option<PathBuf>,
+}
+
+impl Default for HandleBuilder {
+    fn default() -> Self {
+        Self {
+            event_capacity: 16,
+            listen: cfg!(feature = "events"),
+            journal: None,
+            undo_limit: None,
+            timeout: None,
+            retry: None,
+            rate_limit: None,
+            pipeline_depth: 64,
+            #[cfg(feature = "runtime-tokio")]
+            runtime: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            multicast_groups: libc::RTMGRP_IPV4_ROUTE as u32 | libc::RTMGRP_IPV6_ROUTE as u32,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            strict_checking: true,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            recv_buffer_size: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            send_buffer_size: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            owner_protocol: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ack: true,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            echo: false,
+            #[cfg(target_os = "windows")]
+            compartment: None,
+            #[cfg(target_os = "openbsd")]
+            rdomain: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            netns: None,
+        }
+    }
+}
+
+impl HandleBuilder {
+    /// Create a builder with the configuration used by [`Handle::new`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many route changes are buffered for each stream returned by
+    /// [`Handle::route_listen_stream`], 16 by default and at least 1. Streams that fall further
+    /// behind skip the oldest changes.
+    #[cfg(feature = "events")]
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    /// Run the background tasks of the handle on `runtime` instead of the runtime the handle is
+    /// created in, which allows creating it outside of a runtime.
+    #[cfg(feature = "runtime-tokio")]
+    pub fn with_runtime(mut self, runtime: tokio::runtime::Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Set the netlink multicast groups joined for route events, a mask of `RTMGRP_*` flags.
+    ///
+    /// The ipv4 and ipv6 route groups are joined by default, `0` disables route events. The groups
+    /// are only joined while there are streams of route events, a handle that is only used to
+    /// change routes doesn't receive the route events of the system.
+    #[cfg(all(feature = "events", any(target_os = "linux", target_os = "android")))]
+    pub fn with_multicast_groups(mut self, groups: u32) -> Self {
+        self.multicast_groups = groups;
+        self
+    }
+
+    /// Set whether strict checking of dump requests is enabled, see
+    /// [`Handle::set_strict_checking`]. Enabled by default when the running kernel supports it.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_strict_checking(mut self, enable: bool) -> Self {
+        self.strict_checking = enable;
+        self
+    }
+
+    /// Set the receive buffer size of the netlink socket, see [`Handle::set_recv_buffer_size`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the send buffer size of the netlink socket, see [`Handle::set_send_buffer_size`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Install every route added through the handle with `protocol`, overriding
+    /// `Route::protocol`.
+    ///
+    /// Routes tagged this way can be removed with [`Handle::cleanup_owned`], e.g. on startup
+    /// after a crash left routes behind. Pick a protocol number that isn't used by other daemons.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_owner_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.owner_protocol = Some(protocol);
+        self
+    }
+
+    /// Set whether requests adding or removing routes ask the kernel for an acknowledgement,
+    /// enabled by default.
+    ///
+    /// Without acknowledgements `add` and `delete` return as soon as the request is sent, which
+    /// speeds up installing large numbers of routes. Failures are lost: the kernel's answer to
+    /// a failed request is dropped, it is only logged with the `tracing` feature.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_ack(mut self, ack: bool) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    /// Set whether requests adding or removing routes ask the kernel to echo the resulting
+    /// change back, disabled by default. An echo also confirms the request was applied.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Operate on the routing table of the network compartment `compartment` instead of the
+    /// compartment of the calling thread, e.g. one created for a container.
+    ///
+    /// Listing, adding and removing routes as well as change notifications use this compartment.
+    #[cfg(target_os = "windows")]
+    pub fn with_compartment(mut self, compartment: u32) -> Self {
+        self.compartment = Some(compartment);
+        self
+    }
+
+    /// Operate on the routing domain `rdomain` instead of the one of the process, see
+    /// `rdomain(4)`. Creating the handle fails if the domain doesn't exist.
+    ///
+    /// Listing, adding and removing routes as well as change notifications use this domain.
+    #[cfg(target_os = "openbsd")]
+    pub fn with_rdomain(mut self, rdomain: u32) -> Self {
+        self.rdomain = Some(rdomain);
+        self
+    }
+
+    /// Operate on the routing table of the network namespace at `path` instead of the one of
+    /// the process, e.g. `/run/netns/<name>` for a namespace created by `ip netns add`.
+    ///
+    /// Only the netlink socket is opened in the namespace. Where the handle falls back to
+    /// `/proc/net/route`, the table of the namespace of the process is read.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_netns(mut self, path: impl Into<PathBuf>) -> Self {
+        self.netns = Some(path.into());
+        self
+    }
+
+    /// Keep the last `capacity` operations that change the routing table in memory, see
+    /// [`Handle::journal`]. Disabled by default.
+    pub fn with_journal(mut self, capacity: usize) -> Self {
+        let path = self.journal.and_then(|journal| journal.path);
+        self.journal = Some(JournalConfig { capacity, path });
+        self
+    }
+
+    /// Append every operation that changes the routing table to the file at `path` as a line
+    /// of text, in addition to the entries kept by [`HandleBuilder::with_journal`].
+    ///
+    /// The file is created if it doesn't exist. Failing to write it doesn't fail the operation.
+    pub fn with_journal_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let capacity = self.journal.map_or(0, |journal| journal.capacity);
+        self.journal = Some(JournalConfig {
+            capacity,
+            path: Some(path.into()),
+        });
+        self
+    }
+
+    /// Remember how to revert the last `limit` changes made through the handle, see
+    /// [`Handle::undo`]. Disabled by default.
+    pub fn with_undo(mut self, limit: usize) -> Self {
+        self.undo_limit = Some(limit);
+        self
+    }
+
+    /// Fail operations that don't finish within `timeout` with
+    /// [`Error::TimedOut`](crate::Error::TimedOut) instead of waiting forever, e.g. for a kernel
+    /// that stopped answering netlink requests. Disabled by default, see
+    /// [`Handle::with_timeout`] for overriding it per call.
+    ///
+    /// An operation that timed out may still have been applied.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry operations that fail with a transient error according to `policy`. Disabled by
+    /// default.
+    ///
+    /// The timeout set with [`HandleBuilder::with_timeout`] applies to every attempt.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Make at most `per_second` changes per second through the handle and its clones, spaced
+    /// evenly. Disabled by default.
+    ///
+    /// Installing tens of thousands of routes at full speed, e.g. with a transaction or
+    /// `Handle::add_split_routes`, can overflow the kernel's buffers, which fails requests with
+    /// `ENOBUFS` and drops route changes on the monitor socket. Every route counts as one
+    /// change, and so does every rule added or removed.
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limit = Some(per_second);
+        self
+    }
+
+    /// Set how many routes [`Handle::add_routes`] keeps in flight at once, 64 by default and at
+    /// least 1.
+    ///
+    /// The kernel queues an answer for every request on the netlink socket until it is read, so
+    /// a deep pipeline needs a receive buffer large enough to hold them, see
+    /// `HandleBuilder::with_recv_buffer_size` on Linux.
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        self.pipeline_depth = depth.max(1);
+        self
+    }
+
+    /// Create the handle.
+    ///
+    /// Unless a runtime is set with [`HandleBuilder::with_runtime`] this has to be called from
+    /// within a tokio runtime when using the `runtime-tokio` feature.
+    pub fn build(&self) -> Result<Handle> {
+        #[cfg(feature = "runtime-tokio")]
+        let runtime = match &self.runtime {
+            Some(runtime) => Some(runtime.clone()),
+            None => current_runtime()?,
+        };
+        #[cfg(feature = "runtime-tokio")]
+        let _runtime = runtime.as_ref().map(|runtime| runtime.enter());
+
+        let journal = match &self.journal {
+            Some(config) => Some(Arc::new(Journal::open(config)?)),
+            None => None,
+        };
+        Ok(Handle {
+            platform: Arc::new(PlatformHandle::new(self)?),
+            journal,
+            undo: self.undo_limit.map(|limit| Arc::new(UndoStack::new(limit))),
+            counters: Arc::default(),
+            timeout: self.timeout,
+            retry: self.retry,
+            rate_limit: self
+                .rate_limit
+                .map(|per_second| Arc::new(RateLimiter::new(per_second))),
+            pipeline_depth: self.pipeline_depth,
+            #[cfg(feature = "events")]
+            default_route: Arc::default(),
+            dropped: Default::default(),
+        })
+    }
+
+    /// Create a [`RouteManager`], which doesn't receive route changes.
+    pub fn build_manager(&self) -> Result<RouteManager> {
+        let config = Self {
+            listen: false,
+            ..self.clone()
+        };
+        Ok(RouteManager::from_handle(config.build()?))
+    }
+
+    /// Create a [`RouteMonitor`].
+    #[cfg(feature = "events")]
+    pub fn build_monitor(&self) -> Result<RouteMonitor> {
+        Ok(RouteMonitor::from_handle(self.build()?))
+    }
+}
+
+// The runtime the handle is created in. Only the Windows and Fuchsia backends work without one.
+#[cfg(feature = "runtime-tokio")]
+fn current_runtime() -> io::Result<Option<tokio::runtime::Handle>> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => Ok(Some(runtime)),
+        // only the backends built on the runtime module need one
+        Err(_)
+            if !cfg!(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "illumos",
+                target_os = "solaris"
+            )) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+    }
+}
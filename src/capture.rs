@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{Handle, Result, Route};
+
+// The two halves of the address space of `destination`'s ip version. Being more specific than
+// `/0` they win over every default route without having to remove it.
+pub(crate) fn halves(destination: IpAddr) -> [(IpAddr, u8); 2] {
+    match destination {
+        IpAddr::V4(_) => [
+            (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1),
+            (IpAddr::V4(Ipv4Addr::new(128, 0, 0, 0)), 1),
+        ],
+        IpAddr::V6(_) => [
+            (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 1),
+            (IpAddr::V6(Ipv6Addr::new(0x8000, 0, 0, 0, 0, 0, 0, 0)), 1),
+        ],
+    }
+}
+
+/// The `0.0.0.0/1` and `128.0.0.0/1` (or `::/1` and `8000::/1`) routes installed by
+/// [`Handle::capture_default_route`].
+///
+/// The routes stay installed until [`DefaultRouteCapture::release`] is called, dropping the
+/// capture leaves them in place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultRouteCapture {
+    routes: Vec<Route>,
+}
+
+impl DefaultRouteCapture {
+    /// The installed routes.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Remove the routes.
+    pub async fn release(self, handle: &Handle) -> Result<()> {
+        handle
+            .transaction()
+            .delete_routes(self.routes)
+            .commit()
+            .await
+    }
+}
+
+impl Handle {
+    /// Send all traffic through the gateway and interface of each of `routes` without touching
+    /// the existing default routes, e.g. for a VPN.
+    ///
+    /// Every route is installed as the two halves of the address space of its destination's ip
+    /// version, which are more specific than any default route. Pass one route per ip version,
+    /// its destination and prefix are ignored.
+    pub async fn capture_default_route(&self, routes: &[Route]) -> Result<DefaultRouteCapture> {
+        let routes: Vec<_> = routes
+            .iter()
+            .flat_map(|route| {
+                halves(route.destination).map(|(destination, prefix)| Route {
+                    destination,
+                    prefix,
+                    ..route.clone()
+                })
+            })
+            .collect();
+
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        Ok(DefaultRouteCapture { routes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_covers_the_address_space() {
+        let prefixes = crate::split_prefixes(&halves(IpAddr::V4(Ipv4Addr::LOCALHOST)), &[]);
+        assert_eq!(prefixes, [(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)]);
+
+        let prefixes = crate::split_prefixes(&halves(IpAddr::V6(Ipv6Addr::LOCALHOST)), &[]);
+        assert_eq!(prefixes, [(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)]);
+    }
+}
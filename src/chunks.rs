@@ -0,0 +1,81 @@
+use crate::Route;
+
+/// Where a chunked listing got to, see [`Handle::list_chunks`](crate::Handle::list_chunks).
+/// The default cursor starts at the beginning.
+///
+/// Resuming dumps the table again and skips the routes before the cursor without converting
+/// them. The kernel dumps a table in the same order every time, but routes added or removed in
+/// the meantime shift the others, so a resumed listing can miss or repeat routes near the cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ListCursor {
+    // the families are dumped one after the other, IPv4 first; elsewhere there is just one list
+    pub(crate) family: u8,
+    // the routes of the family already passed, whether they matched the filter or not
+    pub(crate) position: u64,
+}
+
+impl ListCursor {
+    // past the last family
+    pub(crate) const END: ListCursor = ListCursor {
+        family: u8::MAX,
+        position: 0,
+    };
+}
+
+/// Routes of a chunked listing together with the cursor after them, see
+/// [`Handle::list_chunks`](crate::Handle::list_chunks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteChunk {
+    pub routes: Vec<Route>,
+    /// Continues the listing after these routes.
+    pub cursor: ListCursor,
+}
+
+// Chunk a listing that was read at once, for the platforms that can't dump incrementally.
+#[cfg_attr(any(target_os = "linux", target_os = "android"), allow(dead_code))]
+pub(crate) fn split(routes: Vec<Route>, chunk_size: usize, resume: ListCursor) -> Vec<RouteChunk> {
+    if resume.family != 0 {
+        return vec![];
+    }
+    let skip = usize::try_from(resume.position).unwrap_or(usize::MAX);
+    let mut routes = routes.into_iter().skip(skip).peekable();
+    let mut position = resume.position;
+    let mut chunks = vec![];
+    while routes.peek().is_some() {
+        let chunk: Vec<Route> = routes.by_ref().take(chunk_size).collect();
+        position += chunk.len() as u64;
+        let cursor = match routes.peek() {
+            Some(_) => ListCursor {
+                family: 0,
+                position,
+            },
+            None => ListCursor::END,
+        };
+        chunks.push(RouteChunk {
+            routes: chunk,
+            cursor,
+        });
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_resumes_split_listings() {
+        let routes: Vec<Route> = (0..5u8)
+            .map(|i| Route::new([10, i, 0, 0].into(), 16))
+            .collect();
+
+        let chunks = split(routes.clone(), 2, ListCursor::default());
+        let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.routes.len()).collect();
+        assert_eq!(sizes, [2, 2, 1]);
+        assert_eq!(chunks[2].cursor, ListCursor::END);
+
+        let resumed = split(routes.clone(), 2, chunks[0].cursor);
+        assert_eq!(resumed[0].routes, routes[2..4]);
+        assert!(split(routes, 2, ListCursor::END).is_empty());
+    }
+}
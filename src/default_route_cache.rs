@@ -0,0 +1,123 @@
+use std::{future::Future, pin::Pin};
+
+use futures::{lock::Mutex, FutureExt, Stream, StreamExt};
+
+use crate::{Result, Route, RouteChange};
+
+pub(crate) type ChangeStream = Pin<Box<dyn Stream<Item = RouteChange> + Send>>;
+
+// The default route of a handle and its clones, looked up again after default routes changed,
+// see `Handle::cached_default_route`.
+#[derive(Default)]
+pub(crate) struct DefaultRouteCache {
+    // held while looking the route up, so concurrent callers wait for one lookup
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // subscribed on first use, before the first lookup
+    changes: Option<ChangeStream>,
+    // `None` until the first lookup and after an invalidation
+    route: Option<Option<Route>>,
+    // the events the handle's streams skipped at the last lookup
+    events_dropped: u64,
+}
+
+impl DefaultRouteCache {
+    pub(crate) async fn get<F>(
+        &self,
+        subscribe: impl FnOnce() -> ChangeStream,
+        events_dropped: u64,
+        lookup: impl FnOnce() -> F,
+    ) -> Result<Option<Route>>
+    where
+        F: Future<Output = Result<Option<Route>>>,
+    {
+        let mut state = self.state.lock().await;
+        let mut stale = state.drain(subscribe);
+        // a skipped event might have been a change of the default route
+        if events_dropped != state.events_dropped {
+            state.events_dropped = events_dropped;
+            stale = true;
+        }
+        if stale {
+            state.route = None;
+        }
+        if let Some(route) = &state.route {
+            return Ok(route.clone());
+        }
+
+        let route = lookup().await?;
+        // without a stream nothing would invalidate the route
+        if state.changes.is_some() {
+            state.route = Some(route.clone());
+        }
+        Ok(route)
+    }
+}
+
+impl State {
+    // Consume the changes received since the last call, returning whether one of them concerned
+    // a default route.
+    fn drain(&mut self, subscribe: impl FnOnce() -> ChangeStream) -> bool {
+        let changes = self.changes.get_or_insert_with(subscribe);
+        let mut stale = false;
+        loop {
+            match changes.next().now_or_never() {
+                Some(Some(RouteChange::Add(route)))
+                | Some(Some(RouteChange::Delete(route)))
+                | Some(Some(RouteChange::Change(route))) => stale |= route.prefix == 0,
+                // the handle was closed, subscribe again next time
+                Some(None) => {
+                    self.changes = None;
+                    return true;
+                }
+                None => return stale,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use futures::{channel::mpsc, executor::block_on};
+
+    use super::*;
+
+    #[test]
+    fn it_looks_up_again_after_default_route_changes() {
+        block_on(async {
+            let (tx, rx) = mpsc::unbounded();
+            let mut rx = Some(rx);
+            let mut subscribe = || -> ChangeStream { Box::pin(rx.take().unwrap()) };
+            let lookups = &AtomicU32::new(0);
+            let lookup = move || async move {
+                lookups.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(Route::new("0.0.0.0".parse().unwrap(), 0)))
+            };
+            let cache = DefaultRouteCache::default();
+
+            cache.get(&mut subscribe, 0, lookup).await.unwrap();
+            cache.get(&mut subscribe, 0, lookup).await.unwrap();
+            assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+            // other routes don't invalidate the cache
+            let other = Route::new("10.0.0.0".parse().unwrap(), 8);
+            tx.unbounded_send(RouteChange::Add(other)).unwrap();
+            cache.get(&mut subscribe, 0, lookup).await.unwrap();
+            assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+            let default = Route::new("0.0.0.0".parse().unwrap(), 0).with_ifindex(7);
+            tx.unbounded_send(RouteChange::Delete(default)).unwrap();
+            cache.get(&mut subscribe, 0, lookup).await.unwrap();
+            assert_eq!(lookups.load(Ordering::Relaxed), 2);
+
+            // skipped events might have changed it
+            cache.get(&mut subscribe, 1, lookup).await.unwrap();
+            assert_eq!(lookups.load(Ordering::Relaxed), 3);
+        });
+    }
+}
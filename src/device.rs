@@ -0,0 +1,179 @@
+use std::net::IpAddr;
+
+use crate::{Error, Handle, Result, Route};
+
+/// Options of [`Handle::add_routes_for_device`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeviceRouteOptions {
+    /// The metric of the routes, the platform's default if `None`.
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    pub metric: Option<u32>,
+
+    /// The table the routes are installed in, the main table if `None`.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub table: Option<u32>,
+}
+
+impl DeviceRouteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install the routes with `metric`.
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    pub fn with_metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    /// Install the routes in `table`.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+}
+
+// the index of the interface named `ifname`
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+fn if_nametoindex(ifname: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(ifname).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        ifindex => Some(ifindex),
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+fn no_such_device(ifname: &str) -> Error {
+    Error::InvalidRoute(format!("no interface named {}", ifname))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn device_index(ifname: &str) -> Result<u32> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+    ))]
+    return if_nametoindex(ifname).ok_or_else(|| no_such_device(ifname));
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+    )))]
+    return Err(Error::Unsupported(format!(
+        "looking up the interface {} isn't supported on this platform",
+        ifname
+    )));
+}
+
+impl Handle {
+    /// Route `prefixes` into the interface named `ifname`, e.g. a freshly created tun device.
+    ///
+    /// The routes have no gateway and are on-link: with link scope on Linux, bound to the
+    /// interface name on macOS so they survive the `utun` index changing, and through the
+    /// adapter with the friendly name `ifname` on Windows. The added routes are returned to
+    /// remove them later.
+    pub async fn add_routes_for_device(
+        &self,
+        ifname: &str,
+        prefixes: &[(IpAddr, u8)],
+        options: &DeviceRouteOptions,
+    ) -> Result<Vec<Route>> {
+        #[cfg(target_os = "windows")]
+        let interface = self
+            .list_interfaces()
+            .await?
+            .into_iter()
+            .find(|interface| interface.alias == ifname)
+            .ok_or_else(|| no_such_device(ifname))?;
+        // the name is resolved when the routes are added
+        #[cfg(target_os = "macos")]
+        device_index(ifname)?;
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let ifindex = device_index(ifname)?;
+        // not every platform has options
+        let _ = options;
+
+        let routes: Vec<_> = prefixes
+            .iter()
+            .map(|(destination, prefix)| {
+                let route = Route::new(*destination, *prefix);
+                #[cfg(target_os = "windows")]
+                let route = route.with_interface(&interface);
+                #[cfg(target_os = "macos")]
+                let route = route.with_ifname(ifname);
+                #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+                let route = route.with_ifindex(ifindex);
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                let route = route.with_scope(crate::RouteScope::Link);
+                #[cfg(any(
+                    target_os = "windows",
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "openbsd"
+                ))]
+                let route = match options.metric {
+                    Some(metric) => route.with_metric(metric),
+                    None => route,
+                };
+                #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+                let route = match options.table {
+                    Some(table) => route.with_table(table),
+                    None => route,
+                };
+                route
+            })
+            .collect();
+
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        Ok(routes)
+    }
+}
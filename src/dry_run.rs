@@ -0,0 +1,80 @@
+use crate::{Handle, Result, Route};
+
+/// What an [`Operation`] does to the routing table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Add,
+    Delete,
+}
+
+/// A change to the routing table computed by a [`DryRun`] without applying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub action: Action,
+    pub route: Route,
+
+    /// The netlink request that would be sent, header included. The sequence number is left at 0,
+    /// it is assigned when the request is sent.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub message: Vec<u8>,
+}
+
+/// Computes the operations of changes to the routing table without touching it, created by
+/// [`Handle::dry_run`].
+///
+/// On Linux the requests are encoded exactly like they are sent, with the handle's owner protocol
+/// and ack settings, and routes that can't be encoded fail the same way. Whether the system would
+/// accept the change, e.g. because the route already exists, isn't checked.
+///
+/// ```no_run
+/// # fn main() -> net_route::Result<()> {
+/// let handle = net_route::Handle::new()?;
+/// let route = net_route::Route::new("10.14.0.0".parse().unwrap(), 24).with_ifindex(9);
+/// for op in handle.dry_run().add(&route)? {
+///     println!("{:?} {:?}", op.action, op.route);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct DryRun<'a> {
+    #[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+    handle: &'a Handle,
+}
+
+impl<'a> DryRun<'a> {
+    pub(crate) fn new(handle: &'a Handle) -> Self {
+        Self { handle }
+    }
+
+    fn operation(&self, action: Action, route: &Route) -> Result<Operation> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let message = match action {
+            Action::Add => self.handle.platform.encode_add(route)?,
+            Action::Delete => self.handle.platform.encode_delete(route)?,
+        };
+        Ok(Operation {
+            action,
+            route: route.clone(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            message,
+        })
+    }
+
+    /// The operations of [`Handle::add`].
+    pub fn add(&self, route: &Route) -> Result<Vec<Operation>> {
+        Ok(vec![self.operation(Action::Add, route)?])
+    }
+
+    /// The operations of [`Handle::delete`].
+    pub fn delete(&self, route: &Route) -> Result<Vec<Operation>> {
+        Ok(vec![self.operation(Action::Delete, route)?])
+    }
+
+    /// The operations replacing `from` by `to`, removing `from` first.
+    pub fn replace(&self, from: &Route, to: &Route) -> Result<Vec<Operation>> {
+        Ok(vec![
+            self.operation(Action::Delete, from)?,
+            self.operation(Action::Add, to)?,
+        ])
+    }
+}
@@ -0,0 +1,260 @@
+use std::{fmt, io};
+
+/// The error type of the operations of [`Handle`](crate::Handle).
+///
+/// The common failure causes have their own variants on every platform, everything else is
+/// kept as the error reported by the system. The variants for errors of the system carry it as
+/// their [`source`](std::error::Error::source), e.g. with the extended error message the Linux
+/// kernel attached. Converting into an `io::Error` keeps the kind and, for netlink errors, the
+/// errno.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The route or another entry the operation refers to doesn't exist.
+    NotFound(io::Error),
+
+    /// The route or rule is already installed.
+    AlreadyExists(io::Error),
+
+    /// The process isn't allowed to change the routing table, see
+    /// [`Handle::capabilities`](crate::Handle::capabilities).
+    PermissionDenied(io::Error),
+
+    /// The operation isn't supported on this platform or by the running system.
+    Unsupported(String),
+
+    /// The route was rejected as malformed, e.g. a gateway of another ip version than the
+    /// destination or a missing next hop. Other invalid arguments are [`Error::Os`].
+    InvalidRoute(String),
+
+    /// The operation didn't finish within the timeout of the handle, see
+    /// [`HandleBuilder::with_timeout`](crate::HandleBuilder::with_timeout).
+    TimedOut(io::Error),
+
+    /// The kernel answered a netlink request with this errno. Only returned on Linux and Android
+    /// for errors not covered by the variants above.
+    Netlink(i32, io::Error),
+
+    /// The routing table changed while it was being listed, so the listed entries might not be a
+    /// consistent snapshot. Listings are dumped again a few times before failing with this, only
+    /// [`Handle::for_each_route`](crate::Handle::for_each_route) returns it right away as the
+    /// routes were already visited. Only returned on Linux and Android.
+    DumpInterrupted,
+
+    /// Any other error reported by the system.
+    Os(io::Error),
+
+    /// `source` occurred while applying `operation` to `entry`, the [`Display`](fmt::Display)
+    /// form of the route or rule. Returned by operations that change several routes or rules,
+    /// e.g. [`Transaction::commit`](crate::Transaction::commit), to tell which entry failed.
+    ///
+    /// Use [`Error::root`] to match on the error without the context. Converting it into an
+    /// `io::Error` keeps the kind of `source`.
+    Context {
+        operation: &'static str,
+        entry: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    pub(crate) fn context(self, operation: &'static str, entry: impl fmt::Display) -> Self {
+        Error::Context {
+            operation,
+            entry: entry.to_string(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The error without the context added by [`Error::Context`].
+    ///
+    /// ```
+    /// # use net_route::Error;
+    /// fn is_conflict(e: &Error) -> bool {
+    ///     matches!(e.root(), Error::AlreadyExists(_))
+    /// }
+    /// ```
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+}
+
+// Carries `Error::DumpInterrupted` through an `io::Error`.
+#[derive(Debug)]
+struct DumpInterrupted;
+
+impl fmt::Display for DumpInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the routing table changed while it was dumped")
+    }
+}
+
+impl std::error::Error for DumpInterrupted {}
+
+// Carries `Error::InvalidRoute` through an `io::Error`, so only errors of the checks of a route
+// become it and not every `InvalidInput`.
+#[derive(Debug)]
+struct InvalidRoute(String);
+
+impl fmt::Display for InvalidRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidRoute {}
+
+/// The error of a route rejected before it was handed to the system.
+pub(crate) fn invalid_route(reason: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, InvalidRoute(reason.into()))
+}
+
+// Appends the message of the system error `e` if it has one besides its kind.
+fn detail(f: &mut fmt::Formatter<'_>, e: &io::Error) -> fmt::Result {
+    match e.get_ref() {
+        Some(message) => write!(f, ": {}", message),
+        None => Ok(()),
+    }
+}
+
+// Whether `e` is a converted `Error::DumpInterrupted`.
+pub(crate) fn is_dump_interrupted(e: &io::Error) -> bool {
+    e.get_ref().map_or(false, |e| e.is::<DumpInterrupted>())
+}
+
+/// A `Result` with [`Error`] as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(e) => write!(f, "not found").and_then(|_| detail(f, e)),
+            Error::AlreadyExists(e) => write!(f, "already exists").and_then(|_| detail(f, e)),
+            Error::PermissionDenied(e) => write!(f, "permission denied").and_then(|_| detail(f, e)),
+            Error::Unsupported(reason) => write!(f, "unsupported: {}", reason),
+            Error::InvalidRoute(reason) => write!(f, "invalid route: {}", reason),
+            Error::TimedOut(e) => write!(f, "timed out").and_then(|_| detail(f, e)),
+            Error::DumpInterrupted => DumpInterrupted.fmt(f),
+            Error::Netlink(_, e) => write!(f, "netlink request failed: {}", e),
+            Error::Os(e) => e.fmt(f),
+            Error::Context {
+                operation,
+                entry,
+                source,
+            } => write!(f, "{} {}: {}", operation, entry, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NotFound(e)
+            | Error::AlreadyExists(e)
+            | Error::PermissionDenied(e)
+            | Error::TimedOut(e)
+            | Error::Netlink(_, e)
+            | Error::Os(e) => Some(e),
+            Error::Context { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(e),
+            io::ErrorKind::AlreadyExists => Error::AlreadyExists(e),
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(e),
+            io::ErrorKind::Unsupported => Error::Unsupported(e.to_string()),
+            io::ErrorKind::InvalidInput
+                if e.get_ref().map_or(false, |e| e.is::<InvalidRoute>()) =>
+            {
+                Error::InvalidRoute(e.to_string())
+            }
+            io::ErrorKind::TimedOut => Error::TimedOut(e),
+            _ if is_dump_interrupted(&e) => Error::DumpInterrupted,
+            _ => {
+                #[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
+                if let Some(errno) = crate::platform_impl::netlink_errno(&e) {
+                    return Error::Netlink(errno, e);
+                }
+                Error::Os(e)
+            }
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotFound(e) | Error::AlreadyExists(e) | Error::TimedOut(e) => e,
+            #[cfg(target_os = "windows")]
+            Error::PermissionDenied(_) => {
+                io::Error::new(io::ErrorKind::PermissionDenied, crate::ElevationRequired)
+            }
+            #[cfg(not(target_os = "windows"))]
+            Error::PermissionDenied(e) => e,
+            Error::Unsupported(reason) => io::Error::new(io::ErrorKind::Unsupported, reason),
+            Error::InvalidRoute(reason) => invalid_route(reason),
+            Error::DumpInterrupted => io::Error::new(io::ErrorKind::Interrupted, DumpInterrupted),
+            Error::Netlink(errno, _) => io::Error::from_raw_os_error(errno),
+            Error::Os(e) => e,
+            Error::Context {
+                operation,
+                entry,
+                source,
+            } => {
+                let source = io::Error::from(*source);
+                io::Error::new(
+                    source.kind(),
+                    format!("{} {}: {}", operation, entry, source),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_error_kinds() {
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such route"));
+        assert!(matches!(e, Error::NotFound(_)));
+        assert_eq!(e.to_string(), "not found: no such route");
+        assert!(std::error::Error::source(&e).is_some());
+
+        let e = Error::from(invalid_route("bad gateway"));
+        assert!(matches!(&e, Error::InvalidRoute(reason) if reason == "bad gateway"));
+        let e = io::Error::from(e);
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(Error::from(e), Error::InvalidRoute(_)));
+
+        let e = Error::from(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer size too large",
+        ));
+        assert!(matches!(e, Error::Os(_)));
+
+        let e = Error::from(io::Error::new(io::ErrorKind::Other, "oops"));
+        assert!(matches!(e, Error::Os(_)));
+
+        let e = Error::from(io::Error::from(Error::DumpInterrupted));
+        assert!(matches!(e, Error::DumpInterrupted));
+    }
+
+    #[test]
+    fn it_keeps_the_root_of_errors_with_context() {
+        let e = Error::AlreadyExists(io::ErrorKind::AlreadyExists.into())
+            .context("add route", "10.0.0.0/8 dev 3");
+        assert!(matches!(e.root(), Error::AlreadyExists(_)));
+        assert_eq!(e.to_string(), "add route 10.0.0.0/8 dev 3: already exists");
+        assert_eq!(io::Error::from(e).kind(), io::ErrorKind::AlreadyExists);
+    }
+}
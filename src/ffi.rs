@@ -0,0 +1,338 @@
+//! A C interface to the crate, for C, C++ and Go programs.
+//!
+//! The functions are built on [`blocking::Handle`](crate::blocking::Handle) and block the calling
+//! thread. Build the crate as a static or dynamic library with the `ffi` feature, e.g.
+//! `cargo rustc --release --features ffi --crate-type staticlib`, and generate the header with
+//! `cbindgen --lang c --crate net-route --output net_route.h`.
+//!
+//! Every function returns `NET_ROUTE_OK` or one of the negative `NET_ROUTE_ERR_*` codes. Handles
+//! and monitors must be released with [`net_route_handle_free`] and [`net_route_monitor_stop`].
+
+use std::ffi::c_void;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::{slice, thread};
+
+use futures::{channel::oneshot, StreamExt};
+
+use crate::{blocking, Error, Route, RouteChange};
+
+pub const NET_ROUTE_OK: i32 = 0;
+pub const NET_ROUTE_ERR_NOT_FOUND: i32 = -1;
+pub const NET_ROUTE_ERR_ALREADY_EXISTS: i32 = -2;
+pub const NET_ROUTE_ERR_PERMISSION_DENIED: i32 = -3;
+pub const NET_ROUTE_ERR_UNSUPPORTED: i32 = -4;
+pub const NET_ROUTE_ERR_INVALID_ROUTE: i32 = -5;
+/// Any other error reported by the system.
+pub const NET_ROUTE_ERR_OS: i32 = -6;
+/// A null pointer or an address family other than 4 and 6 was passed.
+pub const NET_ROUTE_ERR_INVALID_ARGUMENT: i32 = -7;
+/// The buffer passed to [`net_route_list`] can't hold every route.
+pub const NET_ROUTE_ERR_BUFFER_TOO_SMALL: i32 = -8;
+/// The operation didn't finish within the timeout of the handle.
+pub const NET_ROUTE_ERR_TIMED_OUT: i32 = -9;
+
+pub const NET_ROUTE_CHANGE_ADD: u8 = 1;
+pub const NET_ROUTE_CHANGE_DELETE: u8 = 2;
+pub const NET_ROUTE_CHANGE_CHANGE: u8 = 3;
+
+/// A handle to the routing table, created by [`net_route_handle_new`].
+pub struct NetRouteHandle(Arc<blocking::Handle>);
+
+/// A running subscription to route changes, created by [`net_route_monitor_start`].
+pub struct NetRouteMonitor {
+    stop: oneshot::Sender<()>,
+    thread: thread::JoinHandle<()>,
+}
+
+/// A route in the form of [`Route`] shared by every platform.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NetRouteRoute {
+    /// 4 or 6, the first 4 bytes of the addresses are used for ipv4.
+    pub family: u8,
+    pub destination: [u8; 16],
+    pub prefix: u8,
+    /// 4 or 6, 0 if the route has no gateway.
+    pub gateway_family: u8,
+    pub gateway: [u8; 16],
+    /// The index of the interface, 0 for none.
+    pub ifindex: u32,
+}
+
+/// Called on the monitor's thread for every change of the routing table with one of the
+/// `NET_ROUTE_CHANGE_*` constants. `route` is only valid during the call.
+pub type NetRouteChangeCallback =
+    extern "C" fn(change: u8, route: *const NetRouteRoute, context: *mut c_void);
+
+// the context pointer is handed back to the caller's callback, which is responsible for its use
+// from the monitor thread
+struct Context(*mut c_void);
+
+unsafe impl Send for Context {}
+
+fn error_code(e: Error) -> i32 {
+    match e.root() {
+        Error::NotFound(_) => NET_ROUTE_ERR_NOT_FOUND,
+        Error::AlreadyExists(_) => NET_ROUTE_ERR_ALREADY_EXISTS,
+        Error::PermissionDenied(_) => NET_ROUTE_ERR_PERMISSION_DENIED,
+        Error::Unsupported(_) => NET_ROUTE_ERR_UNSUPPORTED,
+        Error::InvalidRoute(_) => NET_ROUTE_ERR_INVALID_ROUTE,
+        Error::TimedOut(_) => NET_ROUTE_ERR_TIMED_OUT,
+        _ => NET_ROUTE_ERR_OS,
+    }
+}
+
+fn status(result: crate::Result<()>) -> i32 {
+    match result {
+        Ok(()) => NET_ROUTE_OK,
+        Err(e) => error_code(e),
+    }
+}
+
+fn address(family: u8, bytes: &[u8; 16]) -> Option<IpAddr> {
+    match family {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ))),
+        6 => Some(IpAddr::V6(Ipv6Addr::from(*bytes))),
+        _ => None,
+    }
+}
+
+fn address_bytes(addr: IpAddr) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    match addr {
+        IpAddr::V4(addr) => bytes[..4].copy_from_slice(&addr.octets()),
+        IpAddr::V6(addr) => bytes = addr.octets(),
+    }
+    bytes
+}
+
+impl From<&Route> for NetRouteRoute {
+    fn from(route: &Route) -> Self {
+        Self {
+            family: if route.destination.is_ipv4() { 4 } else { 6 },
+            destination: address_bytes(route.destination),
+            prefix: route.prefix,
+            gateway_family: match route.gateway {
+                Some(IpAddr::V4(_)) => 4,
+                Some(IpAddr::V6(_)) => 6,
+                None => 0,
+            },
+            gateway: route.gateway.map(address_bytes).unwrap_or_default(),
+            ifindex: route.ifindex.unwrap_or(0),
+        }
+    }
+}
+
+impl NetRouteRoute {
+    fn to_route(&self) -> Option<Route> {
+        let mut route = Route::new(address(self.family, &self.destination)?, self.prefix);
+        if self.gateway_family != 0 {
+            route = route.with_gateway(address(self.gateway_family, &self.gateway)?);
+        }
+        if self.ifindex != 0 {
+            route = route.with_ifindex(self.ifindex);
+        }
+        Some(route)
+    }
+}
+
+/// Create a handle and store it in `*handle`.
+///
+/// # Safety
+///
+/// `handle` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_handle_new(handle: *mut *mut NetRouteHandle) -> i32 {
+    if handle.is_null() {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    }
+    match blocking::Handle::new() {
+        Ok(inner) => {
+            *handle = Box::into_raw(Box::new(NetRouteHandle(Arc::new(inner))));
+            NET_ROUTE_OK
+        }
+        Err(e) => error_code(e),
+    }
+}
+
+/// Release a handle. Monitors started on it keep running until they are stopped.
+///
+/// # Safety
+///
+/// `handle` must be null or returned by [`net_route_handle_new`] and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_handle_free(handle: *mut NetRouteHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Copy the routes of the system into `routes`, which has room for `capacity` routes, and store
+/// their number in `*len`.
+///
+/// If the routes don't fit `NET_ROUTE_ERR_BUFFER_TOO_SMALL` is returned and `*len` is set to the
+/// number of routes, so the call can be retried with a larger buffer.
+///
+/// # Safety
+///
+/// `handle` must be a live handle, `routes` must be valid for `capacity` writes and `len` valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_list(
+    handle: *const NetRouteHandle,
+    routes: *mut NetRouteRoute,
+    capacity: usize,
+    len: *mut usize,
+) -> i32 {
+    let (Some(handle), false) = (handle.as_ref(), len.is_null()) else {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    };
+    let list = match handle.0.list() {
+        Ok(list) => list,
+        Err(e) => return error_code(e),
+    };
+    *len = list.len();
+    if list.len() > capacity {
+        return NET_ROUTE_ERR_BUFFER_TOO_SMALL;
+    }
+    if list.is_empty() {
+        return NET_ROUTE_OK;
+    }
+    if routes.is_null() {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    }
+    let out = slice::from_raw_parts_mut(routes, list.len());
+    for (out, route) in out.iter_mut().zip(&list) {
+        *out = route.into();
+    }
+    NET_ROUTE_OK
+}
+
+/// Add a route to the system's routing table.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `route` valid for reads.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_add(
+    handle: *const NetRouteHandle,
+    route: *const NetRouteRoute,
+) -> i32 {
+    let (Some(handle), Some(route)) = (handle.as_ref(), route.as_ref().and_then(|r| r.to_route()))
+    else {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    };
+    status(handle.0.add(&route))
+}
+
+/// Remove a route from the system's routing table.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `route` valid for reads.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_delete(
+    handle: *const NetRouteHandle,
+    route: *const NetRouteRoute,
+) -> i32 {
+    let (Some(handle), Some(route)) = (handle.as_ref(), route.as_ref().and_then(|r| r.to_route()))
+    else {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    };
+    status(handle.0.delete(&route))
+}
+
+/// Call `callback` with `context` for every change of the routing table until the monitor stored
+/// in `*monitor` is stopped.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `monitor` valid for writes. `context` is passed to
+/// `callback` on another thread.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_monitor_start(
+    handle: *const NetRouteHandle,
+    callback: NetRouteChangeCallback,
+    context: *mut c_void,
+    monitor: *mut *mut NetRouteMonitor,
+) -> i32 {
+    let (Some(handle), false) = (handle.as_ref(), monitor.is_null()) else {
+        return NET_ROUTE_ERR_INVALID_ARGUMENT;
+    };
+    let handle = handle.0.clone();
+    let context = Context(context);
+    let (stop, stopped) = oneshot::channel();
+    let thread = thread::Builder::new()
+        .name("net-route-monitor".into())
+        .spawn(move || {
+            // move the whole wrapper, the raw pointer alone isn't Send
+            let context = context;
+            let changes = handle.inner.route_listen_stream().take_until(stopped);
+            handle.block_on(async {
+                futures::pin_mut!(changes);
+                while let Some(change) = changes.next().await {
+                    let (kind, route) = match &change {
+                        RouteChange::Add(route) => (NET_ROUTE_CHANGE_ADD, route),
+                        RouteChange::Delete(route) => (NET_ROUTE_CHANGE_DELETE, route),
+                        RouteChange::Change(route) => (NET_ROUTE_CHANGE_CHANGE, route),
+                    };
+                    let route = NetRouteRoute::from(route);
+                    callback(kind, &route, context.0);
+                }
+            })
+        });
+    match thread {
+        Ok(thread) => {
+            *monitor = Box::into_raw(Box::new(NetRouteMonitor { stop, thread }));
+            NET_ROUTE_OK
+        }
+        Err(_) => NET_ROUTE_ERR_OS,
+    }
+}
+
+/// Stop a monitor and wait until its callback has returned for the last time. Must not be called
+/// from the callback.
+///
+/// # Safety
+///
+/// `monitor` must be null or returned by [`net_route_monitor_start`] and not stopped before.
+#[no_mangle]
+pub unsafe extern "C" fn net_route_monitor_stop(monitor: *mut NetRouteMonitor) {
+    if monitor.is_null() {
+        return;
+    }
+    let monitor = Box::from_raw(monitor);
+    _ = monitor.stop.send(());
+    _ = monitor.thread.join();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_routes() {
+        let route = Route::new("10.14.0.0".parse().unwrap(), 24)
+            .with_gateway("192.1.2.1".parse().unwrap())
+            .with_ifindex(9);
+        let c_route = NetRouteRoute::from(&route);
+        assert_eq!(c_route.family, 4);
+        assert_eq!(c_route.destination[..4], [10, 14, 0, 0]);
+        assert_eq!(c_route.gateway_family, 4);
+
+        let back = c_route.to_route().unwrap();
+        assert_eq!(back.destination, route.destination);
+        assert_eq!(back.prefix, 24);
+        assert_eq!(back.gateway, route.gateway);
+        assert_eq!(back.ifindex, Some(9));
+
+        let c_route = NetRouteRoute {
+            family: 5,
+            ..Default::default()
+        };
+        assert!(c_route.to_route().is_none());
+    }
+}
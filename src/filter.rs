@@ -0,0 +1,273 @@
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::{RouteProtocol, RouteScope};
+
+use std::net::IpAddr;
+
+use crate::Route;
+
+/// The ip version of a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    /// The ip version of `addr`.
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => IpVersion::V4,
+            IpAddr::V6(_) => IpVersion::V6,
+        }
+    }
+}
+
+/// Selects the routes returned by `Handle::list_filtered`.
+///
+/// On Linux the filter is encoded into the dump request so the kernel only sends matching routes,
+/// on other platforms the full table is read and filtered locally. On FreeBSD only the FIB
+/// selected by `table` is read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DumpFilter {
+    /// Only return routes of this ip version.
+    pub family: Option<IpVersion>,
+
+    /// Only return routes in this table.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub table: Option<u32>,
+
+    /// Only return routes going out through the interface with this index.
+    pub oif: Option<u32>,
+
+    /// Only return routes going out through the interface with this name.
+    #[cfg(target_os = "macos")]
+    pub oif_name: Option<String>,
+
+    /// Only return routes installed by this protocol.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub protocol: Option<RouteProtocol>,
+
+    /// Only return routes with this scope.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub scope: Option<RouteScope>,
+}
+
+impl DumpFilter {
+    /// Create a filter that matches every route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match routes of the given ip version.
+    pub fn with_family(mut self, family: IpVersion) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Only match routes in the given table.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Only match routes going out through the interface with the given index.
+    pub fn with_oif(mut self, oif: u32) -> Self {
+        self.oif = Some(oif);
+        self
+    }
+
+    /// Only match routes going out through the interface with the given name, e.g. `utun3`.
+    #[cfg(target_os = "macos")]
+    pub fn with_oif_name(mut self, name: impl Into<String>) -> Self {
+        self.oif_name = Some(name.into());
+        self
+    }
+
+    /// Only match routes installed by the given protocol.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Only match routes with the given scope.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_scope(mut self, scope: RouteScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Whether the filter wants routes of the given ip version.
+    pub fn includes(&self, family: IpVersion) -> bool {
+        self.family.map_or(true, |f| f == family)
+    }
+
+    /// Check whether `route` is selected by this filter.
+    pub fn matches(&self, route: &Route) -> bool {
+        if !self.includes(IpVersion::of(&route.destination)) {
+            return false;
+        }
+        if self.oif.is_some() && route.ifindex != self.oif {
+            return false;
+        }
+        #[cfg(target_os = "macos")]
+        if self.oif_name.is_some() && route.ifname != self.oif_name {
+            return false;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        if self.table.map_or(false, |table| route.table != table) {
+            return false;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if self.protocol.map_or(false, |p| route.protocol != p)
+                || self.scope.map_or(false, |scope| route.scope != scope)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The fields that have to be equal for a route to be removed by `Handle::delete_matching`.
+///
+/// The destination and prefix always have to match, every other field is ignored unless enabled.
+#[cfg(feature = "helpers")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RouteMatch {
+    /// Require the same gateway.
+    pub gateway: bool,
+
+    /// Require the same interface.
+    pub ifindex: bool,
+
+    /// Require the same metric.
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    pub metric: bool,
+
+    /// Require the same table.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub table: bool,
+}
+
+#[cfg(feature = "helpers")]
+impl RouteMatch {
+    /// Match routes by destination and prefix only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also require the gateway to match.
+    pub fn with_gateway(mut self) -> Self {
+        self.gateway = true;
+        self
+    }
+
+    /// Also require the interface to match.
+    pub fn with_ifindex(mut self) -> Self {
+        self.ifindex = true;
+        self
+    }
+
+    /// Also require the metric to match.
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    pub fn with_metric(mut self) -> Self {
+        self.metric = true;
+        self
+    }
+
+    /// Also require the table to match.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    pub fn with_table(mut self) -> Self {
+        self.table = true;
+        self
+    }
+
+    /// Check whether `candidate` matches `wanted` in the selected fields.
+    pub fn matches(&self, wanted: &Route, candidate: &Route) -> bool {
+        if wanted.destination != candidate.destination || wanted.prefix != candidate.prefix {
+            return false;
+        }
+        if self.gateway && wanted.gateway != candidate.gateway {
+            return false;
+        }
+        if self.ifindex {
+            // on macOS the wanted route may only name its interface
+            #[cfg(target_os = "macos")]
+            if wanted.ifindex.is_none() && wanted.ifname.is_some() {
+                if wanted.ifname != candidate.ifname {
+                    return false;
+                }
+            } else if wanted.ifindex != candidate.ifindex {
+                return false;
+            }
+            #[cfg(not(target_os = "macos"))]
+            if wanted.ifindex != candidate.ifindex {
+                return false;
+            }
+        }
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd"
+        ))]
+        if self.metric && wanted.metric != candidate.metric {
+            return false;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        if self.table && wanted.table != candidate.table {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_family_and_oif() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(3);
+
+        assert!(DumpFilter::new().matches(&route));
+        assert!(DumpFilter::new().with_family(IpVersion::V4).matches(&route));
+        assert!(!DumpFilter::new().with_family(IpVersion::V6).matches(&route));
+        assert!(DumpFilter::new().with_oif(3).matches(&route));
+        assert!(!DumpFilter::new().with_oif(4).matches(&route));
+    }
+
+    #[cfg(feature = "helpers")]
+    #[test]
+    fn it_matches_selected_fields() {
+        let wanted =
+            Route::new("10.0.0.0".parse().unwrap(), 8).with_gateway("192.168.1.1".parse().unwrap());
+        let candidate = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_gateway("192.168.1.2".parse().unwrap())
+            .with_ifindex(3);
+
+        assert!(RouteMatch::new().matches(&wanted, &candidate));
+        assert!(!RouteMatch::new()
+            .with_gateway()
+            .matches(&wanted, &candidate));
+        assert!(!RouteMatch::new()
+            .with_ifindex()
+            .matches(&wanted, &candidate));
+
+        let other_prefix = Route::new("10.0.0.0".parse().unwrap(), 16);
+        assert!(!RouteMatch::new().matches(&wanted, &other_prefix));
+    }
+}
@@ -0,0 +1,217 @@
+//! Aligned tables of routes and rules for terminal output.
+//!
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> net_route::Result<()> {
+//! let handle = net_route::Handle::new()?;
+//! let routes = handle.list().await?;
+//! print!("{}", net_route::fmt::RouteTable::new(&routes).with_color(true));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{Route, RouteKind};
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// Write `rows` below `headers` with every column padded to its widest cell. Colors are applied
+// after padding so the escape codes don't count towards the width.
+fn write_table(
+    f: &mut Formatter<'_>,
+    headers: &[&str],
+    rows: &[(Vec<String>, bool)],
+    color: bool,
+) -> fmt::Result {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for (row, _) in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut write_row = |cells: &[String], style: Option<&str>| -> fmt::Result {
+        let mut line = String::new();
+        for (i, (cell, width)) in cells.iter().zip(&widths).enumerate() {
+            if i + 1 == cells.len() {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        match style {
+            Some(style) if color => writeln!(f, "{}{}{}", style, line, RESET),
+            _ => writeln!(f, "{}", line),
+        }
+    };
+
+    let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    write_row(&headers, Some(BOLD))?;
+    for (row, highlight) in rows {
+        write_row(row, highlight.then_some(RED))?;
+    }
+    Ok(())
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".into(), |value| value.to_string())
+}
+
+fn kind_name(kind: RouteKind) -> &'static str {
+    match kind {
+        RouteKind::Unicast => "unicast",
+        RouteKind::Blackhole => "blackhole",
+        RouteKind::Unreachable => "unreachable",
+        RouteKind::Prohibit => "prohibit",
+        RouteKind::Other => "other",
+    }
+}
+
+/// A table of routes, one per line. Routes that drop packets are printed in red when colors are
+/// enabled.
+pub struct RouteTable<'a> {
+    routes: &'a [Route],
+    color: bool,
+}
+
+impl<'a> RouteTable<'a> {
+    pub fn new(routes: &'a [Route]) -> Self {
+        Self {
+            routes,
+            color: false,
+        }
+    }
+
+    /// Set whether the table is colored with ANSI escape codes, disabled by default.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Display for RouteTable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut headers = vec!["DESTINATION", "GATEWAY", "IFINDEX"];
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        headers.push("TABLE");
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd"
+        ))]
+        headers.push("METRIC");
+        headers.push("KIND");
+
+        let rows: Vec<_> = self
+            .routes
+            .iter()
+            .map(|route| {
+                let mut row = vec![
+                    format!("{}/{}", route.destination, route.prefix),
+                    or_dash(route.gateway),
+                    or_dash(route.ifindex),
+                ];
+                #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+                row.push(route.table.to_string());
+                #[cfg(any(
+                    target_os = "windows",
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "openbsd"
+                ))]
+                row.push(or_dash(route.metric));
+                row.push(kind_name(route.kind).into());
+                (row, !matches!(route.kind, RouteKind::Unicast))
+            })
+            .collect();
+        write_table(f, &headers, &rows, self.color)
+    }
+}
+
+/// A table of policy routing rules, one per line.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+pub struct RuleTable<'a> {
+    rules: &'a [crate::Rule],
+    color: bool,
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl<'a> RuleTable<'a> {
+    pub fn new(rules: &'a [crate::Rule]) -> Self {
+        Self {
+            rules,
+            color: false,
+        }
+    }
+
+    /// Set whether the header is printed in bold, disabled by default.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl Display for RuleTable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let network = |network: Option<(std::net::IpAddr, u8)>| {
+            network.map_or_else(
+                || "all".into(),
+                |(addr, prefix)| format!("{}/{}", addr, prefix),
+            )
+        };
+        let rows: Vec<_> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let row = vec![
+                    or_dash(rule.priority),
+                    network(rule.src),
+                    network(rule.dst),
+                    or_dash(rule.input_interface.as_deref()),
+                    or_dash(rule.output_interface.as_deref()),
+                    or_dash(
+                        rule.fw_mark_mask
+                            .map(|(mark, mask)| format!("{:#x}/{:#x}", mark, mask)),
+                    ),
+                    or_dash(rule.table_id),
+                ];
+                (row, false)
+            })
+            .collect();
+        write_table(
+            f,
+            &["PRIORITY", "FROM", "TO", "IIF", "OIF", "FWMARK", "TABLE"],
+            &rows,
+            self.color,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_aligns_columns() {
+        let routes = [
+            Route::new("10.14.0.0".parse().unwrap(), 24).with_gateway("192.1.2.1".parse().unwrap()),
+            Route::new("0.0.0.0".parse().unwrap(), 0).with_kind(RouteKind::Blackhole),
+        ];
+        let table = RouteTable::new(&routes).to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("DESTINATION   GATEWAY    IFINDEX"));
+        assert!(lines[1].starts_with("10.14.0.0/24  192.1.2.1  -"));
+        assert!(lines[2].starts_with("0.0.0.0/0     -          -"));
+        assert!(lines[2].ends_with("blackhole"));
+        assert!(!table.contains('\x1b'));
+
+        let table = RouteTable::new(&routes).with_color(true).to_string();
+        assert!(table.lines().nth(2).unwrap().starts_with(RED));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+use crate::Rule;
+use crate::{Error, Route};
+#[cfg(target_os = "windows")]
+use crate::{InterfaceForwarding, IpVersion};
+
+/// A change to the routing table recorded in the journal of a [`Handle`](crate::Handle).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalOp {
+    AddRoute(Route),
+    DeleteRoute(Route),
+    /// A route added to the table of the VRF device named `vrf`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    AddRouteInVrf {
+        route: Route,
+        vrf: String,
+    },
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    AddRules(Vec<Rule>),
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    DeleteRules(Vec<Rule>),
+    /// The routes tagged with the owner protocol were removed.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    CleanupOwned,
+    #[cfg(target_os = "windows")]
+    SetInterfaceMetric {
+        ifindex: u32,
+        version: IpVersion,
+        metric: Option<u32>,
+    },
+    #[cfg(target_os = "windows")]
+    SetInterfaceForwarding {
+        ifindex: u32,
+        version: IpVersion,
+        config: InterfaceForwarding,
+    },
+}
+
+/// An entry of the journal, see [`Handle::journal`](crate::Handle::journal).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// When the operation finished.
+    pub time: SystemTime,
+    pub op: JournalOp,
+    /// The error message if the operation failed.
+    pub result: Result<(), String>,
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(
+            f,
+            "{}.{:03} {:?} ",
+            time.as_secs(),
+            time.subsec_millis(),
+            self.op
+        )?;
+        match &self.result {
+            Ok(()) => write!(f, "ok"),
+            Err(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+// How many entries are kept in memory and the file they are appended to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JournalConfig {
+    pub(crate) capacity: usize,
+    pub(crate) path: Option<PathBuf>,
+}
+
+pub(crate) struct Journal {
+    capacity: usize,
+    entries: Mutex<VecDeque<JournalEntry>>,
+    file: Option<Mutex<File>>,
+}
+
+impl Journal {
+    pub(crate) fn open(config: &JournalConfig) -> io::Result<Self> {
+        let file = match &config.path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+        Ok(Self {
+            capacity: config.capacity,
+            entries: Mutex::new(VecDeque::with_capacity(config.capacity)),
+            file,
+        })
+    }
+
+    pub(crate) fn record<T>(&self, op: JournalOp, result: &Result<T, Error>) {
+        let entry = JournalEntry {
+            time: SystemTime::now(),
+            op,
+            result: match result {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            },
+        };
+        if let Some(file) = &self.file {
+            // the journal is for debugging, failing to write it mustn't fail the operation
+            let _ = writeln!(file.lock().unwrap(), "{}", entry);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub(crate) fn entries(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_latest_entries() {
+        let journal = Journal::open(&JournalConfig {
+            capacity: 2,
+            path: None,
+        })
+        .unwrap();
+        for prefix in [8, 16, 24] {
+            let route = Route::new("10.0.0.0".parse().unwrap(), prefix);
+            journal.record(JournalOp::AddRoute(route), &Ok(()));
+        }
+        journal.record::<()>(
+            JournalOp::DeleteRoute(Route::new("10.0.0.0".parse().unwrap(), 8)),
+            &Err(Error::NotFound(io::ErrorKind::NotFound.into())),
+        );
+
+        let entries = journal.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].op,
+            JournalOp::AddRoute(Route::new("10.0.0.0".parse().unwrap(), 24))
+        );
+        assert_eq!(entries[1].result, Err("not found".to_string()));
+        assert!(entries[1].to_string().ends_with("failed: not found"));
+    }
+}
@@ -0,0 +1,333 @@
+//! JSON encoding of routes, rules and route changes for machine-readable tooling.
+//!
+//! The schema is stable across releases and platforms: fields are only ever added, and fields a
+//! platform doesn't have are left out when encoding and ignored when decoding.
+//!
+//! A route is encoded as
+//!
+//! ```json
+//! {
+//!   "destination": "10.14.0.0",
+//!   "prefix": 24,
+//!   "gateway": "192.1.2.1",
+//!   "ifindex": 9,
+//!   "kind": "unicast",
+//!   "table": 254,
+//!   "metric": 100
+//! }
+//! ```
+//!
+//! where `gateway`, `ifindex` and `metric` may be `null`, `kind` is one of `unicast`,
+//! `blackhole`, `unreachable`, `prohibit` and `other`, `table` is only present on Linux, Android
+//! and FreeBSD and `metric` only on Linux, Android, Windows and OpenBSD.
+//!
+//! A route change is encoded as `{"kind": "add", "route": {...}}` with `add`, `delete` or
+//! `change` as the kind.
+//!
+//! A rule, only on Linux and Android, is encoded as
+//!
+//! ```json
+//! {
+//!   "src": null,
+//!   "dst": "8.8.8.8/32",
+//!   "input_interface": null,
+//!   "output_interface": null,
+//!   "table": 2001,
+//!   "priority": null,
+//!   "fw_mark": null,
+//!   "fw_mask": null,
+//!   "protocol": 1,
+//!   "suppress_prefixlength": null,
+//!   "v6": false
+//! }
+//! ```
+//!
+//! with networks as `address/prefix` and `protocol` as the IANA protocol number.
+
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Route, RouteChange, RouteKind};
+
+fn invalid(e: serde_json::Error) -> Error {
+    Error::InvalidRoute(e.to_string())
+}
+
+#[derive(Serialize, Deserialize)]
+struct RouteJson {
+    destination: IpAddr,
+    prefix: u8,
+    #[serde(default)]
+    gateway: Option<IpAddr>,
+    #[serde(default)]
+    ifindex: Option<u32>,
+    #[serde(default = "unicast")]
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    table: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metric: Option<Option<u32>>,
+}
+
+fn unicast() -> String {
+    "unicast".into()
+}
+
+fn kind_name(kind: RouteKind) -> &'static str {
+    match kind {
+        RouteKind::Unicast => "unicast",
+        RouteKind::Blackhole => "blackhole",
+        RouteKind::Unreachable => "unreachable",
+        RouteKind::Prohibit => "prohibit",
+        RouteKind::Other => "other",
+    }
+}
+
+fn kind_from_name(name: &str) -> Result<RouteKind> {
+    Ok(match name {
+        "unicast" => RouteKind::Unicast,
+        "blackhole" => RouteKind::Blackhole,
+        "unreachable" => RouteKind::Unreachable,
+        "prohibit" => RouteKind::Prohibit,
+        "other" => RouteKind::Other,
+        _ => return Err(Error::InvalidRoute(format!("unknown route kind {}", name))),
+    })
+}
+
+impl From<&Route> for RouteJson {
+    fn from(route: &Route) -> Self {
+        Self {
+            destination: route.destination,
+            prefix: route.prefix,
+            gateway: route.gateway,
+            ifindex: route.ifindex,
+            kind: kind_name(route.kind).into(),
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+            table: Some(route.table),
+            #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+            table: None,
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "linux",
+                target_os = "android",
+                target_os = "openbsd"
+            ))]
+            metric: Some(route.metric),
+            #[cfg(not(any(
+                target_os = "windows",
+                target_os = "linux",
+                target_os = "android",
+                target_os = "openbsd"
+            )))]
+            metric: None,
+        }
+    }
+}
+
+impl TryFrom<RouteJson> for Route {
+    type Error = Error;
+
+    fn try_from(json: RouteJson) -> Result<Self> {
+        let mut route = Route::new(json.destination, json.prefix);
+        route.gateway = json.gateway;
+        route.ifindex = json.ifindex;
+        route.kind = kind_from_name(&json.kind)?;
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        if let Some(table) = json.table {
+            route.table = table;
+        }
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd"
+        ))]
+        if let Some(metric) = json.metric {
+            route.metric = metric;
+        }
+        Ok(route)
+    }
+}
+
+impl Route {
+    /// Encode the route as JSON, see the [schema](crate::json).
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&RouteJson::from(self)).expect("routes are always valid JSON")
+    }
+
+    /// Decode a route encoded with [`Route::to_json`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str::<RouteJson>(json)
+            .map_err(invalid)?
+            .try_into()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RouteChangeJson {
+    kind: String,
+    route: RouteJson,
+}
+
+impl RouteChange {
+    /// Encode the change as JSON, see the [schema](crate::json).
+    pub fn to_json(&self) -> String {
+        let (kind, route) = match self {
+            RouteChange::Add(route) => ("add", route),
+            RouteChange::Delete(route) => ("delete", route),
+            RouteChange::Change(route) => ("change", route),
+        };
+        let json = RouteChangeJson {
+            kind: kind.into(),
+            route: route.into(),
+        };
+        serde_json::to_string(&json).expect("route changes are always valid JSON")
+    }
+
+    /// Decode a change encoded with [`RouteChange::to_json`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        let json: RouteChangeJson = serde_json::from_str(json).map_err(invalid)?;
+        let route = json.route.try_into()?;
+        Ok(match json.kind.as_str() {
+            "add" => RouteChange::Add(route),
+            "delete" => RouteChange::Delete(route),
+            "change" => RouteChange::Change(route),
+            kind => return Err(Error::InvalidRoute(format!("unknown change {}", kind))),
+        })
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+mod rule {
+    use std::net::IpAddr;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::invalid;
+    use crate::{Error, IpProtocol, Result, Rule};
+
+    #[derive(Serialize, Deserialize)]
+    struct RuleJson {
+        #[serde(default)]
+        src: Option<String>,
+        #[serde(default)]
+        dst: Option<String>,
+        #[serde(default)]
+        input_interface: Option<String>,
+        #[serde(default)]
+        output_interface: Option<String>,
+        #[serde(default)]
+        table: Option<u32>,
+        #[serde(default)]
+        priority: Option<u32>,
+        #[serde(default)]
+        fw_mark: Option<u32>,
+        #[serde(default)]
+        fw_mask: Option<u32>,
+        #[serde(default)]
+        protocol: Option<u8>,
+        #[serde(default)]
+        suppress_prefixlength: Option<u32>,
+        #[serde(default)]
+        v6: bool,
+    }
+
+    fn network((addr, prefix): (IpAddr, u8)) -> String {
+        format!("{}/{}", addr, prefix)
+    }
+
+    fn parse_network(network: &str) -> Result<(IpAddr, u8)> {
+        let invalid = || Error::InvalidRoute(format!("invalid network {}", network));
+        let (addr, prefix) = network.split_once('/').ok_or_else(invalid)?;
+        Ok((
+            addr.parse().map_err(|_| invalid())?,
+            prefix.parse().map_err(|_| invalid())?,
+        ))
+    }
+
+    impl Rule {
+        /// Encode the rule as JSON, see the [schema](crate::json).
+        pub fn to_json(&self) -> String {
+            let json = RuleJson {
+                src: self.src.map(network),
+                dst: self.dst.map(network),
+                input_interface: self.input_interface.clone(),
+                output_interface: self.output_interface.clone(),
+                table: self.table_id,
+                priority: self.priority,
+                fw_mark: self.fw_mark_mask.map(|(mark, _)| mark),
+                fw_mask: self.fw_mark_mask.map(|(_, mask)| mask),
+                protocol: self.protocol.map(IpProtocol::as_raw),
+                suppress_prefixlength: self.suppress_prefixlength,
+                v6: self.v6,
+            };
+            serde_json::to_string(&json).expect("rules are always valid JSON")
+        }
+
+        /// Decode a rule encoded with [`Rule::to_json`].
+        pub fn from_json(json: &str) -> Result<Self> {
+            let json: RuleJson = serde_json::from_str(json).map_err(invalid)?;
+            Ok(Rule {
+                src: json.src.as_deref().map(parse_network).transpose()?,
+                dst: json.dst.as_deref().map(parse_network).transpose()?,
+                input_interface: json.input_interface,
+                output_interface: json.output_interface,
+                table_id: json.table,
+                priority: json.priority,
+                fw_mark_mask: json
+                    .fw_mark
+                    .map(|mark| (mark, json.fw_mask.unwrap_or(u32::MAX))),
+                protocol: json.protocol.map(IpProtocol::from_raw),
+                suppress_prefixlength: json.suppress_prefixlength,
+                v6: json.v6,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_routes() {
+        let route = Route::new("10.14.0.0".parse().unwrap(), 24)
+            .with_gateway("192.1.2.1".parse().unwrap())
+            .with_ifindex(9)
+            .with_kind(RouteKind::Blackhole);
+        let json = route.to_json();
+        assert!(json.starts_with(
+            r#"{"destination":"10.14.0.0","prefix":24,"gateway":"192.1.2.1","ifindex":9,"kind":"blackhole""#
+        ));
+        assert_eq!(Route::from_json(&json).unwrap(), route);
+
+        let change = RouteChange::Delete(route);
+        assert_eq!(RouteChange::from_json(&change.to_json()).unwrap(), change);
+    }
+
+    #[test]
+    fn it_ignores_fields_of_other_platforms() {
+        let route = Route::from_json(
+            r#"{"destination":"::","prefix":0,"table":254,"metric":null,"luid":7}"#,
+        )
+        .unwrap();
+        assert_eq!(route.destination, "::".parse::<IpAddr>().unwrap());
+        assert_eq!(route.gateway, None);
+        assert_eq!(route.kind, RouteKind::Unicast);
+
+        assert!(Route::from_json(r#"{"destination":"::","prefix":0,"kind":"x"}"#).is_err());
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn it_round_trips_rules() {
+        let rule = crate::Rule {
+            dst: Some(("8.8.8.8".parse().unwrap(), 32)),
+            table_id: Some(2001),
+            protocol: Some(crate::IpProtocol::Icmp),
+            ..Default::default()
+        };
+        assert_eq!(crate::Rule::from_json(&rule.to_json()).unwrap(), rule);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    thread,
+};
+
+use crate::{capture::halves, split_prefixes, Handle, Result, Route};
+
+/// The catch-all routes installed by [`Handle::enable_kill_switch`], removed again when the kill
+/// switch is dropped or disabled.
+///
+/// Dropping removes the routes on a separate thread, so that the runtime driving the handle
+/// isn't blocked, and ignores errors. Use [`KillSwitch::disable`] to wait for the routes to be
+/// gone and learn about failures.
+pub struct KillSwitch {
+    handle: Handle,
+    routes: Vec<Route>,
+}
+
+impl KillSwitch {
+    /// The installed routes.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Remove the routes.
+    pub async fn disable(mut self) -> Result<()> {
+        let routes = mem::take(&mut self.routes);
+        self.handle
+            .transaction()
+            .delete_routes(routes)
+            .commit()
+            .await
+    }
+
+    /// Leave the routes installed, e.g. to keep traffic blocked after the program exits. The
+    /// routes are returned to remove them later.
+    pub fn keep(mut self) -> Vec<Route> {
+        mem::take(&mut self.routes)
+    }
+}
+
+// The prefixes of both ip versions not in `allow`, none shorter than `/1`.
+fn catch_all(allow: &[(IpAddr, u8)]) -> Vec<(IpAddr, u8)> {
+    let everything = [
+        (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    ];
+    split_prefixes(&everything, allow)
+        .into_iter()
+        .flat_map(|(destination, prefix)| match prefix {
+            0 => halves(destination).to_vec(),
+            _ => vec![(destination, prefix)],
+        })
+        .collect()
+}
+
+impl Drop for KillSwitch {
+    fn drop(&mut self) {
+        if self.routes.is_empty() {
+            return;
+        }
+        let handle = self.handle.clone();
+        let routes = mem::take(&mut self.routes);
+        let _ = thread::Builder::new()
+            .name("net-route-kill-switch".into())
+            .spawn(move || {
+                futures::executor::block_on(async {
+                    for route in &routes {
+                        let _ = handle.delete(route).await;
+                    }
+                })
+            });
+    }
+}
+
+impl Handle {
+    /// Install routes covering every address of both ip versions except `allow`, e.g. the local
+    /// network and the VPN server, with the kind, metric and other properties of `route`, the
+    /// routing half of a VPN kill switch.
+    ///
+    /// Pass a route with a kind that drops packets like `RouteKind::Blackhole` and, on platforms
+    /// with metrics, a high metric so that the routes of the tunnel for the same prefixes take
+    /// precedence while it is up. The destination and prefix of `route` are ignored, no prefix
+    /// is shorter than `/1` so the existing default routes can stay in place. Routes that drop
+    /// packets can't be added on Windows.
+    pub async fn enable_kill_switch(
+        &self,
+        route: &Route,
+        allow: &[(IpAddr, u8)],
+    ) -> Result<KillSwitch> {
+        let routes: Vec<_> = catch_all(allow)
+            .into_iter()
+            .map(|(destination, prefix)| Route {
+                destination,
+                prefix,
+                ..route.clone()
+            })
+            .collect();
+
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        Ok(KillSwitch {
+            handle: self.clone(),
+            routes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_leaves_out_allowed_prefixes() {
+        let prefixes = catch_all(&[]);
+        assert_eq!(prefixes.len(), 4);
+        assert!(prefixes.iter().all(|(_, prefix)| *prefix == 1));
+
+        let lan = (IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)), 16);
+        let prefixes = catch_all(&[lan]);
+        assert!(prefixes.contains(&(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1)));
+        assert!(prefixes.contains(&(IpAddr::V4(Ipv4Addr::new(192, 169, 0, 0)), 16)));
+        assert!(!prefixes.contains(&lan));
+        assert_eq!(
+            prefixes.iter().filter(|(addr, _)| addr.is_ipv6()).count(),
+            2
+        );
+    }
+}
@@ -10,7 +10,7 @@
 //! // route traffic destined for 10.14.0.0/24 to 192.1.2.1 using interface 9
 //! # use net_route::{Handle, Route};
 //! # #[tokio::main]
-//! # async fn main() -> std::io::Result<()> {
+//! # async fn main() -> net_route::Result<()> {
 //! let handle = Handle::new()?;
 //! let route = Route::new("10.14.0.0".parse().unwrap(), 24)
 //!     .with_ifindex(9)
@@ -24,7 +24,7 @@
 //! # use futures::StreamExt;
 //! # use net_route::Handle;
 //! # #[tokio::main]
-//! # async fn main() -> std::io::Result<()> {
+//! # async fn main() -> net_route::Result<()> {
 //! let handle = Handle::new()?;
 //! let stream = handle.route_listen_stream();
 //! futures::pin_mut!(stream);
@@ -36,64 +36,1030 @@
 //! ```
 
 use std::{
+    future::Future,
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::ControlFlow,
+    sync::Arc,
+    time::Duration,
 };
 
+#[cfg(all(feature = "blocking", not(target_os = "fuchsia")))]
+pub mod blocking;
+mod builder;
+#[cfg(feature = "helpers")]
+mod capture;
+mod chunks;
+#[cfg(feature = "events")]
+mod default_route_cache;
+#[cfg(feature = "helpers")]
+mod device;
+mod dry_run;
+mod error;
+#[cfg(all(feature = "ffi", not(target_os = "fuchsia")))]
+pub mod ffi;
+mod filter;
+#[cfg(feature = "helpers")]
+pub mod fmt;
+mod journal;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "helpers")]
+mod kill_switch;
+mod manager;
+mod metrics;
+#[cfg(all(feature = "uniffi", not(target_os = "fuchsia")))]
+pub mod mobile;
+#[cfg(feature = "test-util")]
+mod mock;
+#[cfg(feature = "events")]
+mod monitor;
+#[cfg(feature = "ipnetwork")]
+mod network;
+#[cfg(feature = "helpers")]
+mod plan;
 mod platform_impl;
-#[cfg(target_os = "linux")]
-use netlink_packet_route::IpProtocol;
+#[cfg(all(
+    feature = "helpers",
+    any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    )
+))]
+mod preempt;
+#[cfg(feature = "python")]
+mod python;
+mod rate_limit;
+#[cfg(all(feature = "helpers", feature = "events"))]
+mod reconciler;
+#[cfg(all(feature = "helpers", feature = "events"))]
+mod resolver;
+mod retry;
+#[cfg(feature = "helpers")]
+mod split;
+#[cfg(all(feature = "helpers", target_os = "windows"))]
+mod split_tunnel;
+mod timeout;
+#[cfg(feature = "helpers")]
+mod transaction;
+mod undo;
+#[cfg(feature = "events")]
+use default_route_cache::DefaultRouteCache;
 use platform_impl::PlatformHandle;
+use rate_limit::RateLimiter;
+
+#[cfg(all(feature = "uniffi", not(target_os = "fuchsia")))]
+uniffi::setup_scaffolding!();
+
+pub use builder::HandleBuilder;
+#[cfg(feature = "helpers")]
+pub use capture::DefaultRouteCapture;
+pub use chunks::{ListCursor, RouteChunk};
+#[cfg(feature = "helpers")]
+pub use device::DeviceRouteOptions;
+pub use dry_run::{Action, DryRun, Operation};
+pub use error::{Error, Result};
+#[cfg(feature = "helpers")]
+pub use filter::RouteMatch;
+pub use filter::{DumpFilter, IpVersion};
+use journal::Journal;
+pub use journal::{JournalEntry, JournalOp};
+#[cfg(feature = "helpers")]
+pub use kill_switch::KillSwitch;
+pub use manager::RouteManager;
+use metrics::Counters;
+pub use metrics::{ErrorCounts, Metrics};
+#[cfg(feature = "test-util")]
+pub use mock::{MockHandle, MockOperation};
+#[cfg(feature = "events")]
+pub use monitor::RouteMonitor;
+#[cfg(feature = "helpers")]
+pub use plan::{PlanOp, RoutePlan};
+#[cfg(all(
+    feature = "helpers",
+    any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    )
+))]
+pub use preempt::PreemptingRoute;
+#[cfg(all(feature = "helpers", feature = "events"))]
+pub use reconciler::Reconciler;
+#[cfg(all(feature = "helpers", feature = "events"))]
+pub use resolver::ResolverRoutes;
+pub use retry::RetryPolicy;
+#[cfg(feature = "helpers")]
+pub use split::split_prefixes;
+#[cfg(all(feature = "helpers", target_os = "windows"))]
+pub use split_tunnel::SplitTunnel;
+#[cfg(feature = "helpers")]
+pub use transaction::Transaction;
+use undo::UndoStack;
 
 #[cfg(all(target_os = "macos", not(doc)))]
 pub use platform_impl::ifname_to_index;
 
+/// The table netd keeps the routes of the network of the interface `ifindex` in, see
+/// `ROUTE_TABLE_OFFSET_FROM_INDEX` in netd's RouteController.
+#[cfg(target_os = "android")]
+fn netd_table(ifindex: u32) -> u32 {
+    ifindex + 1000
+}
+
 /// Handle that abstracts initialization and cleanup of resources needed to operate on the routing table.
-pub struct Handle(PlatformHandle);
+///
+/// It combines a [`RouteManager`] and a [`RouteMonitor`], use those when only one side is needed.
+///
+/// Clones share the connection and the background tasks, which are stopped once the last clone
+/// is dropped. The handle is configured with a [`HandleBuilder`] before it is created.
+#[derive(Clone)]
+pub struct Handle {
+    platform: Arc<PlatformHandle>,
+    journal: Option<Arc<Journal>>,
+    undo: Option<Arc<UndoStack>>,
+    counters: Arc<Counters>,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    rate_limit: Option<Arc<RateLimiter>>,
+    // how many routes `add_routes` keeps in flight
+    pipeline_depth: usize,
+    #[cfg(feature = "events")]
+    default_route: Arc<DefaultRouteCache>,
+    // the last field, so a dropped clone let go of `platform` before it notifies
+    dropped: DropNotifier,
+}
+
+// Wakes `Handle::close_when_released` whenever a clone of the handle is dropped.
+#[derive(Default)]
+struct DropNotifier(Arc<tokio::sync::Notify>);
+
+impl Clone for DropNotifier {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for DropNotifier {
+    fn drop(&mut self) {
+        // keeps a permit if `close_when_released` isn't waiting yet
+        self.0.notify_one();
+    }
+}
 
 impl Handle {
-    pub fn new() -> io::Result<Self> {
-        Ok(Self(PlatformHandle::new()?))
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Create a handle whose background tasks run on `runtime`, e.g. from a library that owns
+    /// the runtime or outside of any runtime.
+    #[cfg(feature = "runtime-tokio")]
+    pub fn new_on(runtime: tokio::runtime::Handle) -> Result<Self> {
+        Self::builder().with_runtime(runtime).build()
+    }
+
+    /// Configure a handle before creating it, e.g. to change the buffering of route events or
+    /// the netlink socket options.
+    ///
+    /// ```no_run
+    /// # use net_route::Handle;
+    /// # fn main() -> net_route::Result<()> {
+    /// let handle = Handle::builder().with_event_capacity(1024).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> HandleBuilder {
+        HandleBuilder::new()
+    }
+
+    // Add the finished operation to the journal and, if it succeeded, its inverse to the undo
+    // history, if they are enabled.
+    fn record<T>(&self, op: impl FnOnce() -> JournalOp, result: io::Result<T>) -> Result<T> {
+        let result = self.counted(result);
+        if self.journal.is_none() && self.undo.is_none() {
+            return result;
+        }
+        let op = op();
+        if let (Some(undo), Ok(_)) = (&self.undo, &result) {
+            undo.push(&op);
+        }
+        if let Some(journal) = &self.journal {
+            journal.record(op, &result);
+        }
+        result
+    }
+
+    // Run an operation of the platform handle with the timeout and retry policy of this handle.
+    async fn run<T, F>(&self, operation: impl Fn() -> F) -> io::Result<T>
+    where
+        F: Future<Output = io::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = timeout::timeout(self.timeout, operation()).await;
+            let transient = matches!(&result, Err(e) if retry::is_transient(e));
+            let delay = match self.retry {
+                Some(policy) if transient => policy.delay(attempt),
+                _ => None,
+            };
+            let Some(delay) = delay else {
+                return result;
+            };
+            #[cfg(feature = "tracing")]
+            if let Err(e) = &result {
+                tracing::debug!(error = %e, ?delay, "retrying after a transient error");
+            }
+            timeout::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Set the timeout of the operations of this handle, replacing the one set with
+    /// [`HandleBuilder::with_timeout`]. `None` lets operations run until they finish.
+    ///
+    /// Unlike the other `with_*` methods this works on clones too and only affects the returned
+    /// handle, which makes it suitable for overriding the timeout of a single call:
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # #[tokio::main]
+    /// # async fn main() -> net_route::Result<()> {
+    /// let handle = net_route::Handle::new()?;
+    /// let routes = handle
+    ///     .clone()
+    ///     .with_timeout(Some(Duration::from_secs(30)))
+    ///     .list()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Wait until the rate limit allows `n` more changes.
+    async fn throttle(&self, n: usize) {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire(n).await;
+        }
+    }
+
+    // Count the error of a finished operation.
+    fn counted<T>(&self, result: io::Result<T>) -> Result<T> {
+        result.map_err(|e| {
+            let e = Error::from(e);
+            self.counters.error(&e);
+            e
+        })
+    }
+
+    /// The counters of this handle and its clones, e.g. for exporting them from a long-running
+    /// daemon.
+    pub fn metrics(&self) -> Metrics {
+        self.counters.snapshot()
+    }
+
+    /// The operations that changed the routing table through this handle and its clones, oldest
+    /// first, with their outcome. Empty unless enabled with [`HandleBuilder::with_journal`].
+    pub fn journal(&self) -> Vec<JournalEntry> {
+        self.journal
+            .as_ref()
+            .map_or_else(Vec::new, |journal| journal.entries())
+    }
+
+    /// Remove every route in every table that is tagged with the protocol set by
+    /// [`HandleBuilder::with_owner_protocol`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn cleanup_owned(&self) -> Result<()> {
+        let result = self.run(|| self.platform.cleanup_owned()).await;
+        self.record(|| JournalOp::CleanupOwned, result)
+    }
+
+    /// Enable or disable strict checking of dump requests (`NETLINK_GET_STRICT_CHK`).
+    ///
+    /// With strict checking the kernel applies the filters of dump requests itself and rejects
+    /// malformed requests instead of silently returning everything. It is enabled by default
+    /// when the running kernel supports it.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_strict_checking(&self, enable: bool) -> Result<()> {
+        self.counted(self.platform.set_strict_checking(enable))
+    }
+
+    /// Report what the current process is allowed to do with the routing table, so callers can
+    /// ask for elevated privileges before an operation fails half way.
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        self.counted(self.platform.capabilities())
+    }
+
+    /// Set the receive buffer size of the netlink socket in bytes.
+    ///
+    /// The default buffer overflows under heavy route churn, in which case route events are lost.
+    /// When the process has `CAP_NET_ADMIN` the size may exceed `net.core.rmem_max`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<()> {
+        self.counted(self.platform.set_recv_buffer_size(size))
+    }
+
+    /// Set the send buffer size of the netlink socket in bytes.
+    ///
+    /// When the process has `CAP_NET_ADMIN` the size may exceed `net.core.wmem_max`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<()> {
+        self.counted(self.platform.set_send_buffer_size(size))
     }
 
     /// Add route to the system's routing table.
-    pub async fn add(&self, route: &Route) -> io::Result<()> {
-        self.0.add(route).await
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn add(&self, route: &Route) -> Result<()> {
+        self.throttle(1).await;
+        let result = self.run(|| self.platform.add(route)).await;
+        if result.is_ok() {
+            self.counters.route_added();
+        }
+        self.record(|| JournalOp::AddRoute(route.clone()), result)
+    }
+
+    /// Add several routes without waiting for each one to be confirmed before sending the next.
+    ///
+    /// Up to [`HandleBuilder::with_pipeline_depth`] routes are in flight at once. On Linux their
+    /// requests share the netlink connection and the answers are matched to them by sequence
+    /// number, so adding many routes takes a fraction of the round trips of calling
+    /// [`Handle::add`] for each.
+    ///
+    /// Unlike a [`Transaction`](crate::Transaction) nothing is rolled back: every route is tried
+    /// and the first failure is returned as an [`Error::Context`] naming the route. The
+    /// journal, the undo history and the rate limit treat every route as its own change.
+    ///
+    /// ```no_run
+    /// # use net_route::{Handle, Route};
+    /// # #[tokio::main]
+    /// # async fn main() -> net_route::Result<()> {
+    /// let handle = Handle::new()?;
+    /// let routes: Vec<_> = (0..=255u8)
+    ///     .map(|i| Route::new([10, i, 0, 0].into(), 16).with_ifindex(2))
+    ///     .collect();
+    /// handle.add_routes(&routes).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(routes = routes.len()), err)
+    )]
+    pub async fn add_routes(&self, routes: &[Route]) -> Result<()> {
+        use futures::StreamExt;
+
+        let mut results = futures::stream::iter(routes)
+            .map(|route| async move { (route, self.add(route).await) })
+            .buffered(self.pipeline_depth);
+        let mut failure = None;
+        while let Some((route, result)) = results.next().await {
+            if let Err(e) = result {
+                failure.get_or_insert_with(|| e.context("add route", route));
+            }
+        }
+        failure.map_or(Ok(()), Err)
     }
 
     /// Returns a `Stream` which will yield a `RouteChange` event whenever a route is added, removed, or changed from the system's routing table.
+    #[cfg(feature = "events")]
     pub fn route_listen_stream(&self) -> impl futures::Stream<Item = RouteChange> {
-        self.0.route_listen_stream()
+        self.platform.route_listen_stream(self.counters.clone())
+    }
+
+    /// Shut the handle down and wait for its background tasks to finish.
+    ///
+    /// Pending requests are completed before the connection to the system is closed, and the
+    /// streams returned by [`Handle::route_listen_stream`] end. Dropping the handle instead stops
+    /// the background tasks without waiting for them.
+    ///
+    /// Only the last clone can shut the handle down. Closing any other clone drops its reference
+    /// and fails, the connection stays open until the remaining clones are closed or dropped.
+    /// The helpers that keep working on their own, like a `KillSwitch`, `ResolverRoutes` or a
+    /// `Reconciler`, hold clones too, so this fails while any of them exists. Use
+    /// [`Handle::close_when_released`] to wait for them instead.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn close(self) -> Result<()> {
+        match Arc::try_unwrap(self.platform) {
+            Ok(handle) => Ok(handle.close().await?),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the handle is still shared by other clones",
+            )
+            .into()),
+        }
+    }
+
+    /// Like [`Handle::close`], but instead of failing while other clones exist, wait until they
+    /// are all closed or dropped and then shut the handle down, e.g. once the helpers holding
+    /// clones are released.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn close_when_released(self) -> Result<()> {
+        let mut platform = self.platform;
+        let dropped = self.dropped.0.clone();
+        loop {
+            match Arc::try_unwrap(platform) {
+                Ok(handle) => return Ok(handle.close().await?),
+                Err(shared) => {
+                    platform = shared;
+                    dropped.notified().await;
+                }
+            }
+        }
     }
 
     /// Returns a `Vec<Route>` containing a list of both ipv4 and v6 routes on the system.
-    pub async fn list(&self) -> io::Result<Vec<Route>> {
-        self.0.list().await
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list(&self) -> Result<Vec<Route>> {
+        self.counted(self.run(|| self.platform.list()).await)
+    }
+
+    /// Returns a `Vec<Route>` containing the routes selected by `filter`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_filtered(&self, filter: &DumpFilter) -> Result<Vec<Route>> {
+        self.counted(self.run(|| self.platform.list_filtered(filter)).await)
+    }
+
+    /// Hand the routes selected by `filter` to `visit` one at a time, without collecting them,
+    /// until it returns `ControlFlow::Break`.
+    ///
+    /// On Linux every route is handed over as soon as it was received, so memory stays flat even
+    /// for tables with millions of routes, and the rest of the dump is skipped without converting
+    /// it after breaking. Other platforms list the routes first.
+    ///
+    /// Failed dumps aren't retried, as `visit` would see the routes before the failure twice. For
+    /// the same reason a dump during which the table changed fails with
+    /// [`Error::DumpInterrupted`] after `visit` saw every route, where listing would dump again.
+    ///
+    /// ```no_run
+    /// # async fn example() -> net_route::Result<()> {
+    /// use std::ops::ControlFlow;
+    /// use net_route::{DumpFilter, Handle};
+    ///
+    /// let handle = Handle::new()?;
+    /// let mut count = 0;
+    /// handle
+    ///     .for_each_route(&DumpFilter::default(), |route| {
+    ///         count += route.nexthops.len().max(1);
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, visit), err)
+    )]
+    pub async fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> Result<()> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let visited = self.platform.for_each_route(filter, visit);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let visited = async move {
+            let mut visit = visit;
+            for route in self.platform.list_filtered(filter).await? {
+                if visit(&route).is_break() {
+                    break;
+                }
+            }
+            Ok(())
+        };
+        self.counted(timeout::timeout(self.timeout, visited).await)
+    }
+
+    /// Stream the routes selected by `filter` in chunks of at most `chunk_size` routes, for
+    /// tables too large to list at once, e.g. the full BGP tables of routers.
+    ///
+    /// On Linux the table is dumped on a netlink socket of the stream's own, which is only read as
+    /// fast as the chunks are consumed. Memory stays bounded by a chunk and the socket's receive
+    /// buffer however large the table is, and the handle's other operations don't wait for the
+    /// dump. Other platforms list the routes first and split them.
+    ///
+    /// Every chunk carries a [`ListCursor`] to pass as `resume` for continuing after it, e.g.
+    /// once the stream was dropped. The stream ends after its first error. Like
+    /// [`Handle::for_each_route`] dumps aren't retried, a family during which the table changed
+    /// is followed by [`Error::DumpInterrupted`].
+    ///
+    /// ```no_run
+    /// # async fn example() -> net_route::Result<()> {
+    /// use futures::StreamExt;
+    /// use net_route::{DumpFilter, Handle};
+    ///
+    /// let handle = Handle::new()?;
+    /// let chunks = handle.list_chunks(&DumpFilter::default(), 10_000, None);
+    /// futures::pin_mut!(chunks);
+    /// while let Some(chunk) = chunks.next().await {
+    ///     let chunk = chunk?;
+    ///     println!("{} routes", chunk.routes.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_chunks(
+        &self,
+        filter: &DumpFilter,
+        chunk_size: usize,
+        resume: Option<ListCursor>,
+    ) -> impl futures::Stream<Item = Result<RouteChunk>> + Send + 'static {
+        use futures::StreamExt;
+
+        let resume = resume.unwrap_or_default();
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let chunks = self
+            .platform
+            .list_chunks(filter.clone(), chunk_size, resume);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let chunks = {
+            // a clone of the handle rather than of the platform handle for `close_when_released`
+            let handle = self.clone();
+            let filter = filter.clone();
+            let listed = async move {
+                match handle.platform.list_filtered(&filter).await {
+                    Ok(routes) => chunks::split(routes, chunk_size.max(1), resume)
+                        .into_iter()
+                        .map(Ok)
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                }
+            };
+            futures::stream::once(listed).flat_map(futures::stream::iter)
+        };
+        let counters = self.counters.clone();
+        chunks.map(move |chunk| {
+            chunk.map_err(|e| {
+                let e = Error::from(e);
+                counters.error(&e);
+                e
+            })
+        })
+    }
+
+    /// Returns a `Vec<Route>` containing only the ipv4 and v6 routes installed by `protocol`,
+    /// e.g. `RouteProtocol::Static` to skip the connected routes generated by the kernel.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_by_protocol(&self, protocol: RouteProtocol) -> Result<Vec<Route>> {
+        self.counted(self.run(|| self.platform.list_by_protocol(protocol)).await)
+    }
+
+    /// Returns the VRF devices configured on the system together with the tables they are bound to.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_vrfs(&self) -> Result<Vec<Vrf>> {
+        self.counted(self.run(|| self.platform.list_vrfs()).await)
+    }
+
+    /// Returns the ipv4 and v6 routes in the table bound to the VRF device named `vrf`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_in_vrf(&self, vrf: &str) -> Result<Vec<Route>> {
+        self.counted(self.run(|| self.platform.list_in_vrf(vrf)).await)
+    }
+
+    /// Returns the routes that are re-created on boot, whether they are currently installed or not.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_persistent(&self) -> Result<Vec<Route>> {
+        self.counted(self.run(|| self.platform.list_persistent()).await)
+    }
+
+    /// Get the interface the system would send packets for `destination` through, without
+    /// looking up the full route.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn best_interface(&self, destination: IpAddr) -> Result<Interface> {
+        self.counted(self.run(|| self.platform.best_interface(destination)).await)
+    }
+
+    /// Get the interface with index `ifindex`.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn interface(&self, ifindex: u32) -> Result<Interface> {
+        self.counted(self.run(|| self.platform.interface(ifindex)).await)
+    }
+
+    /// Get the interface of the adapter with the given GUID, see [`Route::adapter_guid`].
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn interface_by_guid(&self, guid: u128) -> Result<Interface> {
+        self.counted(self.run(|| self.platform.interface_by_guid(guid)).await)
+    }
+
+    /// Returns the interfaces on the system, including ones that are down.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_interfaces(&self) -> Result<Vec<Interface>> {
+        self.counted(self.run(|| self.platform.list_interfaces()).await)
+    }
+
+    /// Get the metric of the interface `ifindex` for the given ip version.
+    ///
+    /// Windows ranks routes by the sum of the route and interface metric, so overriding a route
+    /// usually requires looking at both.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn interface_metric(&self, ifindex: u32, version: IpVersion) -> Result<u32> {
+        self.counted(
+            self.run(|| self.platform.interface_metric(ifindex, version))
+                .await,
+        )
+    }
+
+    // The setting of the interface metric as taken by `set_interface_metric`, to restore it
+    // after lowering the metric.
+    #[cfg(all(feature = "helpers", target_os = "windows"))]
+    pub(crate) async fn interface_metric_setting(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+    ) -> Result<Option<u32>> {
+        self.counted(
+            self.run(|| self.platform.interface_metric_setting(ifindex, version))
+                .await,
+        )
+    }
+
+    /// Set the metric of the interface `ifindex` for the given ip version, `None` lets the
+    /// system pick one based on the link speed again.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn set_interface_metric(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+        metric: Option<u32>,
+    ) -> Result<()> {
+        let result = self
+            .run(|| self.platform.set_interface_metric(ifindex, version, metric))
+            .await;
+        self.record(
+            || JournalOp::SetInterfaceMetric {
+                ifindex,
+                version,
+                metric,
+            },
+            result,
+        )
+    }
+
+    /// Get whether the interface `ifindex` forwards packets for the given ip version.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn interface_forwarding(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+    ) -> Result<InterfaceForwarding> {
+        self.counted(
+            self.run(|| self.platform.interface_forwarding(ifindex, version))
+                .await,
+        )
+    }
+
+    /// Set whether the interface `ifindex` forwards packets for the given ip version, e.g. to
+    /// route traffic of other hosts through a tunnel.
+    #[cfg(target_os = "windows")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn set_interface_forwarding(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+        config: &InterfaceForwarding,
+    ) -> Result<()> {
+        let result = self
+            .run(|| {
+                self.platform
+                    .set_interface_forwarding(ifindex, version, config)
+            })
+            .await;
+        self.record(
+            || JournalOp::SetInterfaceForwarding {
+                ifindex,
+                version,
+                config: *config,
+            },
+            result,
+        )
     }
 
     /// Get one of the default routes on the system if there is at least one.
-    pub async fn default_route(&self) -> io::Result<Option<Route>> {
-        self.0.default_route().await
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn default_route(&self) -> Result<Option<Route>> {
+        self.counted(self.run(|| self.platform.default_route()).await)
+    }
+
+    /// Like [`Handle::default_route`], but the route is only looked up again after a default
+    /// route was added, changed or removed, for hot paths like choosing the egress of every new
+    /// connection.
+    ///
+    /// The handle and its clones share the cache. The first call subscribes to the route changes
+    /// of the handle, the stream counts in [`Handle::metrics`] like any other. A route change
+    /// stream falling behind, see [`HandleBuilder::with_event_capacity`], also invalidates the
+    /// cache, as the skipped changes might have concerned a default route.
+    #[cfg(feature = "events")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn cached_default_route(&self) -> Result<Option<Route>> {
+        self.default_route
+            .get(
+                || Box::pin(self.route_listen_stream()),
+                self.counters.snapshot().events_dropped,
+                || self.default_route(),
+            )
+            .await
+    }
+
+    /// Add route to the table bound to the VRF device named `vrf`, ignoring `route.table`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn add_in_vrf(&self, route: &Route, vrf: &str) -> Result<()> {
+        self.throttle(1).await;
+        let result = self.run(|| self.platform.add_in_vrf(route, vrf)).await;
+        if result.is_ok() {
+            self.counters.route_added();
+        }
+        self.record(
+            || JournalOp::AddRouteInVrf {
+                route: route.clone(),
+                vrf: vrf.into(),
+            },
+            result,
+        )
+    }
+
+    /// Get the default route of the interface `ifindex`, even if it isn't the primary interface,
+    /// e.g. to learn the Wi-Fi gateway while Ethernet is in use.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn default_route_for_interface(&self, ifindex: u32) -> Result<Option<Route>> {
+        self.counted(
+            self.run(|| self.platform.default_route_for_interface(ifindex))
+                .await,
+        )
+    }
+
+    /// Get the primary interface for `version` and its router, as ordered by the network
+    /// services in System Settings and reported by SystemConfiguration.
+    ///
+    /// Prefer this over picking the default route with the lowest metric, routes on macOS don't
+    /// have one and the scoped default routes of every interface are listed alongside.
+    #[cfg(target_os = "macos")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn primary_interface(&self, version: IpVersion) -> Result<Option<PrimaryInterface>> {
+        self.counted(self.run(|| self.platform.primary_interface(version)).await)
+    }
+
+    /// Ask the kernel which route it would pick for a packet sent to `destination`, like
+    /// `ip route get <destination>` on Linux or `route get <destination>` on macOS, the BSDs and
+    /// illumos. On Fuchsia the netstack resolves the destination through fuchsia.net.routes.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia"
+    ))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn get_route(&self, destination: IpAddr) -> Result<Route> {
+        self.counted(self.run(|| self.platform.get_route(destination)).await)
+    }
+
+    /// Ask the kernel which route it would pick for a packet from `source` to `destination`
+    /// entering through the interface `iif`, like `ip route get <destination> from <source> iif <iif>`.
+    ///
+    /// This is the reverse path check done by `rp_filter`, `Error::Netlink(EXDEV, _)` means such
+    /// packets are dropped.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn get_route_from(
+        &self,
+        destination: IpAddr,
+        source: IpAddr,
+        iif: u32,
+    ) -> Result<Route> {
+        self.counted(
+            self.run(|| {
+                self.platform
+                    .lookup_route(destination, Some(source), Some(iif), None)
+            })
+            .await,
+        )
+    }
+
+    /// Ask the kernel which route it would pick for a packet to `destination` from a socket
+    /// with the firewall mark `mark`, like `ip route get <destination> mark <mark>`.
+    ///
+    /// This resolves the policy routing rules that select a table by mark. On Android netd marks
+    /// the sockets of apps with the netId of their network in the low 16 bits, and the
+    /// `0x10000` bit when the network was selected explicitly, e.g. `netId | 0x10000` resolves
+    /// like a socket bound to that network with `Network.bindSocket`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn get_route_with_mark(&self, destination: IpAddr, mark: u32) -> Result<Route> {
+        self.counted(
+            self.run(|| {
+                self.platform
+                    .lookup_route(destination, None, None, Some(mark))
+            })
+            .await,
+        )
+    }
+
+    /// Returns the routes of the network of the interface `ifindex`, which netd keeps in a
+    /// table of its own instead of the main table.
+    #[cfg(target_os = "android")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_in_network(&self, ifindex: u32) -> Result<Vec<Route>> {
+        let filter = DumpFilter::new().with_table(netd_table(ifindex));
+        self.counted(self.run(|| self.platform.list_filtered(&filter)).await)
+    }
+
+    /// Remove every route that matches `route` in the fields selected by `criteria`.
+    ///
+    /// Fails with `NotFound` if no route matched.
+    #[cfg(feature = "helpers")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn delete_matching(&self, route: &Route, criteria: &RouteMatch) -> Result<()> {
+        let filter = DumpFilter::new().with_family(IpVersion::of(&route.destination));
+        let mut found = false;
+        for candidate in self.list_filtered(&filter).await? {
+            if criteria.matches(route, &candidate) {
+                self.delete(&candidate).await?;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(Error::NotFound(io::ErrorKind::NotFound.into()));
+        }
+        Ok(())
+    }
+
+    /// Start staging a set of route and rule changes that are applied together, see [`Transaction`].
+    #[cfg(feature = "helpers")]
+    pub fn transaction(&self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    /// Compute the operations of route changes without applying them, see [`DryRun`].
+    pub fn dry_run(&self) -> DryRun<'_> {
+        DryRun::new(self)
     }
 
     /// Remove a route from the system's routing table.
-    pub async fn delete(&self, route: &Route) -> io::Result<()> {
-        self.0.delete(route).await
+    ///
+    /// On Linux the first route in `route.table` matching the destination and every other field
+    /// that is set (gateway, interface, metric) is removed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn delete(&self, route: &Route) -> Result<()> {
+        self.throttle(1).await;
+        let result = self.run(|| self.platform.delete(route)).await;
+        if result.is_ok() {
+            self.counters.route_deleted();
+        }
+        self.record(|| JournalOp::DeleteRoute(route.clone()), result)
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn add_rules(&self, rules: Vec<Rule>) -> Result<()> {
+        self.throttle(rules.len()).await;
+        let result = self.run(|| self.platform.add_rules(rules.clone())).await;
+        self.record(|| JournalOp::AddRules(rules), result)
     }
 
-    #[cfg(target_os = "linux")]
-    pub async fn add_rules(&self, rules: Vec<Rule>) -> io::Result<()> {
-        self.0.add_rules(rules).await
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_rules(&self) -> Result<Vec<Rule>> {
+        self.counted(self.run(|| self.platform.list_rules()).await)
     }
 
-    #[cfg(target_os = "linux")]
-    pub async fn list_rules(&self) -> io::Result<Vec<netlink_packet_route::rule::RuleMessage>> {
-        self.0.list_rules().await
+    /// List the rules as the netlink messages the kernel sent, including the attributes [`Rule`]
+    /// doesn't cover. The types come from `netlink-packet-route`, whose version may change in
+    /// minor releases of this crate.
+    #[cfg(all(feature = "linux-raw", any(target_os = "linux", target_os = "android")))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn list_rule_messages(&self) -> Result<Vec<netlink_packet_route::rule::RuleMessage>> {
+        self.counted(self.run(|| self.platform.list_rule_messages()).await)
     }
 
-    #[cfg(target_os = "linux")]
-    pub async fn delete_rules(&self, rules: Vec<Rule>) -> io::Result<()> {
-        self.0.delete_rules(rules).await
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub async fn delete_rules(&self, rules: Vec<Rule>) -> Result<()> {
+        self.throttle(rules.len()).await;
+        let result = self.run(|| self.platform.delete_rules(rules.clone())).await;
+        self.record(|| JournalOp::DeleteRules(rules), result)
     }
 }
 
@@ -116,31 +1082,146 @@ pub struct Route {
     /// On macOS, this must be `Some` if gateway is `None`
     pub ifindex: Option<u32>,
 
-    #[cfg(target_os = "linux")]
+    /// Whether matching packets are forwarded or dropped.
+    pub kind: RouteKind,
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
     /// The routing table this route belongs to.
+    ///
+    /// On FreeBSD this is the FIB, see `setfib(1)`.
     pub table: u32,
 
     /// Network address of the source.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub source: Option<IpAddr>,
 
     /// Prefix length of the source address.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub source_prefix: u8,
 
     /// Source address hint. Does not influence routing.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub source_hint: Option<IpAddr>,
 
-    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    /// The routing protocol that installed this route, `proto` in `ip route` output.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub protocol: RouteProtocol,
+
+    /// The paths of a multipath (ECMP) route, empty for routes with a single nexthop.
+    ///
+    /// Only filled in when listing, multipath routes can't be added yet.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub nexthops: Vec<NextHop>,
+
+    /// Id of the nexthop object this route resolves through, `nhid` in `ip route` output.
+    ///
+    /// Routes using a nexthop object carry no gateway or interface of their own.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub nexthop_id: Option<u32>,
+
+    /// The distance to the destination, `scope` in `ip route` output.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub scope: RouteScope,
+
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
     /// The route metric offset value for this route.
+    ///
+    /// On OpenBSD this is the route priority from 1 to 63, where lower values are preferred.
+    /// Routes to the same destination with different priorities can coexist.
     pub metric: Option<u32>,
 
+    /// The name of the local interface, e.g. `utun3`.
+    ///
+    /// Filled in when listing. When adding or deleting a route without `ifindex`, the interface
+    /// is looked up by this name, which is useful for `utun` devices that get a new index every
+    /// time they are created.
+    #[cfg(target_os = "macos")]
+    pub ifname: Option<String>,
+
+    /// Whether the route is scoped to `ifindex`, like `route add -ifscope en0`, and only used for
+    /// traffic bound to that interface.
+    #[cfg(target_os = "macos")]
+    pub ifscope: bool,
+
+    /// The `RTF_*` flags of the route, e.g. to skip the `RouteFlags::LLINFO` entries of the ARP
+    /// and neighbor cache.
+    ///
+    /// Only filled in when listing.
+    #[cfg(target_os = "macos")]
+    pub flags: RouteFlags,
+
+    /// The path properties kept with the route.
+    #[cfg(target_os = "macos")]
+    pub metrics: RouteMetrics,
+
     #[cfg(target_os = "windows")]
     /// Luid of the local interface through which the next hop of this route may be reached.
     ///
-    /// If luid is specified, ifindex is optional.
+    /// If luid is specified, ifindex is optional. Prefer the luid when adding routes, as the
+    /// ifindex of an adapter can change when it is reset.
     pub luid: Option<u64>,
+
+    #[cfg(target_os = "windows")]
+    /// GUID of the adapter through which the next hop of this route may be reached, as handed
+    /// out by drivers like wintun, e.g. `0x6ba7b8109dad11d180b400c04fd430c8` for
+    /// `{6BA7B810-9DAD-11D1-80B4-00C04FD430C8}`.
+    ///
+    /// Resolved to the luid when adding or removing a route without a luid. Not filled in when
+    /// listing.
+    pub adapter_guid: Option<u128>,
+
+    #[cfg(target_os = "windows")]
+    /// Friendly name of the interface, e.g. `Ethernet`.
+    ///
+    /// Only filled in when listing, it is ignored when adding or removing routes.
+    pub alias: Option<String>,
+
+    #[cfg(target_os = "windows")]
+    /// Whether the route survives a reboot, like routes added with `route -p`.
+    ///
+    /// Only ipv4 routes can be persistent.
+    pub persistent: bool,
+
+    #[cfg(target_os = "windows")]
+    /// The store the route was read from, `RouteStore::Persistent` for routes returned by
+    /// [`Handle::list_persistent`] and for change events about the persistent copy of a route.
+    ///
+    /// Ignored when adding or removing routes, use `persistent` instead.
+    pub store: RouteStore,
+
+    #[cfg(target_os = "windows")]
+    /// What installed the route.
+    ///
+    /// Only filled in when listing, added routes are always `RouteOrigin::NetMgmt`.
+    pub origin: RouteOrigin,
+
+    #[cfg(target_os = "windows")]
+    /// Whether the route is advertised in ipv6 router advertisements.
+    pub publish: bool,
+
+    #[cfg(target_os = "windows")]
+    /// Whether the route never ages out, e.g. for routes learned from router advertisements.
+    pub immortal: bool,
+
+    #[cfg(target_os = "windows")]
+    /// Whether the route is a loopback route, i.e. the gateway is on the local host.
+    ///
+    /// Defaults to `true`, the default of `InitializeIpForwardEntry`.
+    pub loopback: bool,
+
+    #[cfg(target_os = "windows")]
+    /// How long the route stays in the table before the system removes it, `None` for forever.
+    pub valid_lifetime: Option<Duration>,
+
+    #[cfg(target_os = "windows")]
+    /// How long the route is preferred over other routes to the same destination, `None` for
+    /// forever. Must not exceed the valid lifetime.
+    pub preferred_lifetime: Option<Duration>,
 }
 
 impl Route {
@@ -153,19 +1234,58 @@ impl Route {
             prefix,
             gateway: None,
             ifindex: None,
-            #[cfg(target_os = "linux")]
+            kind: RouteKind::Unicast,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
             // default to main table
             table: 254,
-            #[cfg(target_os = "linux")]
+            #[cfg(target_os = "freebsd")]
+            table: 0,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
             source: None,
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "android"))]
             source_prefix: 0,
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "android"))]
             source_hint: None,
-            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            protocol: RouteProtocol::Static,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            nexthops: vec![],
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            nexthop_id: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            scope: RouteScope::Universe,
+            #[cfg(any(target_os = "windows", target_os = "linux", target_os = "android", target_os = "openbsd"))]
             metric: None,
+            #[cfg(target_os = "macos")]
+            ifname: None,
+            #[cfg(target_os = "macos")]
+            ifscope: false,
+            #[cfg(target_os = "macos")]
+            flags: RouteFlags::default(),
+            #[cfg(target_os = "macos")]
+            metrics: RouteMetrics::default(),
             #[cfg(target_os = "windows")]
             luid: None,
+            #[cfg(target_os = "windows")]
+            adapter_guid: None,
+            #[cfg(target_os = "windows")]
+            alias: None,
+            #[cfg(target_os = "windows")]
+            persistent: false,
+            #[cfg(target_os = "windows")]
+            store: RouteStore::Active,
+            #[cfg(target_os = "windows")]
+            origin: RouteOrigin::NetMgmt,
+            #[cfg(target_os = "windows")]
+            publish: false,
+            #[cfg(target_os = "windows")]
+            immortal: false,
+            #[cfg(target_os = "windows")]
+            loopback: true,
+            #[cfg(target_os = "windows")]
+            valid_lifetime: None,
+            #[cfg(target_os = "windows")]
+            preferred_lifetime: None,
         }
     }
 
@@ -181,15 +1301,31 @@ impl Route {
         self
     }
 
+    /// Set whether matching packets are forwarded or dropped, e.g. `RouteKind::Blackhole` to
+    /// drop traffic that must not leave through another interface.
+    pub fn with_kind(mut self, kind: RouteKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Install the route through the interface `ifindex` in the table netd uses for the network
+    /// of that interface, so it is used by the sockets of that network.
+    #[cfg(target_os = "android")]
+    pub fn with_network(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self.table = netd_table(ifindex);
+        self
+    }
+
     /// Set table the route will be installed in.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
     pub fn with_table(mut self, table: u32) -> Self {
         self.table = table;
         self
     }
 
     /// Set source.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn with_source(mut self, source: IpAddr, prefix: u8) -> Self {
         self.source = Some(source);
         self.source_prefix = prefix;
@@ -197,19 +1333,68 @@ impl Route {
     }
 
     /// Set source hint.
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn with_source_hint(mut self, hint: IpAddr) -> Self {
         self.source_hint = Some(hint);
         self
     }
 
+    /// Set the protocol the route will be installed with.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set the nexthop object the route resolves through, instead of a gateway and interface.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_nexthop_id(mut self, id: u32) -> Self {
+        self.nexthop_id = Some(id);
+        self
+    }
+
+    /// Set the scope the route will be installed with.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn with_scope(mut self, scope: RouteScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
     /// Set route metric.
-    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
     pub fn with_metric(mut self, metric: u32) -> Self {
         self.metric = Some(metric);
         self
     }
 
+    /// Set the name of the local interface through which the next hop of this route should be
+    /// reached, it is resolved to an index when the route is added or deleted.
+    #[cfg(target_os = "macos")]
+    pub fn with_ifname(mut self, ifname: impl Into<String>) -> Self {
+        self.ifname = Some(ifname.into());
+        self
+    }
+
+    /// Scope the route to the interface `ifindex`, e.g. to install a default route per interface.
+    #[cfg(target_os = "macos")]
+    pub fn with_ifscope(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self.ifscope = true;
+        self
+    }
+
+    /// Set the path properties kept with the route.
+    #[cfg(target_os = "macos")]
+    pub fn with_metrics(mut self, metrics: RouteMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Set luid of the local interface through which the next hop of this route should be reached.
     #[cfg(target_os = "windows")]
     pub fn with_luid(mut self, luid: u64) -> Self {
@@ -217,6 +1402,64 @@ impl Route {
         self
     }
 
+    /// Set the GUID of the adapter through which the next hop of this route should be reached.
+    #[cfg(target_os = "windows")]
+    pub fn with_adapter_guid(mut self, guid: u128) -> Self {
+        self.adapter_guid = Some(guid);
+        self
+    }
+
+    /// Set the interface through which the next hop of this route should be reached.
+    #[cfg(target_os = "windows")]
+    pub fn with_interface(mut self, interface: &Interface) -> Self {
+        self.ifindex = Some(interface.ifindex);
+        self.luid = Some(interface.luid);
+        self.alias = Some(interface.alias.clone());
+        self
+    }
+
+    /// Set whether the route is advertised in ipv6 router advertisements.
+    #[cfg(target_os = "windows")]
+    pub fn with_publish(mut self, publish: bool) -> Self {
+        self.publish = publish;
+        self
+    }
+
+    /// Set whether the route never ages out.
+    #[cfg(target_os = "windows")]
+    pub fn with_immortal(mut self, immortal: bool) -> Self {
+        self.immortal = immortal;
+        self
+    }
+
+    /// Set whether the route is a loopback route.
+    #[cfg(target_os = "windows")]
+    pub fn with_loopback(mut self, loopback: bool) -> Self {
+        self.loopback = loopback;
+        self
+    }
+
+    /// Set the time after which the system removes the route again.
+    #[cfg(target_os = "windows")]
+    pub fn with_valid_lifetime(mut self, lifetime: Duration) -> Self {
+        self.valid_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Set the time after which the route is no longer preferred.
+    #[cfg(target_os = "windows")]
+    pub fn with_preferred_lifetime(mut self, lifetime: Duration) -> Self {
+        self.preferred_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Set whether the route should be re-created after a reboot.
+    #[cfg(target_os = "windows")]
+    pub fn with_persistent(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
     /// Get the netmask covering the network portion of the destination address.
     pub fn mask(&self) -> IpAddr {
         match self.destination {
@@ -230,7 +1473,40 @@ impl Route {
     }
 }
 
-#[cfg(target_os = "linux")]
+/// Formats the route like `ip route`, e.g. `10.0.0.0/8 via 192.168.1.1 dev 3`, with the index of
+/// the interface instead of its name.
+impl std::fmt::Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            RouteKind::Unicast => {}
+            RouteKind::Blackhole => f.write_str("blackhole ")?,
+            RouteKind::Unreachable => f.write_str("unreachable ")?,
+            RouteKind::Prohibit => f.write_str("prohibit ")?,
+            RouteKind::Other => f.write_str("other ")?,
+        }
+        write!(f, "{}/{}", self.destination, self.prefix)?;
+        if let Some(gateway) = self.gateway {
+            write!(f, " via {}", gateway)?;
+        }
+        if let Some(ifindex) = self.ifindex {
+            write!(f, " dev {}", ifindex)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+        write!(f, " table {}", self.table)?;
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd"
+        ))]
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Rule {
     pub src: Option<(IpAddr, u8)>,
@@ -245,6 +1521,513 @@ pub struct Rule {
     pub v6: bool,
 }
 
+/// Formats the rule like `ip rule`, e.g. `100: from 10.0.0.0/8 lookup 200`.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(priority) = self.priority {
+            write!(f, "{}: ", priority)?;
+        }
+        match self.src {
+            Some((address, prefix)) => write!(f, "from {}/{}", address, prefix)?,
+            None => f.write_str("from all")?,
+        }
+        if let Some((address, prefix)) = self.dst {
+            write!(f, " to {}/{}", address, prefix)?;
+        }
+        if let Some(interface) = &self.input_interface {
+            write!(f, " iif {}", interface)?;
+        }
+        if let Some(interface) = &self.output_interface {
+            write!(f, " oif {}", interface)?;
+        }
+        if let Some((mark, mask)) = self.fw_mark_mask {
+            write!(f, " fwmark {:#x}/{:#x}", mark, mask)?;
+        }
+        if let Some(protocol) = self.protocol {
+            write!(f, " ipproto {}", protocol.as_raw())?;
+        }
+        if let Some(table) = self.table_id {
+            write!(f, " lookup {}", table)?;
+        }
+        if let Some(length) = self.suppress_prefixlength {
+            write!(f, " suppress_prefixlength {}", length)?;
+        }
+        Ok(())
+    }
+}
+
+/// The IP protocol a [`Rule`] matches.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpProtocol {
+    Icmp,
+    Tcp,
+    Udp,
+    Icmpv6,
+    /// Any other protocol by its number in the IANA registry.
+    Other(u8),
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl IpProtocol {
+    /// Create the protocol from its IANA number.
+    pub fn from_raw(raw: u8) -> Self {
+        match raw {
+            1 => Self::Icmp,
+            6 => Self::Tcp,
+            17 => Self::Udp,
+            58 => Self::Icmpv6,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Get the IANA number of the protocol.
+    pub fn as_raw(self) -> u8 {
+        match self {
+            Self::Icmp => 1,
+            Self::Tcp => 6,
+            Self::Udp => 17,
+            Self::Icmpv6 => 58,
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// One of the paths of a multipath route.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextHop {
+    /// The address of the next hop on this path.
+    pub gateway: Option<IpAddr>,
+
+    /// The index of the local interface used by this path.
+    pub ifindex: u32,
+
+    /// The relative share of traffic sent along this path, starting at 1.
+    pub weight: u16,
+}
+
+/// The `RTF_*` flags of a route on macOS, see `route(4)`.
+#[cfg(target_os = "macos")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RouteFlags(u32);
+
+#[cfg(target_os = "macos")]
+impl RouteFlags {
+    pub const UP: Self = Self(0x1);
+    pub const GATEWAY: Self = Self(0x2);
+    pub const HOST: Self = Self(0x4);
+    pub const REJECT: Self = Self(0x8);
+    pub const DYNAMIC: Self = Self(0x10);
+    pub const MODIFIED: Self = Self(0x20);
+    pub const DONE: Self = Self(0x40);
+    pub const CLONING: Self = Self(0x100);
+    pub const XRESOLVE: Self = Self(0x200);
+    pub const LLINFO: Self = Self(0x400);
+    pub const STATIC: Self = Self(0x800);
+    pub const BLACKHOLE: Self = Self(0x1000);
+    pub const PROTO2: Self = Self(0x4000);
+    pub const PROTO1: Self = Self(0x8000);
+    pub const PRCLONING: Self = Self(0x10000);
+    pub const WASCLONED: Self = Self(0x20000);
+    pub const PROTO3: Self = Self(0x40000);
+    pub const PINNED: Self = Self(0x100000);
+    pub const LOCAL: Self = Self(0x200000);
+    pub const BROADCAST: Self = Self(0x400000);
+    pub const MULTICAST: Self = Self(0x800000);
+    pub const IFSCOPE: Self = Self(0x1000000);
+    pub const CONDEMNED: Self = Self(0x2000000);
+    pub const IFREF: Self = Self(0x4000000);
+    pub const PROXY: Self = Self(0x8000000);
+    pub const ROUTER: Self = Self(0x10000000);
+
+    /// Create flags from the raw `rtm_flags` value.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// The raw `rtm_flags` value.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether all flags in `other` are set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl std::ops::BitOr for RouteFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The interface macOS sends traffic through by default, see [`Handle::primary_interface`].
+#[cfg(target_os = "macos")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimaryInterface {
+    /// The name of the interface, e.g. `en0`.
+    pub name: String,
+
+    /// The index of the interface.
+    pub ifindex: u32,
+
+    /// The router of the network service, `None` for point-to-point links like VPN tunnels.
+    pub router: Option<IpAddr>,
+}
+
+/// Path properties kept with a route, `None` for the ones that aren't set.
+///
+/// Only supported on macOS, where they map to the `rt_metrics` of a route.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RouteMetrics {
+    /// The MTU of the path.
+    pub mtu: Option<u32>,
+
+    /// The number of hops to the destination.
+    pub hopcount: Option<u32>,
+
+    /// The time until the route expires and is removed.
+    pub expire: Option<Duration>,
+
+    /// The estimated round trip time of the path.
+    pub rtt: Option<Duration>,
+}
+
+/// What happens to packets matching a route.
+///
+/// Routes that drop packets can't be added on Windows.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteKind {
+    /// Forward packets to the gateway or interface.
+    #[default]
+    Unicast,
+
+    /// Silently drop packets, `RTF_BLACKHOLE` on macOS.
+    Blackhole,
+
+    /// Drop packets and report the destination as unreachable, `RTF_REJECT` on macOS.
+    Unreachable,
+
+    /// Drop packets and report the destination as administratively prohibited. Same as
+    /// `Unreachable` on macOS.
+    Prohibit,
+
+    /// Any other kind, e.g. local or broadcast routes on Linux. Can't be added.
+    Other,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RouteKind {
+    /// Create the kind from the `rtm_type` of a route, `RTN_*` in `rtnetlink.h`.
+    pub fn from_raw(raw: u8) -> Self {
+        match raw {
+            1 => Self::Unicast,
+            6 => Self::Blackhole,
+            7 => Self::Unreachable,
+            8 => Self::Prohibit,
+            _ => Self::Other,
+        }
+    }
+
+    /// Get the `rtm_type` of the kind, `None` for `RouteKind::Other` which covers several types.
+    pub fn as_raw(self) -> Option<u8> {
+        match self {
+            Self::Unicast => Some(1),
+            Self::Blackhole => Some(6),
+            Self::Unreachable => Some(7),
+            Self::Prohibit => Some(8),
+            Self::Other => None,
+        }
+    }
+}
+
+/// The routing protocol that installed a route, `RTPROT_*` in `rtnetlink.h`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteProtocol {
+    Unspec,
+    IcmpRedirect,
+    /// Installed by the kernel, e.g. the routes of the addresses of an interface.
+    Kernel,
+    /// Installed during boot.
+    Boot,
+    /// Installed by the administrator, the default for added routes.
+    Static,
+    Gated,
+    Ra,
+    Mrt,
+    Zebra,
+    Bird,
+    DnRouted,
+    Xorp,
+    Ntk,
+    Dhcp,
+    Mrouted,
+    KeepAlived,
+    Babel,
+    OpenNr,
+    Bgp,
+    Isis,
+    Ospf,
+    Rip,
+    Eigrp,
+    /// Any other protocol number, e.g. one picked for [`HandleBuilder::with_owner_protocol`].
+    Other(u8),
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RouteProtocol {
+    /// Create the protocol from the `rtm_protocol` of a route.
+    pub fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => Self::Unspec,
+            1 => Self::IcmpRedirect,
+            2 => Self::Kernel,
+            3 => Self::Boot,
+            4 => Self::Static,
+            8 => Self::Gated,
+            9 => Self::Ra,
+            10 => Self::Mrt,
+            11 => Self::Zebra,
+            12 => Self::Bird,
+            13 => Self::DnRouted,
+            14 => Self::Xorp,
+            15 => Self::Ntk,
+            16 => Self::Dhcp,
+            17 => Self::Mrouted,
+            18 => Self::KeepAlived,
+            42 => Self::Babel,
+            99 => Self::OpenNr,
+            186 => Self::Bgp,
+            187 => Self::Isis,
+            188 => Self::Ospf,
+            189 => Self::Rip,
+            192 => Self::Eigrp,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Get the `rtm_protocol` of the protocol.
+    pub fn as_raw(self) -> u8 {
+        match self {
+            Self::Unspec => 0,
+            Self::IcmpRedirect => 1,
+            Self::Kernel => 2,
+            Self::Boot => 3,
+            Self::Static => 4,
+            Self::Gated => 8,
+            Self::Ra => 9,
+            Self::Mrt => 10,
+            Self::Zebra => 11,
+            Self::Bird => 12,
+            Self::DnRouted => 13,
+            Self::Xorp => 14,
+            Self::Ntk => 15,
+            Self::Dhcp => 16,
+            Self::Mrouted => 17,
+            Self::KeepAlived => 18,
+            Self::Babel => 42,
+            Self::OpenNr => 99,
+            Self::Bgp => 186,
+            Self::Isis => 187,
+            Self::Ospf => 188,
+            Self::Rip => 189,
+            Self::Eigrp => 192,
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// The distance to the destination of a route, `RT_SCOPE_*` in `rtnetlink.h`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteScope {
+    /// Reached through a gateway, the default for added routes.
+    Universe,
+    Site,
+    /// Directly attached to an interface.
+    Link,
+    /// An address of the local host.
+    Host,
+    /// The destination doesn't exist.
+    NoWhere,
+    /// Any other scope, values between `Universe` and `Site` are user defined.
+    Other(u8),
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RouteScope {
+    /// Create the scope from the `rtm_scope` of a route.
+    pub fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => Self::Universe,
+            200 => Self::Site,
+            253 => Self::Link,
+            254 => Self::Host,
+            255 => Self::NoWhere,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Get the `rtm_scope` of the scope.
+    pub fn as_raw(self) -> u8 {
+        match self {
+            Self::Universe => 0,
+            Self::Site => 200,
+            Self::Link => 253,
+            Self::Host => 254,
+            Self::NoWhere => 255,
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// Operations the current process is allowed to perform, see [`Handle::capabilities`].
+///
+/// This requires `CAP_NET_ADMIN` on Linux, an elevated process on Windows and root on macOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether routes can be added and removed.
+    pub modify_routes: bool,
+
+    /// Whether policy routing rules can be added and removed. Always `false` on platforms
+    /// without rule support.
+    pub modify_rules: bool,
+}
+
+/// The store of a route on Windows, see [`Route::store`].
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteStore {
+    /// The routing table in use.
+    Active,
+
+    /// The routes that are re-created on boot.
+    Persistent,
+}
+
+/// What installed a route on Windows, based on the protocol and origin of the route entry.
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteOrigin {
+    /// Generated by the system for local addresses and subnets.
+    Local,
+
+    /// Added through the ip helper api, e.g. by `route add` or this crate.
+    NetMgmt,
+
+    /// Learned from DHCP.
+    Dhcp,
+
+    /// Learned from an ipv6 router advertisement.
+    RouterAdvertisement,
+
+    /// A static route configured on the interface.
+    Static,
+
+    /// Any other routing protocol, by its `NL_ROUTE_PROTOCOL` number.
+    Other(u32),
+}
+
+/// The reason for [`Error::PermissionDenied`] on Windows, returned when the routing table is
+/// modified by a process that isn't running as administrator.
+///
+/// The `io::Error` an `Error::PermissionDenied` converts into carries it, so it can still be
+/// detected with `e.get_ref().map_or(false, |e| e.is::<ElevationRequired>())`.
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElevationRequired;
+
+#[cfg(target_os = "windows")]
+impl std::fmt::Display for ElevationRequired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            "modifying the routing table requires administrator rights, \
+            run the process elevated (\"Run as administrator\")",
+        )
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl std::error::Error for ElevationRequired {}
+
+/// Identifies a network interface on Windows.
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// Index of the interface, may change when the adapter is reset.
+    pub ifindex: u32,
+
+    /// Luid of the interface, stable until reboot.
+    pub luid: u64,
+
+    /// Friendly name of the interface, e.g. `Ethernet`.
+    pub alias: String,
+
+    /// The kind of link, e.g. to prefer wired over wireless default routes.
+    pub kind: InterfaceType,
+
+    /// Whether the interface is operational and can pass packets.
+    pub up: bool,
+}
+
+/// The kind of link of an [`Interface`].
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterfaceType {
+    Ethernet,
+    Wifi,
+    Ppp,
+    /// Tunnel and virtual adapters, e.g. wintun or tap adapters of VPN clients.
+    Tunnel,
+    Loopback,
+    /// Any other type, by its `IFTYPE` number.
+    Other(u32),
+}
+
+/// Forwarding settings of an interface on Windows, see [`Handle::set_interface_forwarding`].
+#[cfg(target_os = "windows")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceForwarding {
+    /// Whether packets received on the interface that are addressed to other hosts are forwarded.
+    pub forwarding: bool,
+
+    /// Whether packets may be sent through the interface with a source address of another
+    /// interface.
+    pub weak_host_send: bool,
+
+    /// Whether packets received on the interface are accepted for addresses of other interfaces.
+    pub weak_host_receive: bool,
+}
+
+/// A VRF (virtual routing and forwarding) master device and the routing table bound to it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vrf {
+    /// Name of the VRF device.
+    pub name: String,
+
+    /// Index of the VRF device.
+    pub ifindex: u32,
+
+    /// The routing table used by interfaces enslaved to this VRF.
+    pub table: u32,
+}
+
+/// A change of the routing table, see [`Handle::route_listen_stream`].
+///
+/// On Windows the route carries the luid of its interface and whether the event concerns the
+/// active or the persistent store, so the duplicate events for persistent routes can be skipped.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RouteChange {
     Add(Route),
@@ -285,4 +2068,27 @@ mod tests {
             Ipv6Addr::new(0xffff, 0xffff, 0, 0, 0, 0, 0, 0)
         );
     }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn it_round_trips_raw_values() {
+        use crate::{RouteKind, RouteProtocol, RouteScope};
+
+        for raw in 0..=u8::MAX {
+            assert_eq!(RouteProtocol::from_raw(raw).as_raw(), raw);
+            assert_eq!(RouteScope::from_raw(raw).as_raw(), raw);
+            let kind = RouteKind::from_raw(raw);
+            if let Some(kind_raw) = kind.as_raw() {
+                assert_eq!(kind_raw, raw);
+            } else {
+                assert_eq!(kind, RouteKind::Other);
+            }
+        }
+    }
+
+    #[test]
+    fn it_shares_the_handle_across_tasks() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<crate::Handle>();
+    }
 }
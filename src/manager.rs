@@ -0,0 +1,106 @@
+use std::ops::ControlFlow;
+
+#[cfg(feature = "helpers")]
+use crate::Transaction;
+use crate::{
+    Capabilities, DumpFilter, Handle, HandleBuilder, ListCursor, Result, Route, RouteChunk,
+};
+
+/// A handle that only reads and changes the routing table, without receiving route changes.
+///
+/// No socket is bound to route events and no listener task is started, use a [`Handle`] or a
+/// [`RouteMonitor`](crate::RouteMonitor) to follow changes.
+#[derive(Clone)]
+pub struct RouteManager(Handle);
+
+impl RouteManager {
+    pub fn new() -> Result<Self> {
+        HandleBuilder::new().build_manager()
+    }
+
+    pub(crate) fn from_handle(handle: Handle) -> Self {
+        Self(handle)
+    }
+
+    /// See [`Handle::capabilities`].
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        self.0.capabilities()
+    }
+
+    /// Add route to the system's routing table.
+    pub async fn add(&self, route: &Route) -> Result<()> {
+        self.0.add(route).await
+    }
+
+    /// Add several routes with their requests pipelined, see [`Handle::add_routes`].
+    pub async fn add_routes(&self, routes: &[Route]) -> Result<()> {
+        self.0.add_routes(routes).await
+    }
+
+    /// Remove a route from the system's routing table, see [`Handle::delete`].
+    pub async fn delete(&self, route: &Route) -> Result<()> {
+        self.0.delete(route).await
+    }
+
+    /// Returns a `Vec<Route>` containing a list of both ipv4 and v6 routes on the system.
+    pub async fn list(&self) -> Result<Vec<Route>> {
+        self.0.list().await
+    }
+
+    /// Returns a `Vec<Route>` containing the routes selected by `filter`.
+    pub async fn list_filtered(&self, filter: &DumpFilter) -> Result<Vec<Route>> {
+        self.0.list_filtered(filter).await
+    }
+
+    /// Hand the routes selected by `filter` to `visit` one at a time, see
+    /// [`Handle::for_each_route`].
+    pub async fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.0.for_each_route(filter, visit).await
+    }
+
+    /// Stream the routes selected by `filter` in chunks, see [`Handle::list_chunks`].
+    pub fn list_chunks(
+        &self,
+        filter: &DumpFilter,
+        chunk_size: usize,
+        resume: Option<ListCursor>,
+    ) -> impl futures::Stream<Item = Result<RouteChunk>> + Send + 'static {
+        self.0.list_chunks(filter, chunk_size, resume)
+    }
+
+    /// Get one of the default routes on the system if there is at least one.
+    pub async fn default_route(&self) -> Result<Option<Route>> {
+        self.0.default_route().await
+    }
+
+    /// Get the route the system would use to reach `destination`, see [`Handle::get_route`].
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia"
+    ))]
+    pub async fn get_route(&self, destination: std::net::IpAddr) -> Result<Route> {
+        self.0.get_route(destination).await
+    }
+
+    /// Start staging a set of route and rule changes that are applied together, see [`Transaction`].
+    #[cfg(feature = "helpers")]
+    pub fn transaction(&self) -> Transaction<'_> {
+        self.0.transaction()
+    }
+
+    /// See [`Handle::close`].
+    pub async fn close(self) -> Result<()> {
+        self.0.close().await
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Error;
+
+/// The counters of a [`Handle`](crate::Handle) and its clones since it was created, see
+/// [`Handle::metrics`](crate::Handle::metrics).
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Routes added, including routes added by transactions and helpers.
+    pub routes_added: u64,
+    /// Routes removed.
+    pub routes_deleted: u64,
+    /// Route changes delivered to the streams of [`Handle::route_listen_stream`](crate::Handle::route_listen_stream).
+    pub events_received: u64,
+    /// Route changes skipped because a stream fell behind, see
+    /// [`HandleBuilder::with_event_capacity`](crate::HandleBuilder::with_event_capacity).
+    pub events_dropped: u64,
+    /// Failed operations by the variant of their [`Error`].
+    pub errors: ErrorCounts,
+}
+
+/// The number of failed operations for every variant of [`Error`].
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCounts {
+    pub not_found: u64,
+    pub already_exists: u64,
+    pub permission_denied: u64,
+    pub unsupported: u64,
+    pub invalid_route: u64,
+    pub timed_out: u64,
+    pub dump_interrupted: u64,
+    pub netlink: u64,
+    pub os: u64,
+}
+
+impl ErrorCounts {
+    /// The number of failed operations.
+    pub fn total(&self) -> u64 {
+        self.not_found
+            + self.already_exists
+            + self.permission_denied
+            + self.unsupported
+            + self.invalid_route
+            + self.timed_out
+            + self.dump_interrupted
+            + self.netlink
+            + self.os
+    }
+}
+
+// Shared by the clones of a handle and the streams they return.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    routes_added: AtomicU64,
+    routes_deleted: AtomicU64,
+    events_received: AtomicU64,
+    events_dropped: AtomicU64,
+    not_found: AtomicU64,
+    already_exists: AtomicU64,
+    permission_denied: AtomicU64,
+    unsupported: AtomicU64,
+    invalid_route: AtomicU64,
+    timed_out: AtomicU64,
+    dump_interrupted: AtomicU64,
+    netlink: AtomicU64,
+    os: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn route_added(&self) {
+        self.routes_added.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn route_deleted(&self) {
+        self.routes_deleted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    pub(crate) fn event_received(&self) {
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    pub(crate) fn events_dropped(&self, n: u64) {
+        self.events_dropped.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn error(&self, e: &Error) {
+        let counter = match e.root() {
+            Error::NotFound(_) => &self.not_found,
+            Error::AlreadyExists(_) => &self.already_exists,
+            Error::PermissionDenied(_) => &self.permission_denied,
+            Error::Unsupported(_) => &self.unsupported,
+            Error::InvalidRoute(_) => &self.invalid_route,
+            Error::TimedOut(_) => &self.timed_out,
+            Error::DumpInterrupted => &self.dump_interrupted,
+            Error::Netlink(..) => &self.netlink,
+            Error::Os(_) | Error::Context { .. } => &self.os,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        Metrics {
+            routes_added: load(&self.routes_added),
+            routes_deleted: load(&self.routes_deleted),
+            events_received: load(&self.events_received),
+            events_dropped: load(&self.events_dropped),
+            errors: ErrorCounts {
+                not_found: load(&self.not_found),
+                already_exists: load(&self.already_exists),
+                permission_denied: load(&self.permission_denied),
+                unsupported: load(&self.unsupported),
+                invalid_route: load(&self.invalid_route),
+                timed_out: load(&self.timed_out),
+                dump_interrupted: load(&self.dump_interrupted),
+                netlink: load(&self.netlink),
+                os: load(&self.os),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_errors_by_kind() {
+        let counters = Counters::default();
+        counters.route_added();
+        let not_found = || Error::NotFound(std::io::ErrorKind::NotFound.into());
+        counters.error(&not_found());
+        counters.error(&not_found());
+        counters.error(&Error::Netlink(22, std::io::Error::from_raw_os_error(22)));
+        counters.events_dropped(3);
+
+        let metrics = counters.snapshot();
+        assert_eq!(metrics.routes_added, 1);
+        assert_eq!(metrics.events_dropped, 3);
+        assert_eq!(metrics.errors.not_found, 2);
+        assert_eq!(metrics.errors.netlink, 1);
+        assert_eq!(metrics.errors.total(), 3);
+    }
+}
@@ -0,0 +1,321 @@
+//! Bindings for Kotlin and Swift generated with uniffi, for VPN apps on Android and iOS.
+//!
+//! The types mirror [`Route`], [`Rule`](crate::Rule) and [`RouteChange`] with addresses as
+//! strings, which the foreign languages can parse with their own address types. The calls block,
+//! so they should be made from a background thread. Build the crate as a `cdylib` with the
+//! `uniffi` feature and generate the bindings with `uniffi-bindgen generate --library`.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures::{channel::oneshot, StreamExt};
+
+use crate::{blocking, Error, Route, RouteChange};
+
+/// The error of the calls, see [`Error`].
+#[derive(Debug, uniffi::Error)]
+pub enum RouteError {
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    Unsupported { reason: String },
+    InvalidRoute { reason: String },
+    TimedOut,
+    Os { message: String },
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::NotFound => write!(f, "not found"),
+            RouteError::AlreadyExists => write!(f, "already exists"),
+            RouteError::PermissionDenied => write!(f, "permission denied"),
+            RouteError::Unsupported { reason } => write!(f, "unsupported: {}", reason),
+            RouteError::InvalidRoute { reason } => write!(f, "invalid route: {}", reason),
+            RouteError::TimedOut => write!(f, "timed out"),
+            RouteError::Os { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+impl From<Error> for RouteError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotFound(_) => RouteError::NotFound,
+            Error::AlreadyExists(_) => RouteError::AlreadyExists,
+            Error::PermissionDenied(_) => RouteError::PermissionDenied,
+            Error::Unsupported(reason) => RouteError::Unsupported { reason },
+            Error::InvalidRoute(reason) => RouteError::InvalidRoute { reason },
+            Error::TimedOut(_) => RouteError::TimedOut,
+            Error::Context {
+                operation,
+                entry,
+                source,
+            } => {
+                let context = |message: String| format!("{} {}: {}", operation, entry, message);
+                match RouteError::from(*source) {
+                    RouteError::Unsupported { reason } => RouteError::Unsupported {
+                        reason: context(reason),
+                    },
+                    RouteError::InvalidRoute { reason } => RouteError::InvalidRoute {
+                        reason: context(reason),
+                    },
+                    RouteError::Os { message } => RouteError::Os {
+                        message: context(message),
+                    },
+                    e => e,
+                }
+            }
+            e => RouteError::Os {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+fn parse_addr(addr: &str) -> Result<IpAddr, RouteError> {
+    addr.parse().map_err(|_| RouteError::InvalidRoute {
+        reason: format!("invalid address {}", addr),
+    })
+}
+
+/// A route, see [`Route`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct RouteRecord {
+    pub destination: String,
+    pub prefix: u8,
+    pub gateway: Option<String>,
+    pub ifindex: Option<u32>,
+    /// The routing table on Linux and Android, ignored elsewhere.
+    pub table: Option<u32>,
+}
+
+impl From<&Route> for RouteRecord {
+    fn from(route: &Route) -> Self {
+        Self {
+            destination: route.destination.to_string(),
+            prefix: route.prefix,
+            gateway: route.gateway.map(|gateway| gateway.to_string()),
+            ifindex: route.ifindex,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            table: Some(route.table),
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            table: None,
+        }
+    }
+}
+
+impl TryFrom<RouteRecord> for Route {
+    type Error = RouteError;
+
+    fn try_from(record: RouteRecord) -> Result<Self, RouteError> {
+        let mut route = Route::new(parse_addr(&record.destination)?, record.prefix);
+        if let Some(gateway) = &record.gateway {
+            route = route.with_gateway(parse_addr(gateway)?);
+        }
+        if let Some(ifindex) = record.ifindex {
+            route = route.with_ifindex(ifindex);
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(table) = record.table {
+            route = route.with_table(table);
+        }
+        Ok(route)
+    }
+}
+
+/// A policy routing rule, see [`Rule`](crate::Rule). Networks are written as `address/prefix`.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct RuleRecord {
+    pub src: Option<String>,
+    pub dst: Option<String>,
+    pub input_interface: Option<String>,
+    pub output_interface: Option<String>,
+    pub table_id: Option<u32>,
+    pub priority: Option<u32>,
+    pub fw_mark: Option<u32>,
+    pub fw_mask: Option<u32>,
+    pub v6: bool,
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+fn parse_network(network: &str) -> Result<(IpAddr, u8), RouteError> {
+    let invalid = || RouteError::InvalidRoute {
+        reason: format!("invalid network {}", network),
+    };
+    let (addr, prefix) = network.split_once('/').ok_or_else(invalid)?;
+    Ok((parse_addr(addr)?, prefix.parse().map_err(|_| invalid())?))
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl From<&crate::Rule> for RuleRecord {
+    fn from(rule: &crate::Rule) -> Self {
+        let network = |(addr, prefix): (IpAddr, u8)| format!("{}/{}", addr, prefix);
+        Self {
+            src: rule.src.map(network),
+            dst: rule.dst.map(network),
+            input_interface: rule.input_interface.clone(),
+            output_interface: rule.output_interface.clone(),
+            table_id: rule.table_id,
+            priority: rule.priority,
+            fw_mark: rule.fw_mark_mask.map(|(mark, _)| mark),
+            fw_mask: rule.fw_mark_mask.map(|(_, mask)| mask),
+            v6: rule.v6,
+        }
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl TryFrom<RuleRecord> for crate::Rule {
+    type Error = RouteError;
+
+    fn try_from(record: RuleRecord) -> Result<Self, RouteError> {
+        Ok(crate::Rule {
+            src: record.src.as_deref().map(parse_network).transpose()?,
+            dst: record.dst.as_deref().map(parse_network).transpose()?,
+            input_interface: record.input_interface,
+            output_interface: record.output_interface,
+            table_id: record.table_id,
+            priority: record.priority,
+            fw_mark_mask: record
+                .fw_mark
+                .map(|mark| (mark, record.fw_mask.unwrap_or(u32::MAX))),
+            v6: record.v6,
+            ..Default::default()
+        })
+    }
+}
+
+/// What happened to the route passed to [`RouteListener::on_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum RouteChangeKind {
+    Add,
+    Delete,
+    Change,
+}
+
+/// Implemented in Kotlin or Swift to receive the changes of the routing table.
+#[uniffi::export(callback_interface)]
+pub trait RouteListener: Send + Sync {
+    /// Called on a background thread for every change.
+    fn on_change(&self, kind: RouteChangeKind, route: RouteRecord);
+}
+
+/// A handle to the routing table, see [`Handle`](crate::Handle).
+#[derive(uniffi::Object)]
+pub struct RouteHandle(Arc<blocking::Handle>);
+
+#[uniffi::export]
+impl RouteHandle {
+    #[uniffi::constructor]
+    pub fn new() -> Result<Arc<Self>, RouteError> {
+        Ok(Arc::new(Self(Arc::new(blocking::Handle::new()?))))
+    }
+
+    pub fn list(&self) -> Result<Vec<RouteRecord>, RouteError> {
+        Ok(self.0.list()?.iter().map(RouteRecord::from).collect())
+    }
+
+    pub fn default_route(&self) -> Result<Option<RouteRecord>, RouteError> {
+        Ok(self.0.default_route()?.as_ref().map(RouteRecord::from))
+    }
+
+    pub fn add(&self, route: RouteRecord) -> Result<(), RouteError> {
+        Ok(self.0.add(&route.try_into()?)?)
+    }
+
+    pub fn delete(&self, route: RouteRecord) -> Result<(), RouteError> {
+        Ok(self.0.delete(&route.try_into()?)?)
+    }
+
+    /// Call `listener` for every change of the routing table until the subscription is
+    /// cancelled.
+    pub fn subscribe(&self, listener: Box<dyn RouteListener>) -> Arc<RouteSubscription> {
+        let handle = self.0.clone();
+        let (stop, stopped) = oneshot::channel();
+        let changes = handle.inner.route_listen_stream().take_until(stopped);
+        let thread = thread::spawn(move || {
+            handle.block_on(async {
+                futures::pin_mut!(changes);
+                while let Some(change) = changes.next().await {
+                    let (kind, route) = match &change {
+                        RouteChange::Add(route) => (RouteChangeKind::Add, route),
+                        RouteChange::Delete(route) => (RouteChangeKind::Delete, route),
+                        RouteChange::Change(route) => (RouteChangeKind::Change, route),
+                    };
+                    listener.on_change(kind, route.into());
+                }
+            })
+        });
+        Arc::new(RouteSubscription(Mutex::new(Some((stop, thread)))))
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+#[uniffi::export]
+impl RouteHandle {
+    pub fn list_rules(&self) -> Result<Vec<RuleRecord>, RouteError> {
+        let rules = self.0.block_on(self.0.inner.list_rules())?;
+        Ok(rules.iter().map(RuleRecord::from).collect())
+    }
+
+    pub fn add_rule(&self, rule: RuleRecord) -> Result<(), RouteError> {
+        let rule = rule.try_into()?;
+        Ok(self.0.block_on(self.0.inner.add_rules(vec![rule]))?)
+    }
+
+    pub fn delete_rule(&self, rule: RuleRecord) -> Result<(), RouteError> {
+        let rule = rule.try_into()?;
+        Ok(self.0.block_on(self.0.inner.delete_rules(vec![rule]))?)
+    }
+}
+
+/// A running subscription created by [`RouteHandle::subscribe`].
+#[derive(uniffi::Object)]
+pub struct RouteSubscription(Mutex<Option<(oneshot::Sender<()>, thread::JoinHandle<()>)>>);
+
+#[uniffi::export]
+impl RouteSubscription {
+    /// Stop the subscription and wait until the listener has returned for the last time. Must
+    /// not be called from the listener.
+    pub fn cancel(&self) {
+        let Some((stop, thread)) = self.0.lock().unwrap().take() else {
+            return;
+        };
+        _ = stop.send(());
+        _ = thread.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_route_records() {
+        let record = RouteRecord {
+            destination: "10.14.0.0".into(),
+            prefix: 24,
+            gateway: Some("192.1.2.1".into()),
+            ifindex: Some(9),
+            table: None,
+        };
+        let route = Route::try_from(record.clone()).unwrap();
+        assert_eq!(route.gateway, Some("192.1.2.1".parse().unwrap()));
+        assert_eq!(RouteRecord::from(&route).gateway, record.gateway);
+
+        let record = RouteRecord {
+            destination: "not an address".into(),
+            ..record
+        };
+        assert!(matches!(
+            Route::try_from(record),
+            Err(RouteError::InvalidRoute { .. })
+        ));
+    }
+}
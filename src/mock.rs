@@ -0,0 +1,573 @@
+use std::{
+    io,
+    ops::ControlFlow,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::sync::broadcast;
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+use crate::Rule;
+use crate::{DumpFilter, Error, IpVersion, Result, Route, RouteChange};
+
+/// The operations of a [`MockHandle`] that faults can be injected into.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockOperation {
+    Add,
+    Delete,
+    /// Listing routes, with or without a filter.
+    List,
+    DefaultRoute,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    AddRules,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    ListRules,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    DeleteRules,
+}
+
+struct Fault {
+    operation: MockOperation,
+    once: bool,
+    error: Box<dyn Fn() -> Error + Send>,
+}
+
+// A change made by another process, applied once the clock of the mock reaches `at`.
+struct Scheduled {
+    at: Duration,
+    change: RouteChange,
+}
+
+struct Table {
+    routes: Vec<Route>,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    rules: Vec<Rule>,
+    // the virtual time since the mock was created
+    now: Duration,
+    latency: Duration,
+    // by time, changes scheduled for the same time in the order they were scheduled
+    scheduled: Vec<Scheduled>,
+    faults: Vec<Fault>,
+    tx: broadcast::Sender<RouteChange>,
+}
+
+impl Table {
+    // Move the clock forward by `duration`, applying the scheduled changes that fall due.
+    fn elapse(&mut self, duration: Duration) {
+        let until = self.now + duration;
+        while self
+            .scheduled
+            .first()
+            .map_or(false, |next| next.at <= until)
+        {
+            let next = self.scheduled.remove(0);
+            self.now = self.now.max(next.at);
+            self.apply_external(next.change);
+        }
+        self.now = until;
+    }
+
+    fn apply_external(&mut self, change: RouteChange) {
+        let change = match change {
+            RouteChange::Add(route) | RouteChange::Change(route) => {
+                match self
+                    .routes
+                    .iter_mut()
+                    .find(|installed| same_route(&route, installed))
+                {
+                    Some(installed) => {
+                        *installed = route.clone();
+                        RouteChange::Change(route)
+                    }
+                    None => {
+                        self.routes.push(route.clone());
+                        RouteChange::Add(route)
+                    }
+                }
+            }
+            RouteChange::Delete(route) => {
+                let Some(i) = self
+                    .routes
+                    .iter()
+                    .position(|installed| deletes(&route, installed))
+                else {
+                    return;
+                };
+                RouteChange::Delete(self.routes.remove(i))
+            }
+        };
+        self.notify(change);
+    }
+
+    fn notify(&self, change: RouteChange) {
+        _ = self.tx.send(change);
+    }
+}
+
+/// A routing table in memory with the route, rule and event methods of
+/// [`Handle`](crate::Handle), for unit tests of routing logic that run without root and leave
+/// the system alone.
+///
+/// Clones share the table. Adding and removing routes follows the kernel: a route with the same
+/// destination, prefix, table and metric as an installed one can't be added again, and removing a
+/// route only compares the gateway and interface if they are set. Every change is sent to the
+/// streams of [`MockHandle::route_listen_stream`].
+///
+/// # Simulation
+///
+/// The mock keeps a virtual clock, which only moves when operations take the time set with
+/// [`MockHandle::with_latency`] and when the test calls [`MockHandle::advance`]. Changes made by
+/// other processes, e.g. the default route going away during a failover, are scheduled on this
+/// clock with [`MockHandle::schedule`] and applied with their events once it passes their time.
+/// Whether such a change happens before or after an operation only depends on the schedule and
+/// the latency, so tests of code reacting to events run the same way every time.
+///
+/// ```
+/// # fn main() -> net_route::Result<()> {
+/// # futures::executor::block_on(async {
+/// use std::time::Duration;
+/// use net_route::{MockHandle, Route, RouteChange};
+///
+/// let default = Route::new("0.0.0.0".parse().unwrap(), 0).with_ifindex(2);
+/// let handle = MockHandle::new()
+///     .with_routes([default.clone()])
+///     .with_latency(Duration::from_millis(10));
+/// handle.schedule(Duration::from_millis(25), RouteChange::Delete(default));
+///
+/// let route = Route::new("10.14.0.0".parse().unwrap(), 24).with_ifindex(9);
+/// handle.add(&route).await?;
+/// assert!(handle.default_route().await?.is_some());
+/// // the second operation ends after the default route was removed
+/// assert!(handle.default_route().await?.is_none());
+/// # Ok(())
+/// # })
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MockHandle {
+    table: Arc<Mutex<Table>>,
+}
+
+impl Default for MockHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Whether the kernel considers `a` and `b` the same route.
+fn same_route(a: &Route, b: &Route) -> bool {
+    if a.destination != b.destination || a.prefix != b.prefix {
+        return false;
+    }
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    if a.table != b.table {
+        return false;
+    }
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    if a.metric != b.metric {
+        return false;
+    }
+    true
+}
+
+// Whether removing `wanted` removes `installed`.
+fn deletes(wanted: &Route, installed: &Route) -> bool {
+    same_route(wanted, installed)
+        && (wanted.gateway.is_none() || wanted.gateway == installed.gateway)
+        && (wanted.ifindex.is_none() || wanted.ifindex == installed.ifindex)
+}
+
+// Like the kernel, rules removed without a priority match any priority.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+fn same_rule(wanted: &Rule, installed: &Rule) -> bool {
+    let priority = wanted.priority.or(installed.priority);
+    *wanted
+        == Rule {
+            priority,
+            ..installed.clone()
+        }
+}
+
+impl MockHandle {
+    /// Create a handle with an empty table.
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self {
+            table: Arc::new(Mutex::new(Table {
+                routes: vec![],
+                #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+                rules: vec![],
+                now: Duration::ZERO,
+                latency: Duration::ZERO,
+                scheduled: vec![],
+                faults: vec![],
+                tx,
+            })),
+        }
+    }
+
+    /// Install `routes` without sending events, e.g. the table of the scenario under test.
+    pub fn with_routes(self, routes: impl IntoIterator<Item = Route>) -> Self {
+        self.table.lock().unwrap().routes.extend(routes);
+        self
+    }
+
+    /// Install `rules`.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn with_rules(self, rules: impl IntoIterator<Item = Rule>) -> Self {
+        self.table.lock().unwrap().rules.extend(rules);
+        self
+    }
+
+    /// Set the virtual time every operation takes, 0 by default. Changes scheduled to happen
+    /// until an operation ends are applied before it.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.table.lock().unwrap().latency = latency;
+        self
+    }
+
+    /// Set how many changes are buffered for each stream returned by
+    /// [`MockHandle::route_listen_stream`], 16 by default and at least 1. Streams that fall
+    /// further behind skip the oldest changes, like the streams of a real handle, which tests
+    /// the handling of lost events.
+    ///
+    /// Meant to be set right after [`MockHandle::new`]: the streams created before, also from
+    /// clones of the handle, end as the channel they receive from is replaced.
+    pub fn with_event_capacity(self, capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        self.table.lock().unwrap().tx = tx;
+        self
+    }
+
+    /// Fail the next call of `operation` with the error returned by `error`, e.g.
+    /// `|| Error::Unsupported("not now".into())`, without changing the table. The latency of the
+    /// call still passes.
+    pub fn fail_next(&self, operation: MockOperation, error: impl Fn() -> Error + Send + 'static) {
+        self.inject(operation, true, Box::new(error));
+    }
+
+    /// Fail every call of `operation` with the error returned by `error` until
+    /// [`MockHandle::clear_faults`] is called, e.g. to test a missing permission.
+    pub fn fail_always(
+        &self,
+        operation: MockOperation,
+        error: impl Fn() -> Error + Send + 'static,
+    ) {
+        self.inject(operation, false, Box::new(error));
+    }
+
+    fn inject(&self, operation: MockOperation, once: bool, error: Box<dyn Fn() -> Error + Send>) {
+        self.table.lock().unwrap().faults.push(Fault {
+            operation,
+            once,
+            error,
+        });
+    }
+
+    /// Remove the faults injected with [`MockHandle::fail_next`] and
+    /// [`MockHandle::fail_always`].
+    pub fn clear_faults(&self) {
+        self.table.lock().unwrap().faults.clear();
+    }
+
+    /// Schedule a change made by another process at `at` on the virtual clock.
+    ///
+    /// Adding an installed route replaces it and sends [`RouteChange::Change`], removing a
+    /// route that isn't installed does nothing. Changes scheduled in the past are applied by the
+    /// next operation or call to [`MockHandle::advance`].
+    pub fn schedule(&self, at: Duration, change: RouteChange) {
+        let mut table = self.table.lock().unwrap();
+        let i = table
+            .scheduled
+            .partition_point(|scheduled| scheduled.at <= at);
+        table.scheduled.insert(i, Scheduled { at, change });
+    }
+
+    /// Move the virtual clock forward by `duration`, applying the scheduled changes that fall
+    /// due in the meantime.
+    pub fn advance(&self, duration: Duration) {
+        self.table.lock().unwrap().elapse(duration);
+    }
+
+    /// The virtual time since the mock was created.
+    pub fn now(&self) -> Duration {
+        self.table.lock().unwrap().now
+    }
+
+    // Lock the table once the latency of an operation passed, or fail with an injected fault.
+    fn operation(&self, operation: MockOperation) -> Result<MutexGuard<'_, Table>> {
+        let mut table = self.table.lock().unwrap();
+        let latency = table.latency;
+        table.elapse(latency);
+        let Some(i) = table
+            .faults
+            .iter()
+            .position(|fault| fault.operation == operation)
+        else {
+            return Ok(table);
+        };
+        let error = (table.faults[i].error)();
+        if table.faults[i].once {
+            table.faults.remove(i);
+        }
+        Err(error)
+    }
+
+    /// Add `route` to the table, failing with [`Error::AlreadyExists`] if it is installed.
+    pub async fn add(&self, route: &Route) -> Result<()> {
+        let mut table = self.operation(MockOperation::Add)?;
+        if table
+            .routes
+            .iter()
+            .any(|installed| same_route(route, installed))
+        {
+            return Err(Error::AlreadyExists(io::ErrorKind::AlreadyExists.into()));
+        }
+        table.routes.push(route.clone());
+        table.notify(RouteChange::Add(route.clone()));
+        Ok(())
+    }
+
+    /// Remove `route` from the table, failing with [`Error::NotFound`] if it isn't installed.
+    pub async fn delete(&self, route: &Route) -> Result<()> {
+        let mut table = self.operation(MockOperation::Delete)?;
+        let i = table
+            .routes
+            .iter()
+            .position(|installed| deletes(route, installed))
+            .ok_or_else(|| Error::NotFound(io::ErrorKind::NotFound.into()))?;
+        let removed = table.routes.remove(i);
+        table.notify(RouteChange::Delete(removed));
+        Ok(())
+    }
+
+    /// The routes in the table, in the order they were added.
+    pub async fn list(&self) -> Result<Vec<Route>> {
+        Ok(self.operation(MockOperation::List)?.routes.clone())
+    }
+
+    /// The routes in the table selected by `filter`.
+    pub async fn list_filtered(&self, filter: &DumpFilter) -> Result<Vec<Route>> {
+        let table = self.operation(MockOperation::List)?;
+        Ok(table
+            .routes
+            .iter()
+            .filter(|route| filter.matches(route))
+            .cloned()
+            .collect())
+    }
+
+    /// Hand the routes in the table selected by `filter` to `visit` until it breaks. Counts as a
+    /// [`MockOperation::List`].
+    pub async fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        mut visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let table = self.operation(MockOperation::List)?;
+        for route in table.routes.iter().filter(|route| filter.matches(route)) {
+            if visit(route).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The first IPv4 default route, or the first IPv6 one if there is none.
+    pub async fn default_route(&self) -> Result<Option<Route>> {
+        let table = self.operation(MockOperation::DefaultRoute)?;
+        let default = |version| {
+            table
+                .routes
+                .iter()
+                .find(|route| route.prefix == 0 && IpVersion::of(&route.destination) == version)
+                .cloned()
+        };
+        Ok(default(IpVersion::V4).or_else(|| default(IpVersion::V6)))
+    }
+
+    /// Returns a `Stream` yielding the changes made to the table after it is created.
+    pub fn route_listen_stream(&self) -> impl futures::Stream<Item = RouteChange> {
+        let rx = self.table.lock().unwrap().tx.subscribe();
+        futures::stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(change) => return Some((change, rx)),
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                }
+            }
+        })
+    }
+
+    /// Add `rules`, replacing equal rules like the real handle does.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub async fn add_rules(&self, rules: Vec<Rule>) -> Result<()> {
+        let mut table = self.operation(MockOperation::AddRules)?;
+        for rule in rules {
+            if !table.rules.contains(&rule) {
+                table.rules.push(rule);
+            }
+        }
+        Ok(())
+    }
+
+    /// The rules in the table, in the order they were added.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub async fn list_rules(&self) -> Result<Vec<Rule>> {
+        Ok(self.operation(MockOperation::ListRules)?.rules.clone())
+    }
+
+    /// Remove `rules`, failing with [`Error::NotFound`] after removing the others if one of them
+    /// isn't installed.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub async fn delete_rules(&self, rules: Vec<Rule>) -> Result<()> {
+        let mut table = self.operation(MockOperation::DeleteRules)?;
+        let mut missing = false;
+        for rule in &rules {
+            match table
+                .rules
+                .iter()
+                .position(|installed| same_rule(rule, installed))
+            {
+                Some(i) => {
+                    table.rules.remove(i);
+                }
+                None => missing = true,
+            }
+        }
+        if missing {
+            return Err(Error::NotFound(io::ErrorKind::NotFound.into()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use futures::{executor::block_on, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn it_behaves_like_the_kernel() {
+        block_on(async {
+            let handle = MockHandle::new();
+            let changes = handle.route_listen_stream();
+            futures::pin_mut!(changes);
+
+            let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+                .with_gateway("192.168.1.1".parse().unwrap())
+                .with_ifindex(3);
+            handle.add(&route).await.unwrap();
+            assert!(matches!(
+                handle.add(&route).await,
+                Err(Error::AlreadyExists(_))
+            ));
+            assert_eq!(changes.next().await, Some(RouteChange::Add(route.clone())));
+
+            // removing by destination alone removes the installed route
+            let wanted = Route::new("10.0.0.0".parse().unwrap(), 8);
+            handle.delete(&wanted).await.unwrap();
+            assert_eq!(changes.next().await, Some(RouteChange::Delete(route)));
+            assert!(matches!(
+                handle.delete(&wanted).await,
+                Err(Error::NotFound(_))
+            ));
+            assert!(handle.list().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn it_visits_routes_until_break() {
+        block_on(async {
+            let routes = (1..=3).map(|i| Route::new(format!("10.0.{}.0", i).parse().unwrap(), 24));
+            let handle = MockHandle::new().with_routes(routes);
+
+            let mut visited = vec![];
+            handle
+                .for_each_route(&DumpFilter::default(), |route| {
+                    visited.push(route.destination);
+                    if visited.len() == 2 {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                visited,
+                [
+                    "10.0.1.0".parse::<IpAddr>().unwrap(),
+                    "10.0.2.0".parse().unwrap()
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_applies_scheduled_changes_in_order() {
+        block_on(async {
+            let uplink = Route::new("0.0.0.0".parse().unwrap(), 0).with_ifindex(2);
+            let backup = Route::new("0.0.0.0".parse().unwrap(), 0).with_ifindex(3);
+            let handle = MockHandle::new()
+                .with_routes([uplink.clone()])
+                .with_latency(Duration::from_secs(1));
+            handle.schedule(Duration::from_secs(3), RouteChange::Add(backup.clone()));
+            handle.schedule(Duration::from_secs(2), RouteChange::Delete(uplink.clone()));
+            let changes = handle.route_listen_stream();
+            futures::pin_mut!(changes);
+
+            let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(9);
+            handle.add(&route).await.unwrap();
+            handle.advance(Duration::from_secs(2));
+            assert_eq!(handle.now(), Duration::from_secs(3));
+
+            assert_eq!(changes.next().await, Some(RouteChange::Add(route)));
+            assert_eq!(changes.next().await, Some(RouteChange::Delete(uplink)));
+            assert_eq!(changes.next().await, Some(RouteChange::Add(backup.clone())));
+            assert_eq!(handle.default_route().await.unwrap(), Some(backup));
+        });
+    }
+
+    #[test]
+    fn it_injects_faults() {
+        block_on(async {
+            let handle = MockHandle::new().with_event_capacity(1);
+            let changes = handle.route_listen_stream();
+            futures::pin_mut!(changes);
+
+            let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(3);
+            handle.fail_next(MockOperation::Add, || {
+                Error::PermissionDenied(io::ErrorKind::PermissionDenied.into())
+            });
+            assert!(matches!(
+                handle.add(&route).await,
+                Err(Error::PermissionDenied(_))
+            ));
+            handle.add(&route).await.unwrap();
+
+            handle.fail_always(MockOperation::List, || {
+                Error::Netlink(101, io::Error::from_raw_os_error(101))
+            });
+            assert!(matches!(handle.list().await, Err(Error::Netlink(101, _))));
+            assert!(matches!(handle.list().await, Err(Error::Netlink(101, _))));
+            handle.clear_faults();
+            assert_eq!(handle.list().await.unwrap(), [route.clone()]);
+
+            // the stream only keeps the latest change
+            handle.delete(&route).await.unwrap();
+            assert_eq!(changes.next().await, Some(RouteChange::Delete(route)));
+        });
+    }
+}
@@ -0,0 +1,89 @@
+use std::ops::ControlFlow;
+
+use crate::{
+    DumpFilter, Handle, HandleBuilder, ListCursor, Result, Route, RouteChange, RouteChunk,
+};
+
+/// A handle that only reads the routing table and follows its changes.
+///
+/// It offers no way to change routes, so it can be handed to code that must not modify the
+/// table and works without the permissions needed to do so.
+#[derive(Clone)]
+pub struct RouteMonitor(Handle);
+
+impl RouteMonitor {
+    pub fn new() -> Result<Self> {
+        HandleBuilder::new().build_monitor()
+    }
+
+    pub(crate) fn from_handle(handle: Handle) -> Self {
+        Self(handle)
+    }
+
+    /// Returns a `Stream` which will yield a `RouteChange` event whenever a route is added, removed, or changed from the system's routing table.
+    pub fn route_listen_stream(&self) -> impl futures::Stream<Item = RouteChange> {
+        self.0.route_listen_stream()
+    }
+
+    /// Returns a `Vec<Route>` containing a list of both ipv4 and v6 routes on the system.
+    pub async fn list(&self) -> Result<Vec<Route>> {
+        self.0.list().await
+    }
+
+    /// Returns a `Vec<Route>` containing the routes selected by `filter`.
+    pub async fn list_filtered(&self, filter: &DumpFilter) -> Result<Vec<Route>> {
+        self.0.list_filtered(filter).await
+    }
+
+    /// Hand the routes selected by `filter` to `visit` one at a time, see
+    /// [`Handle::for_each_route`].
+    pub async fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.0.for_each_route(filter, visit).await
+    }
+
+    /// Stream the routes selected by `filter` in chunks, see [`Handle::list_chunks`].
+    pub fn list_chunks(
+        &self,
+        filter: &DumpFilter,
+        chunk_size: usize,
+        resume: Option<ListCursor>,
+    ) -> impl futures::Stream<Item = Result<RouteChunk>> + Send + 'static {
+        self.0.list_chunks(filter, chunk_size, resume)
+    }
+
+    /// Get one of the default routes on the system if there is at least one.
+    pub async fn default_route(&self) -> Result<Option<Route>> {
+        self.0.default_route().await
+    }
+
+    /// Get the default route, only looking it up again after default routes changed, see
+    /// [`Handle::cached_default_route`].
+    pub async fn cached_default_route(&self) -> Result<Option<Route>> {
+        self.0.cached_default_route().await
+    }
+
+    /// Get the route the system would use to reach `destination`, see [`Handle::get_route`].
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia"
+    ))]
+    pub async fn get_route(&self, destination: std::net::IpAddr) -> Result<Route> {
+        self.0.get_route(destination).await
+    }
+
+    /// See [`Handle::close`].
+    pub async fn close(self) -> Result<()> {
+        self.0.close().await
+    }
+}
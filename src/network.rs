@@ -0,0 +1,100 @@
+// Conversions between the prefixes of routes and rules and the types of the ipnetwork crate.
+
+use ipnetwork::{IpNetwork, IpNetworkError, Ipv4Network, Ipv6Network};
+
+use crate::Route;
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+fn to_network((addr, prefix): (std::net::IpAddr, u8)) -> Option<IpNetwork> {
+    IpNetwork::new(addr, prefix).ok()
+}
+
+/// A route to the network, with the host bits of its address cleared.
+impl From<IpNetwork> for Route {
+    fn from(network: IpNetwork) -> Self {
+        Route::new(network.network(), network.prefix())
+    }
+}
+
+impl From<Ipv4Network> for Route {
+    fn from(network: Ipv4Network) -> Self {
+        IpNetwork::V4(network).into()
+    }
+}
+
+impl From<Ipv6Network> for Route {
+    fn from(network: Ipv6Network) -> Self {
+        IpNetwork::V6(network).into()
+    }
+}
+
+/// The destination network of the route, which fails if the prefix is too long for the address.
+impl TryFrom<&Route> for IpNetwork {
+    type Error = IpNetworkError;
+
+    fn try_from(route: &Route) -> Result<Self, IpNetworkError> {
+        IpNetwork::new(route.destination, route.prefix)
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl crate::Rule {
+    /// Match packets from `network`.
+    pub fn with_src_network(mut self, network: IpNetwork) -> Self {
+        self.src = Some((network.network(), network.prefix()));
+        self.v6 = network.is_ipv6();
+        self
+    }
+
+    /// Match packets to `network`.
+    pub fn with_dst_network(mut self, network: IpNetwork) -> Self {
+        self.dst = Some((network.network(), network.prefix()));
+        self.v6 = network.is_ipv6();
+        self
+    }
+
+    /// The network matched by the source of packets, `None` if the rule doesn't match on it or
+    /// the prefix is too long for the address.
+    pub fn src_network(&self) -> Option<IpNetwork> {
+        self.src.and_then(to_network)
+    }
+
+    /// The network matched by the destination of packets, see [`Rule::src_network`](Self::src_network).
+    pub fn dst_network(&self) -> Option<IpNetwork> {
+        self.dst.and_then(to_network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::*;
+
+    #[test]
+    fn it_converts_networks() {
+        let network: IpNetwork = "10.14.0.7/24".parse().unwrap();
+        let route = Route::from(network);
+        assert_eq!(route.destination, "10.14.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(route.prefix, 24);
+        assert_eq!(
+            IpNetwork::try_from(&route).unwrap(),
+            "10.14.0.0/24".parse::<IpNetwork>().unwrap()
+        );
+
+        let mut route = Route::new("10.14.0.0".parse().unwrap(), 33);
+        assert!(IpNetwork::try_from(&route).is_err());
+        route.prefix = 32;
+        assert!(IpNetwork::try_from(&route).is_ok());
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn it_converts_rule_networks() {
+        let network: IpNetwork = "2001:db8::/32".parse().unwrap();
+        let rule = crate::Rule::default().with_dst_network(network);
+        assert!(rule.v6);
+        assert_eq!(rule.dst_network(), Some(network));
+        assert_eq!(rule.src_network(), None);
+    }
+}
@@ -0,0 +1,341 @@
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+use std::ops::RangeInclusive;
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+use crate::Rule;
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+const RT_TABLE_MAIN: u32 = 254;
+use crate::{DumpFilter, Handle, Result, Route, RouteMatch, Transaction};
+
+/// A change computed by [`RoutePlan::plan`] to bring the system to the desired state.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanOp {
+    AddRoute(Route),
+    DeleteRoute(Route),
+    /// Remove `from` and add `to` in its place, for a route to the same destination in the same
+    /// table that differs in its gateway, interface, kind or metric.
+    ReplaceRoute {
+        from: Route,
+        to: Route,
+    },
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    AddRule(Rule),
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    DeleteRule(Rule),
+}
+
+impl PlanOp {
+    pub(crate) fn stage<'a>(&self, transaction: Transaction<'a>) -> Transaction<'a> {
+        match self.clone() {
+            PlanOp::AddRoute(route) => transaction.add_route(route),
+            PlanOp::DeleteRoute(route) => transaction.delete_route(route),
+            PlanOp::ReplaceRoute { from, to } => transaction.delete_route(from).add_route(to),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            PlanOp::AddRule(rule) => transaction.add_rule(rule),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            PlanOp::DeleteRule(rule) => transaction.delete_rule(rule),
+        }
+    }
+}
+
+// The fields identifying a route, the rest of it is replaced when it differs.
+fn route_key() -> RouteMatch {
+    let key = RouteMatch::new();
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    let key = key.with_table();
+    key
+}
+
+// Whether the installed route `current` is what `wanted` asks for. Fields left empty in `wanted`
+// are filled in by the system, so only the ones it sets are compared.
+fn route_satisfies(wanted: &Route, current: &Route) -> bool {
+    let key = match wanted.gateway {
+        Some(_) => RouteMatch::new().with_gateway(),
+        None => RouteMatch::new(),
+    };
+    let key = match wanted.ifindex {
+        Some(_) => key.with_ifindex(),
+        None => key,
+    };
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd"
+    ))]
+    let key = match wanted.metric {
+        Some(_) => key.with_metric(),
+        None => key,
+    };
+    key.matches(wanted, current) && wanted.kind == current.kind
+}
+
+// Like `route_satisfies` for rules, the kernel picks a priority for rules added without one and
+// they are added to the main table without a table.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+fn rule_satisfies(wanted: &Rule, current: &Rule) -> bool {
+    let wanted = Rule {
+        priority: wanted.priority.or(current.priority),
+        table_id: wanted.table_id.or(Some(RT_TABLE_MAIN)),
+        ..wanted.clone()
+    };
+    wanted == *current
+}
+
+/// The desired set of routes and rules, compared with the installed ones to compute the changes
+/// that get the system there.
+///
+/// Applying the same plan again changes nothing, which makes it suitable for configuration that
+/// is applied on every start. Installed routes and rules are only removed if they are in the
+/// scope of the plan and not part of it, so by default the plan only adds and replaces entries.
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> net_route::Result<()> {
+/// use net_route::{DumpFilter, Handle, Route, RoutePlan};
+///
+/// let handle = Handle::new()?;
+/// let plan = RoutePlan::new()
+///     .with_route(Route::new("10.14.0.0".parse().unwrap(), 24).with_ifindex(9))
+///     .with_scope(DumpFilter::new().with_oif(9));
+/// for op in plan.plan(&handle).await? {
+///     println!("{:?}", op);
+/// }
+/// plan.apply(&handle).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct RoutePlan {
+    routes: Vec<Route>,
+    scope: Option<DumpFilter>,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    rules: Vec<Rule>,
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    rule_scope: Option<RangeInclusive<u32>>,
+}
+
+impl RoutePlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `route` to the desired routes.
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Add `routes` to the desired routes.
+    pub fn with_routes(mut self, routes: impl IntoIterator<Item = Route>) -> Self {
+        self.routes.extend(routes);
+        self
+    }
+
+    /// Remove the installed routes selected by `scope` that aren't desired, e.g. every route
+    /// through the tunnel interface or in a dedicated table.
+    pub fn with_scope(mut self, scope: DumpFilter) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Add `rule` to the desired rules.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Remove the installed rules with a priority in `priorities` that aren't desired.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn with_rule_scope(mut self, priorities: RangeInclusive<u32>) -> Self {
+        self.rule_scope = Some(priorities);
+        self
+    }
+
+    /// The desired routes.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// The desired rules.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Compute the changes that bring the installed routes and rules to the desired state,
+    /// without applying them.
+    ///
+    /// Removals come first, then replacements and additions, the order [`RoutePlan::apply`]
+    /// applies them in.
+    pub async fn plan(&self, handle: &Handle) -> Result<Vec<PlanOp>> {
+        let mut deletes = vec![];
+        let mut changes = vec![];
+        self.diff_routes(&handle.list().await?, &mut deletes, &mut changes);
+        #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+        if !self.rules.is_empty() || self.rule_scope.is_some() {
+            self.diff_rules(&handle.list_rules().await?, &mut deletes, &mut changes);
+        }
+        deletes.extend(changes);
+        Ok(deletes)
+    }
+
+    /// Compute the changes and apply them, either all of them or none. The applied changes are
+    /// returned.
+    pub async fn apply(&self, handle: &Handle) -> Result<Vec<PlanOp>> {
+        let ops = self.plan(handle).await?;
+        let mut transaction = handle.transaction();
+        for op in &ops {
+            transaction = op.stage(transaction);
+        }
+        transaction.commit().await?;
+        Ok(ops)
+    }
+
+    fn diff_routes(&self, routes: &[Route], deletes: &mut Vec<PlanOp>, changes: &mut Vec<PlanOp>) {
+        let key = route_key();
+        // the installed routes taken by a desired route
+        let mut used = vec![false; routes.len()];
+
+        for wanted in &self.routes {
+            let candidate = |i: &usize| !used[*i] && key.matches(wanted, &routes[*i]);
+            let satisfied = (0..routes.len())
+                .filter(candidate)
+                .find(|i| route_satisfies(wanted, &routes[*i]));
+            let found = satisfied.or_else(|| (0..routes.len()).find(candidate));
+            match found {
+                Some(i) => {
+                    used[i] = true;
+                    if satisfied.is_none() {
+                        changes.push(PlanOp::ReplaceRoute {
+                            from: routes[i].clone(),
+                            to: wanted.clone(),
+                        });
+                    }
+                }
+                None => changes.push(PlanOp::AddRoute(wanted.clone())),
+            }
+        }
+        if let Some(scope) = &self.scope {
+            for (current, used) in routes.iter().zip(used) {
+                if !used && scope.matches(current) {
+                    deletes.push(PlanOp::DeleteRoute(current.clone()));
+                }
+            }
+        }
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    fn diff_rules(&self, rules: &[Rule], deletes: &mut Vec<PlanOp>, changes: &mut Vec<PlanOp>) {
+        let mut used = vec![false; rules.len()];
+        for wanted in &self.rules {
+            match rules
+                .iter()
+                .enumerate()
+                .find(|(i, current)| !used[*i] && rule_satisfies(wanted, current))
+            {
+                Some((i, _)) => used[i] = true,
+                None => changes.push(PlanOp::AddRule(wanted.clone())),
+            }
+        }
+        if let Some(scope) = &self.rule_scope {
+            for (current, used) in rules.iter().zip(used) {
+                let in_scope = current
+                    .priority
+                    .map_or(false, |priority| scope.contains(&priority));
+                if !used && in_scope {
+                    deletes.push(PlanOp::DeleteRule(current.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(plan: &RoutePlan, installed: &[Route]) -> Vec<PlanOp> {
+        let mut deletes = vec![];
+        let mut changes = vec![];
+        plan.diff_routes(installed, &mut deletes, &mut changes);
+        deletes.extend(changes);
+        deletes
+    }
+
+    #[test]
+    fn it_computes_changes() {
+        let installed = [
+            Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(3),
+            Route::new("10.1.0.0".parse().unwrap(), 16).with_ifindex(3),
+            Route::new("10.2.0.0".parse().unwrap(), 16).with_ifindex(3),
+            Route::new("10.3.0.0".parse().unwrap(), 16).with_ifindex(4),
+        ];
+        let plan = RoutePlan::new()
+            .with_route(Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(3))
+            .with_route(Route::new("10.1.0.0".parse().unwrap(), 16).with_ifindex(4))
+            .with_route(Route::new("10.4.0.0".parse().unwrap(), 16).with_ifindex(3))
+            .with_scope(DumpFilter::new().with_oif(3));
+
+        let ops = diff(&plan, &installed);
+        assert_eq!(
+            ops,
+            [
+                PlanOp::DeleteRoute(installed[2].clone()),
+                PlanOp::ReplaceRoute {
+                    from: installed[1].clone(),
+                    to: plan.routes()[1].clone(),
+                },
+                PlanOp::AddRoute(plan.routes()[2].clone()),
+            ]
+        );
+
+        let installed: Vec<_> = plan.routes().to_vec();
+        assert!(diff(&plan, &installed).is_empty());
+    }
+
+    #[test]
+    fn it_ignores_fields_filled_in_by_the_system() {
+        let plan =
+            RoutePlan::new().with_route(Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(3));
+        let installed = [Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_ifindex(3)
+            .with_gateway("192.168.1.1".parse().unwrap())];
+        assert!(diff(&plan, &installed).is_empty());
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn it_applies_rules_once() {
+        let wanted = Rule {
+            fw_mark_mask: Some((0x1, 0x1)),
+            ..Default::default()
+        };
+        let plan = RoutePlan::new().with_rule(wanted.clone());
+        let changes = |installed: &[Rule]| {
+            let mut deletes = vec![];
+            let mut changes = vec![];
+            plan.diff_rules(installed, &mut deletes, &mut changes);
+            deletes.extend(changes);
+            deletes
+        };
+        assert_eq!(changes(&[]), [PlanOp::AddRule(wanted.clone())]);
+
+        // listed the way the kernel reports the added rule
+        let added = Rule {
+            table_id: Some(RT_TABLE_MAIN),
+            priority: Some(32765),
+            ..wanted
+        };
+        assert!(changes(&[added.clone()]).is_empty());
+
+        let elsewhere = Rule {
+            table_id: Some(100),
+            ..added
+        };
+        assert_eq!(changes(&[elsewhere]).len(), 1);
+    }
+}
@@ -0,0 +1,733 @@
+use std::{
+    io::{self, ErrorKind},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::prelude::FromRawFd,
+    ptr,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use crate::error::invalid_route;
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
+use async_stream::stream;
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::platform_impl::bsd::bind::*;
+use crate::platform_impl::runtime::{self, AsyncReadExt, AsyncWriteExt, Task, UnixStream};
+use crate::{Capabilities, DumpFilter, HandleBuilder, IpVersion, Route, RouteChange, RouteKind};
+
+// FreeBSD, NetBSD and OpenBSD share the routing socket of 4.4BSD, see route(4) and
+// sbin/route/route.c of each. The differences handled here:
+// - OpenBSD carries the header length in rtm_hdrlen and the sockaddrs start after it, and ranks
+//   routes to the same destination by rtm_priority.
+// - NetBSD pads the sockaddrs to 8 bytes on every architecture instead of sizeof(long).
+// - FreeBSD has several routing tables (FIBs). Dumps select one by the last mib element, and
+//   requests apply to the FIB of the routing socket set with SO_SETFIB.
+// - OpenBSD has routing domains. Dumps select one by the last mib element, requests by
+//   rtm_tableid, and sockets only receive the changes of the one set with ROUTE_TABLEFILTER.
+
+const HDR_SIZE: usize = mem::size_of::<rt_msghdr>();
+
+const ESRCH: i32 = 3;
+const ENOMEM: i32 = 12;
+
+// RTF_LLINFO, called RTF_LLDATA in newer releases, marks the entries of the ARP and neighbor cache
+const RTF_LLDATA: u32 = 0x400;
+
+#[cfg(target_os = "netbsd")]
+const SA_ALIGN: usize = mem::size_of::<u64>();
+#[cfg(not(target_os = "netbsd"))]
+const SA_ALIGN: usize = mem::size_of::<std::os::raw::c_long>();
+
+pub(crate) struct Handle {
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    tx: broadcast::Sender<RouteChange>,
+    listen_handle: Option<Task>,
+    #[cfg(target_os = "openbsd")]
+    rdomain: u32,
+}
+
+impl Handle {
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
+        #[cfg(target_os = "openbsd")]
+        let rdomain = match config.rdomain {
+            Some(rdomain) => {
+                // fails with EINVAL if the routing domain doesn't exist
+                set_table_filter(&route_socket()?, rdomain)?;
+                rdomain
+            }
+            None => unsafe { getrtable() as u32 },
+        };
+        let listen_handle = match config.listen {
+            true => {
+                let sock = route_socket()?;
+                #[cfg(target_os = "openbsd")]
+                if config.rdomain.is_some() {
+                    set_table_filter(&sock, rdomain)?;
+                }
+                Some(Task::spawn(Self::listen(tx.clone(), sock)))
+            }
+            false => None,
+        };
+
+        Ok(Self {
+            tx,
+            listen_handle,
+            #[cfg(target_os = "openbsd")]
+            rdomain,
+        })
+    }
+
+    // The listener only reads from the routing socket, it's stopped right away and joined so the
+    // socket is closed once this returns.
+    pub(crate) async fn close(mut self) -> io::Result<()> {
+        let Some(listen_handle) = &mut self.listen_handle else {
+            return Ok(());
+        };
+        listen_handle.abort();
+        listen_handle.join().await
+    }
+
+    // a header for a request of type `rtm_type` in the routing domain of this handle
+    fn header(&self, rtm_type: u32) -> rt_msghdr {
+        let mut hdr: rt_msghdr = unsafe { mem::zeroed() };
+        hdr.rtm_type = rtm_type as u8;
+        #[cfg(target_os = "openbsd")]
+        {
+            hdr.rtm_tableid = self.rdomain as u16;
+        }
+        hdr
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        Ok(Capabilities {
+            modify_routes: unsafe { geteuid() } == 0,
+            modify_rules: false,
+        })
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        // the default route used by this process is the one in its FIB
+        #[cfg(target_os = "freebsd")]
+        let filter = DumpFilter::new().with_table(sysctl_u32("net.my_fibnum")?);
+        #[cfg(not(target_os = "freebsd"))]
+        let filter = DumpFilter::new();
+        // on OpenBSD several default routes can coexist, they are dumped by priority and the
+        // first one is in use
+        Ok(self
+            .list_filtered(&filter)
+            .await?
+            .into_iter()
+            .find(|route| {
+                route.destination.is_unspecified()
+                    && route.prefix == 0
+                    && route
+                        .gateway
+                        .map_or(false, |gateway| !gateway.is_unspecified())
+            }))
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
+        change_route(self.header(RTM_DELETE), route).await
+    }
+
+    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
+        change_route(self.header(RTM_ADD), route).await
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        self.list_filtered(&DumpFilter::new()).await
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let family = match filter.family {
+            Some(IpVersion::V4) => AF_INET,
+            Some(IpVersion::V6) => AF_INET6,
+            None => 0,
+        };
+        #[cfg(target_os = "freebsd")]
+        let mut routes = match filter.table {
+            Some(fib) => list_routes(family, fib)?,
+            None => {
+                let mut routes = vec![];
+                for fib in 0..sysctl_u32("net.fibs")? {
+                    routes.extend(list_routes(family, fib)?);
+                }
+                routes
+            }
+        };
+        #[cfg(target_os = "openbsd")]
+        let mut routes = list_routes(family, self.rdomain)?;
+        #[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+        let mut routes = list_routes(family, 0)?;
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
+    }
+
+    pub(crate) async fn get_route(&self, destination: IpAddr) -> io::Result<Route> {
+        let mut hdr = self.header(RTM_GET);
+        hdr.rtm_addrs = (RTA_DST | RTA_IFP) as i32;
+        // an empty link address asks the kernel to include the interface in the reply
+        let addrs = [ip_to_sockaddr(destination), link_to_sockaddr(0)];
+        let reply = request(route_socket()?, hdr, &addrs).await?;
+        let hdr = read_header(&reply)
+            .ok_or_else(|| io::Error::new(ErrorKind::Other, "Unexpected message len"))?;
+        message_to_route(&hdr, &reply[body_offset(&hdr)..])
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "No route found for destination"))
+    }
+
+    async fn listen(tx: broadcast::Sender<RouteChange>, mut sock: UnixStream) {
+        // the socket only receives changes of the FIB of the process
+        #[cfg(target_os = "freebsd")]
+        let fib = sysctl_u32("net.my_fibnum").unwrap_or(0);
+        let mut buf = [0u8; 2048];
+        loop {
+            let read = match sock.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let Some(hdr) = read_header(&buf[..read]) else {
+                continue;
+            };
+            let change: fn(Route) -> RouteChange = match hdr.rtm_type as u32 {
+                RTM_ADD => RouteChange::Add,
+                RTM_DELETE => RouteChange::Delete,
+                RTM_CHANGE => RouteChange::Change,
+                _ => continue,
+            };
+            let len = (hdr.rtm_msglen as usize).min(read);
+            if let Some(route) = message_to_route(&hdr, &buf[body_offset(&hdr)..len]) {
+                #[cfg(target_os = "freebsd")]
+                let route = route.with_table(fib);
+                let change = change(route);
+                #[cfg(feature = "tracing")]
+                tracing::trace!(?change, "route changed");
+                _ = tx.send(change);
+            }
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Some(listen_handle) = &self.listen_handle {
+            listen_handle.abort();
+        }
+    }
+}
+
+fn route_socket() -> io::Result<UnixStream> {
+    let fd = unsafe { socket(PF_ROUTE as i32, SOCK_RAW as i32, AF_UNSPEC as i32) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    runtime::async_socket(route_fd)
+}
+
+#[cfg(target_os = "freebsd")]
+fn set_fib(sock: &UnixStream, fib: u32) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fib = fib as i32;
+    if unsafe {
+        setsockopt(
+            sock.as_raw_fd(),
+            SOL_SOCKET as i32,
+            SO_SETFIB as i32,
+            &fib as *const i32 as *const _,
+            mem::size_of::<i32>() as u32,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "openbsd")]
+fn set_table_filter(sock: &UnixStream, rdomain: u32) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe {
+        setsockopt(
+            sock.as_raw_fd(),
+            PF_ROUTE as i32,
+            ROUTE_TABLEFILTER as i32,
+            &rdomain as *const u32 as *const _,
+            mem::size_of::<u32>() as u32,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "freebsd")]
+fn sysctl_u32(name: &str) -> io::Result<u32> {
+    let name = std::ffi::CString::new(name).expect("sysctl name contains a nul byte");
+    let mut value = 0u32;
+    let mut len = mem::size_of::<u32>();
+    if unsafe {
+        sysctlbyname(
+            name.as_ptr(),
+            &mut value as *mut u32 as *mut _,
+            &mut len,
+            ptr::null_mut(),
+            0,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+fn code_to_error(err: i32) -> io::Error {
+    match err {
+        ESRCH => io::Error::new(ErrorKind::NotFound, "no such route"),
+        _ => io::Error::from_raw_os_error(err),
+    }
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+// messages are only byte aligned in the read buffers
+fn read_header(buf: &[u8]) -> Option<rt_msghdr> {
+    if buf.len() < HDR_SIZE {
+        return None;
+    }
+    let hdr: rt_msghdr = unsafe { ptr::read_unaligned(buf.as_ptr() as *const rt_msghdr) };
+    let valid = hdr.rtm_version as u32 == RTM_VERSION
+        && body_offset(&hdr) >= HDR_SIZE
+        && body_offset(&hdr) <= hdr.rtm_msglen as usize;
+    valid.then_some(hdr)
+}
+
+// where the sockaddrs following the header start
+#[cfg(target_os = "openbsd")]
+fn body_offset(hdr: &rt_msghdr) -> usize {
+    hdr.rtm_hdrlen as usize
+}
+
+#[cfg(not(target_os = "openbsd"))]
+fn body_offset(_: &rt_msghdr) -> usize {
+    HDR_SIZE
+}
+
+// the sockaddrs following the header are padded, see SA_SIZE() or ROUNDUP() in route.c
+fn sa_size(sa_len: u8) -> usize {
+    if sa_len == 0 {
+        SA_ALIGN
+    } else {
+        1 + ((sa_len as usize - 1) | (SA_ALIGN - 1))
+    }
+}
+
+// split the sockaddrs of a message by their RTAX_* index
+fn route_addrs(rtm_addrs: i32, mut msg: &[u8]) -> [Option<&[u8]>; RTAX_MAX as usize] {
+    let mut addrs = [None; RTAX_MAX as usize];
+    for (idx, addr) in addrs.iter_mut().enumerate() {
+        if rtm_addrs & (1 << idx) == 0 {
+            continue;
+        }
+        let Some(&sa_len) = msg.first() else {
+            break;
+        };
+        *addr = Some(&msg[..(sa_len as usize).min(msg.len())]);
+        msg = &msg[sa_size(sa_len).min(msg.len())..];
+    }
+    addrs
+}
+
+fn sa_to_ip(sa: &[u8]) -> Option<IpAddr> {
+    match *sa.get(1)? as u32 {
+        AF_INET => {
+            let octets: [u8; 4] = sa.get(4..8)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        AF_INET6 => {
+            let mut octets: [u8; 16] = sa.get(8..24)?.try_into().ok()?;
+            // the kernel embeds the scope id of link-local unicast and interface- or link-local
+            // multicast addresses in bytes 2 and 3, see the KAME notes in route.c
+            let link_local = octets[0] == 0xfe && octets[1] & 0xc0 == 0x80;
+            let local_multicast = octets[0] == 0xff && matches!(octets[1] & 0x0f, 1 | 2);
+            if link_local || local_multicast {
+                octets[2] = 0;
+                octets[3] = 0;
+            }
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+// the kernel trims trailing zero bytes from netmasks, so the sockaddr can be shorter than the
+// address and even have no family
+fn sa_to_prefix(sa: &[u8], destination: IpAddr) -> u8 {
+    let (offset, len) = match destination {
+        IpAddr::V4(_) => (4, 4),
+        IpAddr::V6(_) => (8, 16),
+    };
+    sa.iter()
+        .skip(offset)
+        .take(len)
+        .map(|byte| byte.count_ones())
+        .sum::<u32>() as u8
+}
+
+fn ip_to_sockaddr(ip: IpAddr) -> Vec<u8> {
+    let mut sa = match ip {
+        IpAddr::V4(addr) => {
+            let mut sa = vec![0u8; mem::size_of::<sockaddr_in>()];
+            sa[4..8].copy_from_slice(&addr.octets());
+            sa[1] = AF_INET as u8;
+            sa
+        }
+        IpAddr::V6(addr) => {
+            let mut sa = vec![0u8; mem::size_of::<sockaddr_in6>()];
+            sa[8..24].copy_from_slice(&addr.octets());
+            sa[1] = AF_INET6 as u8;
+            sa
+        }
+    };
+    sa[0] = sa.len() as u8;
+    sa
+}
+
+fn link_to_sockaddr(ifindex: u32) -> Vec<u8> {
+    let mut sdl: sockaddr_dl = unsafe { mem::zeroed() };
+    sdl.sdl_len = mem::size_of::<sockaddr_dl>() as u8;
+    sdl.sdl_family = AF_LINK as u8;
+    sdl.sdl_index = ifindex as u16;
+    as_bytes(&sdl).to_vec()
+}
+
+fn push_sockaddr(msg: &mut Vec<u8>, sa: &[u8]) {
+    msg.extend_from_slice(sa);
+    msg.resize(msg.len() + sa_size(sa[0]) - sa.len(), 0);
+}
+
+fn message_to_route(hdr: &rt_msghdr, msg: &[u8]) -> Option<Route> {
+    let flags = hdr.rtm_flags as u32;
+    if flags & RTF_LLDATA != 0 {
+        return None;
+    }
+
+    let addrs = route_addrs(hdr.rtm_addrs, msg);
+    let destination = sa_to_ip(addrs[RTAX_DST as usize]?)?;
+
+    let prefix = match addrs[RTAX_NETMASK as usize] {
+        Some(mask) if flags & RTF_HOST == 0 => sa_to_prefix(mask, destination),
+        _ => match destination {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        },
+    };
+
+    let mut route = Route::new(destination, prefix).with_ifindex(hdr.rtm_index as u32);
+    // interface routes have a link address as gateway
+    route.gateway = addrs[RTAX_GATEWAY as usize].and_then(sa_to_ip);
+    // the RTP_DOWN bit marks routes whose interface is down
+    #[cfg(target_os = "openbsd")]
+    {
+        route.metric = Some(hdr.rtm_priority as u32 & RTP_MASK);
+    }
+    route.kind = if flags & RTF_BLACKHOLE != 0 {
+        RouteKind::Blackhole
+    } else if flags & RTF_REJECT != 0 {
+        RouteKind::Unreachable
+    } else if flags & (RTF_LOCAL | RTF_BROADCAST) != 0 {
+        RouteKind::Other
+    } else {
+        RouteKind::Unicast
+    };
+    Some(route)
+}
+
+// `table` selects the FIB on FreeBSD and the routing domain on OpenBSD, and has to be 0 on NetBSD
+fn try_get_msg_buf(family: u32, table: u32) -> io::Result<Vec<u8>> {
+    const MAX_RETRYS: usize = 3;
+
+    let mut mib = [
+        CTL_NET as i32,
+        PF_ROUTE as i32,
+        0,
+        family as i32, // 0 for both ipv4 & ipv6
+        NET_RT_DUMP as i32,
+        table as i32,
+    ];
+    for _ in 0..MAX_RETRYS {
+        let mut len = 0;
+        if unsafe {
+            sysctl(
+                mib.as_mut_ptr(),
+                mib.len() as u32,
+                ptr::null_mut(),
+                &mut len,
+                ptr::null_mut(),
+                0,
+            )
+        } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; len];
+        if unsafe {
+            sysctl(
+                mib.as_mut_ptr(),
+                mib.len() as u32,
+                buf.as_mut_ptr() as *mut _,
+                &mut len,
+                ptr::null_mut(),
+                0,
+            )
+        } < 0
+        {
+            // the table grew between the two calls
+            if io::Error::last_os_error().raw_os_error() == Some(ENOMEM) {
+                continue;
+            }
+            return Err(io::Error::last_os_error());
+        }
+        buf.truncate(len);
+        return Ok(buf);
+    }
+
+    Err(io::Error::new(
+        ErrorKind::Other,
+        "Failed to get routing table",
+    ))
+}
+
+fn list_routes(family: u32, table: u32) -> io::Result<Vec<Route>> {
+    let buf = try_get_msg_buf(family, table)?;
+
+    let mut routes = vec![];
+    let mut offset = 0;
+    while let Some(hdr) = read_header(&buf[offset..]) {
+        let msg_len = hdr.rtm_msglen as usize;
+        if offset + msg_len > buf.len() {
+            break;
+        }
+        let msg = &buf[offset + body_offset(&hdr)..offset + msg_len];
+        if let Some(route) = message_to_route(&hdr, msg) {
+            #[cfg(target_os = "freebsd")]
+            let route = route.with_table(table);
+            routes.push(route);
+        }
+        offset += msg_len;
+    }
+    Ok(routes)
+}
+
+static RTM_SEQ: AtomicI32 = AtomicI32::new(1);
+
+// Send a message with the sockaddrs in `addrs` on `sock` and return the reply of the kernel.
+async fn request(
+    mut sock: UnixStream,
+    mut hdr: rt_msghdr,
+    addrs: &[Vec<u8>],
+) -> io::Result<Vec<u8>> {
+    let mut body = vec![];
+    for sa in addrs {
+        push_sockaddr(&mut body, sa);
+    }
+    let seq = RTM_SEQ.fetch_add(1, Ordering::Relaxed);
+    hdr.rtm_msglen = (HDR_SIZE + body.len()) as u16;
+    hdr.rtm_version = RTM_VERSION as u8;
+    #[cfg(target_os = "openbsd")]
+    {
+        hdr.rtm_hdrlen = HDR_SIZE as u16;
+    }
+    hdr.rtm_seq = seq;
+    let mut msg = as_bytes(&hdr).to_vec();
+    msg.extend_from_slice(&body);
+
+    if let Err(e) = sock.write_all(&msg).await {
+        // the kernel fails the write with the error of the request, e.g. EEXIST
+        return Err(match e.raw_os_error() {
+            Some(err) => code_to_error(err),
+            None => e,
+        });
+    }
+
+    // the socket also receives every other routing message, wait for the reply to ours
+    let pid = unsafe { getpid() };
+    let mut buf = [0u8; 2048];
+    loop {
+        let read = sock.read(&mut buf).await?;
+        let Some(reply) = read_header(&buf[..read]) else {
+            continue;
+        };
+        if reply.rtm_pid != pid || reply.rtm_seq != seq {
+            continue;
+        }
+        if reply.rtm_errno != 0 {
+            return Err(code_to_error(reply.rtm_errno));
+        }
+        return Ok(buf[..(reply.rtm_msglen as usize).min(read)].to_vec());
+    }
+}
+
+async fn change_route(mut hdr: rt_msghdr, route: &Route) -> io::Result<()> {
+    let max_prefix = match route.destination {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    let rtm_type = hdr.rtm_type as u32;
+    let mut flags = RTF_UP | RTF_STATIC;
+    let mut rtm_addrs = RTA_DST;
+    let mut addrs = vec![ip_to_sockaddr(route.destination)];
+
+    // the priority picks one of several routes to the same destination when deleting, a
+    // priority of 0 lets the kernel use the default for static routes
+    #[cfg(target_os = "openbsd")]
+    if let Some(metric) = route.metric {
+        if metric == 0 || metric > RTP_MASK {
+            return Err(invalid_route("route priority must be between 1 and 63"));
+        }
+        hdr.rtm_priority = metric as u8;
+    }
+
+    let gateway = match (route.gateway, route.ifindex) {
+        (Some(gateway), _) => {
+            flags |= RTF_GATEWAY;
+            Some(ip_to_sockaddr(gateway))
+        }
+        // deleting only needs a gateway to pick one of several paths
+        _ if rtm_type == RTM_DELETE => None,
+        (None, Some(ifindex)) => Some(link_to_sockaddr(ifindex)),
+        // like `route add -blackhole`, dropping routes still need a next hop, use loopback
+        (None, None) if route.kind != RouteKind::Unicast => {
+            Some(ip_to_sockaddr(match route.destination {
+                IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            }))
+        }
+        (None, None) => return Err(invalid_route("routes need a gateway or an ifindex")),
+    };
+    if let Some(gateway) = gateway {
+        rtm_addrs |= RTA_GATEWAY;
+        addrs.push(gateway);
+    }
+
+    if route.prefix >= max_prefix {
+        flags |= RTF_HOST;
+    } else {
+        rtm_addrs |= RTA_NETMASK;
+        addrs.push(ip_to_sockaddr(route.mask()));
+    }
+
+    // pin a route with a gateway to the given interface, like `route add -ifp`
+    if let (Some(_), Some(ifindex)) = (route.gateway, route.ifindex) {
+        if rtm_type == RTM_ADD {
+            rtm_addrs |= RTA_IFP;
+            addrs.push(link_to_sockaddr(ifindex));
+        }
+    }
+
+    if rtm_type == RTM_ADD {
+        flags |= match route.kind {
+            RouteKind::Unicast => 0,
+            RouteKind::Blackhole => RTF_BLACKHOLE,
+            RouteKind::Unreachable | RouteKind::Prohibit => RTF_REJECT,
+            RouteKind::Other => return Err(invalid_route("routes of other kinds can't be added")),
+        };
+    }
+
+    hdr.rtm_flags = flags as i32;
+    hdr.rtm_addrs = rtm_addrs as i32;
+    let sock = route_socket()?;
+    #[cfg(target_os = "freebsd")]
+    set_fib(&sock, route.table)?;
+    request(sock, hdr, &addrs).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_trimmed_netmasks() {
+        // a /16 netmask as sent by the kernel, without the trailing zero bytes
+        let mask = [6, 0, 0, 0, 255, 255];
+        assert_eq!(sa_to_prefix(&mask, "10.1.0.0".parse().unwrap()), 16);
+        // the default route has an empty netmask
+        assert_eq!(sa_to_prefix(&[0], "0.0.0.0".parse().unwrap()), 0);
+    }
+
+    #[test]
+    fn it_splits_padded_sockaddrs() {
+        let mut msg = vec![];
+        push_sockaddr(&mut msg, &ip_to_sockaddr("10.1.0.0".parse().unwrap()));
+        push_sockaddr(&mut msg, &[6, 0, 0, 0, 255, 255]);
+
+        let addrs = route_addrs((RTA_DST | RTA_NETMASK) as i32, &msg);
+        assert_eq!(
+            sa_to_ip(addrs[RTAX_DST as usize].unwrap()),
+            Some("10.1.0.0".parse().unwrap())
+        );
+        assert_eq!(
+            addrs[RTAX_NETMASK as usize],
+            Some(&[6, 0, 0, 0, 255, 255][..])
+        );
+        assert_eq!(addrs[RTAX_GATEWAY as usize], None);
+    }
+
+    #[cfg(target_os = "openbsd")]
+    #[test]
+    fn it_reads_priorities() {
+        let mut msg = vec![];
+        push_sockaddr(&mut msg, &ip_to_sockaddr("0.0.0.0".parse().unwrap()));
+        push_sockaddr(&mut msg, &ip_to_sockaddr("192.168.1.1".parse().unwrap()));
+        push_sockaddr(&mut msg, &[0]);
+
+        let mut hdr: rt_msghdr = unsafe { mem::zeroed() };
+        hdr.rtm_flags = (RTF_UP | RTF_GATEWAY) as i32;
+        hdr.rtm_addrs = (RTA_DST | RTA_GATEWAY | RTA_NETMASK) as i32;
+        hdr.rtm_priority = (RTP_STATIC | RTP_DOWN) as u8;
+
+        let route = message_to_route(&hdr, &msg).unwrap();
+        assert_eq!(route.prefix, 0);
+        assert_eq!(route.gateway, Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(route.metric, Some(RTP_STATIC));
+    }
+}
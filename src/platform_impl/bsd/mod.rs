@@ -0,0 +1,4 @@
+mod bind;
+pub(crate) mod bsd;
+
+pub(crate) use bsd::Handle;
@@ -0,0 +1,396 @@
+use std::{
+    io::{self, ErrorKind},
+    net::IpAddr,
+};
+
+use crate::error::invalid_route;
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
+use async_stream::stream;
+use fidl_fuchsia_net as fnet;
+use fidl_fuchsia_net_interfaces_admin as fnet_interfaces_admin;
+use fidl_fuchsia_net_root as fnet_root;
+use fidl_fuchsia_net_routes as fnet_routes;
+use fidl_fuchsia_net_routes_admin as fnet_routes_admin;
+use fidl_fuchsia_net_routes_ext as fnet_routes_ext;
+use fnet_routes_ext::admin::FidlRouteAdminIpExt;
+use fnet_routes_ext::FidlRouteIpExt;
+use fuchsia_async as fasync;
+use fuchsia_component::client::connect_to_protocol;
+use futures::StreamExt;
+use net_types::{
+    ip::{Ip, Ipv4, Ipv4Addr, Ipv6, Ipv6Addr, Subnet},
+    SpecifiedAddr,
+};
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::{Capabilities, DumpFilter, HandleBuilder, IpVersion, Route, RouteChange, RouteKind};
+
+// Fuchsia has no routing socket, the netstack serves the table over FIDL: fuchsia.net.routes
+// to read and watch it, and fuchsia.net.routes.admin to change it through route sets. Routes
+// added to a route set are removed by the netstack when the set is closed, so the routes added
+// through a handle only live as long as the handle. Adding routes through an interface needs
+// an authorization from fuchsia.net.root, which has to be routed to the component.
+//
+// The FIDL bindings and the fuchsia-* crates aren't published on crates.io, this backend is
+// built inside the Fuchsia tree where they are provided by the build.
+
+pub(crate) struct Handle {
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    tx: broadcast::Sender<RouteChange>,
+    state: fnet_routes::StateProxy,
+    route_set_v4: fnet_routes_admin::RouteSetV4Proxy,
+    route_set_v6: fnet_routes_admin::RouteSetV6Proxy,
+    _watchers: Vec<fasync::Task<()>>,
+}
+
+impl Handle {
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
+
+        let watchers = match config.listen {
+            true => vec![
+                fasync::Task::spawn(Self::listen::<Ipv4>(tx.clone())),
+                fasync::Task::spawn(Self::listen::<Ipv6>(tx.clone())),
+            ],
+            false => vec![],
+        };
+
+        Ok(Self {
+            tx,
+            state: connect_to_protocol::<fnet_routes::StateMarker>().map_err(other)?,
+            route_set_v4: new_route_set::<Ipv4>()?,
+            route_set_v6: new_route_set::<Ipv6>()?,
+            _watchers: watchers,
+        })
+    }
+
+    pub(crate) async fn close(self) -> io::Result<()> {
+        // Dropping the watchers cancels them. Closing the route sets removes the routes added
+        // through them, as when the handle is dropped.
+        drop(self);
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        // whether the admin protocols are routed to the component only shows once they are used
+        Ok(Capabilities {
+            modify_routes: true,
+            modify_rules: false,
+        })
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        let mut routes = installed_routes::<Ipv4>().await?;
+        routes.extend(installed_routes::<Ipv6>().await?);
+        // the netstack uses the default route with the lowest effective metric
+        routes.sort_by_key(|(_, metric)| *metric);
+        Ok(routes.into_iter().map(|(route, _)| route).find(|route| {
+            route.destination.is_unspecified() && route.prefix == 0 && route.gateway.is_some()
+        }))
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
+        self.change_route(route, false).await
+    }
+
+    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
+        self.change_route(route, true).await
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        self.list_filtered(&DumpFilter::new()).await
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let mut routes = vec![];
+        if filter.includes(IpVersion::V4) {
+            routes.extend(installed_routes::<Ipv4>().await?);
+        }
+        if filter.includes(IpVersion::V6) {
+            routes.extend(installed_routes::<Ipv6>().await?);
+        }
+        Ok(routes
+            .into_iter()
+            .map(|(route, _)| route)
+            .filter(|route| filter.matches(route))
+            .collect())
+    }
+
+    pub(crate) async fn get_route(&self, destination: IpAddr) -> io::Result<Route> {
+        let resolved = self
+            .state
+            .resolve(&to_fidl_addr(destination))
+            .await
+            .map_err(other)?
+            .map_err(|status| match status {
+                // ZX_ERR_ADDRESS_UNREACHABLE
+                -41 => io::Error::new(ErrorKind::NotFound, "No route found for destination"),
+                status => io::Error::new(ErrorKind::Other, format!("zx status {}", status)),
+            })?;
+
+        let prefix = match destination {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let mut route = Route::new(destination, prefix);
+        match resolved {
+            fnet_routes::Resolved::Direct(dest) => {
+                route.ifindex = dest.interface_id.map(|id| id as u32);
+            }
+            fnet_routes::Resolved::Gateway(dest) => {
+                route.ifindex = dest.interface_id.map(|id| id as u32);
+                route.gateway = dest.address.map(from_fidl_addr);
+            }
+        }
+        Ok(route)
+    }
+
+    async fn change_route(&self, route: &Route, add: bool) -> io::Result<()> {
+        let ifindex = route
+            .ifindex
+            .ok_or_else(|| invalid_route("routes need an ifindex on Fuchsia"))?;
+        let mismatch = || invalid_route("gateway version must match destination");
+        match route.destination {
+            IpAddr::V4(destination) => {
+                let gateway = match route.gateway {
+                    Some(IpAddr::V4(gateway)) => Some(Ipv4Addr::from(gateway)),
+                    Some(IpAddr::V6(_)) => return Err(mismatch()),
+                    None => None,
+                };
+                let fidl_route = fidl_route::<Ipv4>(destination.into(), gateway, ifindex, route)?;
+                change::<Ipv4>(&self.route_set_v4, ifindex, fidl_route, add).await
+            }
+            IpAddr::V6(destination) => {
+                let gateway = match route.gateway {
+                    Some(IpAddr::V6(gateway)) => Some(Ipv6Addr::from(gateway)),
+                    Some(IpAddr::V4(_)) => return Err(mismatch()),
+                    None => None,
+                };
+                let fidl_route = fidl_route::<Ipv6>(destination.into(), gateway, ifindex, route)?;
+                change::<Ipv6>(&self.route_set_v6, ifindex, fidl_route, add).await
+            }
+        }
+    }
+
+    async fn listen<I: FidlRouteIpExt>(tx: broadcast::Sender<RouteChange>) {
+        let Ok(state) = connect_to_protocol::<I::StateMarker>() else {
+            return;
+        };
+        let Ok(events) = fnet_routes_ext::event_stream_from_state::<I>(&state) else {
+            return;
+        };
+        futures::pin_mut!(events);
+        while let Some(Ok(event)) = events.next().await {
+            // the routes that exist when watching starts are reported as existing, not added
+            let change = match event {
+                fnet_routes_ext::Event::Added(installed) => {
+                    to_route::<I>(&installed.route).map(RouteChange::Add)
+                }
+                fnet_routes_ext::Event::Removed(installed) => {
+                    to_route::<I>(&installed.route).map(RouteChange::Delete)
+                }
+                _ => continue,
+            };
+            if let Some(change) = change {
+                _ = tx.send(change);
+            }
+        }
+    }
+}
+
+fn other(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(ErrorKind::Other, e)
+}
+
+fn new_route_set<I: FidlRouteAdminIpExt>(
+) -> io::Result<<I::RouteSetMarker as fidl::endpoints::ProtocolMarker>::Proxy> {
+    let route_table = connect_to_protocol::<I::RouteTableMarker>().map_err(other)?;
+    fnet_routes_ext::admin::new_route_set::<I>(&route_table).map_err(other)
+}
+
+// Read the routes installed in the netstack with their effective metric.
+async fn installed_routes<I: FidlRouteIpExt>() -> io::Result<Vec<(Route, u32)>> {
+    let state = connect_to_protocol::<I::StateMarker>().map_err(other)?;
+    let events = fnet_routes_ext::event_stream_from_state::<I>(&state).map_err(other)?;
+    let installed: Vec<fnet_routes_ext::InstalledRoute<I>> =
+        fnet_routes_ext::collect_routes_until_idle(events)
+            .await
+            .map_err(other)?;
+    Ok(installed
+        .iter()
+        .filter_map(|installed| {
+            let route = to_route::<I>(&installed.route)?;
+            Some((route, installed.effective_properties.metric))
+        })
+        .collect())
+}
+
+fn to_route<I: Ip>(route: &fnet_routes_ext::Route<I>) -> Option<Route> {
+    let fnet_routes_ext::RouteAction::Forward(target) = &route.action else {
+        return None;
+    };
+    let destination = to_std_addr(route.destination.network().to_ip_addr());
+    let mut route = Route::new(destination, route.destination.prefix())
+        .with_ifindex(target.outbound_interface as u32);
+    route.gateway = target
+        .next_hop
+        .map(|next_hop| to_std_addr(next_hop.get().to_ip_addr()));
+    Some(route)
+}
+
+fn fidl_route<I: Ip>(
+    destination: I::Addr,
+    gateway: Option<I::Addr>,
+    ifindex: u32,
+    route: &Route,
+) -> io::Result<fnet_routes_ext::Route<I>> {
+    if route.kind != RouteKind::Unicast {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "only unicast routes can be added on Fuchsia",
+        ));
+    }
+    let destination = Subnet::new(destination, route.prefix)
+        .map_err(|_| invalid_route("host bits set in destination"))?;
+    Ok(fnet_routes_ext::Route {
+        destination,
+        action: fnet_routes_ext::RouteAction::Forward(fnet_routes_ext::RouteTarget {
+            outbound_interface: ifindex as u64,
+            next_hop: gateway.and_then(SpecifiedAddr::new),
+        }),
+        // without a metric the route inherits the metric of the interface
+        properties: fnet_routes_ext::RouteProperties {
+            specified_properties: fnet_routes_ext::SpecifiedRouteProperties {
+                metric: fnet_routes::SpecifiedMetric::InheritedFromInterface(fnet_routes::Empty),
+            },
+        },
+    })
+}
+
+async fn change<I: FidlRouteAdminIpExt + FidlRouteIpExt>(
+    route_set: &<I::RouteSetMarker as fidl::endpoints::ProtocolMarker>::Proxy,
+    ifindex: u32,
+    route: fnet_routes_ext::Route<I>,
+    add: bool,
+) -> io::Result<()> {
+    let route = route.try_into().map_err(other)?;
+    if add {
+        authenticate::<I>(route_set, ifindex).await?;
+        let added = fnet_routes_ext::admin::add_route::<I>(route_set, &route)
+            .await
+            .map_err(other)?
+            .map_err(route_set_error)?;
+        if !added {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, "route exists"));
+        }
+    } else {
+        let removed = fnet_routes_ext::admin::remove_route::<I>(route_set, &route)
+            .await
+            .map_err(other)?
+            .map_err(route_set_error)?;
+        if !removed {
+            return Err(io::Error::new(ErrorKind::NotFound, "no such route"));
+        }
+    }
+    Ok(())
+}
+
+// A route set may only use an interface after proving it is allowed to, with a token handed out
+// by the control handle of the interface.
+async fn authenticate<I: FidlRouteAdminIpExt>(
+    route_set: &<I::RouteSetMarker as fidl::endpoints::ProtocolMarker>::Proxy,
+    ifindex: u32,
+) -> io::Result<()> {
+    let interfaces = connect_to_protocol::<fnet_root::InterfacesMarker>().map_err(other)?;
+    let (control, server_end) =
+        fidl::endpoints::create_proxy::<fnet_interfaces_admin::ControlMarker>();
+    interfaces
+        .get_admin(ifindex as u64, server_end)
+        .map_err(other)?;
+    let fnet_interfaces_admin::GrantForInterfaceAuthorization {
+        interface_id,
+        token,
+    } = control
+        .get_authorization_for_interface()
+        .await
+        .map_err(other)?;
+    let proof = fnet_interfaces_admin::ProofOfInterfaceAuthorization {
+        interface_id,
+        token: token.duplicate_handle(fidl::Rights::NONE).map_err(other)?,
+    };
+    fnet_routes_ext::admin::authenticate_for_interface::<I>(route_set, proof)
+        .await
+        .map_err(other)?
+        .map_err(|e| {
+            io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("not authorized for interface {}: {:?}", ifindex, e),
+            )
+        })
+}
+
+fn route_set_error(e: fnet_routes_admin::RouteSetError) -> io::Error {
+    match e {
+        fnet_routes_admin::RouteSetError::Unauthenticated => io::Error::new(
+            ErrorKind::PermissionDenied,
+            "route set isn't authorized for the interface",
+        ),
+        e => io::Error::new(ErrorKind::InvalidInput, format!("{:?}", e)),
+    }
+}
+
+fn to_fidl_addr(addr: IpAddr) -> fnet::IpAddress {
+    match addr {
+        IpAddr::V4(addr) => fnet::IpAddress::Ipv4(fnet::Ipv4Address {
+            addr: addr.octets(),
+        }),
+        IpAddr::V6(addr) => fnet::IpAddress::Ipv6(fnet::Ipv6Address {
+            addr: addr.octets(),
+        }),
+    }
+}
+
+fn from_fidl_addr(addr: fnet::IpAddress) -> IpAddr {
+    match addr {
+        fnet::IpAddress::Ipv4(addr) => addr.addr.into(),
+        fnet::IpAddress::Ipv6(addr) => addr.addr.into(),
+    }
+}
+
+fn to_std_addr(addr: net_types::ip::IpAddr) -> IpAddr {
+    match addr {
+        net_types::ip::IpAddr::V4(addr) => IpAddr::from(addr.ipv4_bytes()),
+        net_types::ip::IpAddr::V6(addr) => IpAddr::from(addr.ipv6_bytes()),
+    }
+}
@@ -0,0 +1,204 @@
+// Definitions of net/route.h, sys/tihdr.h, sys/stropts.h and inet/mib2.h, which libc doesn't
+// have for illumos.
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(dead_code)]
+
+use std::os::raw::{c_char, c_int};
+
+pub(crate) const RTM_VERSION: u8 = 3;
+
+pub(crate) const RTM_ADD: u8 = 0x1;
+pub(crate) const RTM_DELETE: u8 = 0x2;
+pub(crate) const RTM_CHANGE: u8 = 0x3;
+pub(crate) const RTM_GET: u8 = 0x4;
+
+pub(crate) const RTF_UP: i32 = 0x1;
+pub(crate) const RTF_GATEWAY: i32 = 0x2;
+pub(crate) const RTF_HOST: i32 = 0x4;
+pub(crate) const RTF_REJECT: i32 = 0x8;
+pub(crate) const RTF_STATIC: i32 = 0x800;
+pub(crate) const RTF_BLACKHOLE: i32 = 0x1000;
+
+pub(crate) const RTA_DST: i32 = 0x1;
+pub(crate) const RTA_GATEWAY: i32 = 0x2;
+pub(crate) const RTA_NETMASK: i32 = 0x4;
+pub(crate) const RTA_IFP: i32 = 0x10;
+
+pub(crate) const RTAX_DST: usize = 0;
+pub(crate) const RTAX_GATEWAY: usize = 1;
+pub(crate) const RTAX_NETMASK: usize = 2;
+pub(crate) const RTAX_MAX: usize = 8;
+
+pub(crate) const AF_LINK: u16 = 25;
+
+// struct sockaddr_dl of net/if_dl.h, sdl_data is 244 bytes
+pub(crate) const SOCKADDR_DL_SIZE: usize = 252;
+
+pub(crate) const ESRCH: i32 = 3;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct rt_metrics {
+    pub(crate) rmx_locks: u32,
+    pub(crate) rmx_mtu: u32,
+    pub(crate) rmx_hopcount: u32,
+    pub(crate) rmx_expire: u32,
+    pub(crate) rmx_recvpipe: u32,
+    pub(crate) rmx_sendpipe: u32,
+    pub(crate) rmx_ssthresh: u32,
+    pub(crate) rmx_rtt: u32,
+    pub(crate) rmx_rttvar: u32,
+    pub(crate) rmx_pksent: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct rt_msghdr {
+    pub(crate) rtm_msglen: u16,
+    pub(crate) rtm_version: u8,
+    pub(crate) rtm_type: u8,
+    pub(crate) rtm_index: u16,
+    pub(crate) rtm_flags: i32,
+    pub(crate) rtm_addrs: i32,
+    pub(crate) rtm_pid: libc::pid_t,
+    pub(crate) rtm_seq: i32,
+    pub(crate) rtm_errno: i32,
+    pub(crate) rtm_use: i32,
+    pub(crate) rtm_inits: u32,
+    pub(crate) rtm_rmx: rt_metrics,
+}
+
+pub(crate) const T_SVR4_OPTMGMT_REQ: i32 = 9;
+pub(crate) const T_ERROR_ACK: i32 = 18;
+pub(crate) const T_OPTMGMT_ACK: i32 = 22;
+pub(crate) const T_SUCCESS: i32 = 0x020;
+pub(crate) const T_CURRENT: i32 = 0x080;
+
+pub(crate) const MORECTL: c_int = 1;
+pub(crate) const MOREDATA: c_int = 2;
+
+// the levels are the protocol numbers
+pub(crate) const MIB2_IP: u32 = libc::IPPROTO_IP as u32;
+pub(crate) const MIB2_IP6: u32 = libc::IPPROTO_IPV6 as u32;
+pub(crate) const MIB2_IP_ROUTE: u32 = 21;
+pub(crate) const MIB2_IP6_ROUTE: u32 = 21;
+
+// ire_type of inet/ip.h
+pub(crate) const IRE_BROADCAST: i32 = 0x0001;
+pub(crate) const IRE_LOCAL: i32 = 0x0004;
+pub(crate) const IRE_LOOPBACK: i32 = 0x0008;
+pub(crate) const IRE_IF_CLONE: i32 = 0x0020;
+pub(crate) const IRE_MULTICAST: i32 = 0x0200;
+pub(crate) const IRE_NOROUTE: i32 = 0x0400;
+
+pub(crate) const OCTET_LENGTH: usize = 32;
+
+#[repr(C)]
+pub(crate) struct strbuf {
+    pub(crate) maxlen: c_int,
+    pub(crate) len: c_int,
+    pub(crate) buf: *mut c_char,
+}
+
+extern "C" {
+    pub(crate) fn putmsg(
+        fd: c_int,
+        ctlptr: *const strbuf,
+        dataptr: *const strbuf,
+        flags: c_int,
+    ) -> c_int;
+    pub(crate) fn getmsg(
+        fd: c_int,
+        ctlptr: *mut strbuf,
+        dataptr: *mut strbuf,
+        flags: *mut c_int,
+    ) -> c_int;
+}
+
+// T_optmgmt_req and T_optmgmt_ack have the same layout
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct T_optmgmt {
+    pub(crate) PRIM_type: i32,
+    pub(crate) OPT_length: i32,
+    pub(crate) OPT_offset: i32,
+    pub(crate) MGMT_flags: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct T_error_ack {
+    pub(crate) PRIM_type: i32,
+    pub(crate) ERROR_prim: i32,
+    pub(crate) TLI_error: i32,
+    pub(crate) UNIX_error: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct opthdr {
+    pub(crate) level: u32,
+    pub(crate) name: u32,
+    pub(crate) len: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Octet_t {
+    pub(crate) o_length: c_int,
+    pub(crate) o_bytes: [u8; OCTET_LENGTH],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct mib2_ipRouteEntry {
+    pub(crate) ipRouteDest: u32,
+    pub(crate) ipRouteIfIndex: Octet_t,
+    pub(crate) ipRouteMetric1: c_int,
+    pub(crate) ipRouteMetric2: c_int,
+    pub(crate) ipRouteMetric3: c_int,
+    pub(crate) ipRouteMetric4: c_int,
+    pub(crate) ipRouteNextHop: u32,
+    pub(crate) ipRouteType: c_int,
+    pub(crate) ipRouteProto: c_int,
+    pub(crate) ipRouteAge: c_int,
+    pub(crate) ipRouteMask: u32,
+    pub(crate) ipRouteMetric5: c_int,
+    pub(crate) re_max_frag: u32,
+    pub(crate) re_rtt: u32,
+    pub(crate) re_ref: u32,
+    pub(crate) re_frag_flag: c_int,
+    pub(crate) re_src_addr: u32,
+    pub(crate) re_ire_type: c_int,
+    pub(crate) re_obpkt: u32,
+    pub(crate) re_ibpkt: u32,
+    pub(crate) re_flags: c_int,
+    pub(crate) re_in_ill: Octet_t,
+    pub(crate) re_in_src_addr: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct mib2_ipv6RouteEntry {
+    pub(crate) ipv6RouteDest: [u8; 16],
+    pub(crate) ipv6RoutePfxLength: c_int,
+    pub(crate) ipv6RouteIfIndex: Octet_t,
+    pub(crate) ipv6RouteNextHop: [u8; 16],
+    pub(crate) ipv6RouteType: c_int,
+    pub(crate) ipv6RouteProtocol: c_int,
+    pub(crate) ipv6RoutePolicy: c_int,
+    pub(crate) ipv6RouteAge: c_int,
+    pub(crate) ipv6RouteNextHopRDI: c_int,
+    pub(crate) ipv6RouteMetric: c_int,
+    pub(crate) ipv6RouteWeight: c_int,
+    pub(crate) re_max_frag: u32,
+    pub(crate) re_rtt: u32,
+    pub(crate) re_ref: u32,
+    pub(crate) re_frag_flag: c_int,
+    pub(crate) re_src_addr: [u8; 16],
+    pub(crate) re_ire_type: c_int,
+    pub(crate) re_obpkt: u32,
+    pub(crate) re_ibpkt: u32,
+    pub(crate) re_flags: c_int,
+}
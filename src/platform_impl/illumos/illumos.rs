@@ -0,0 +1,428 @@
+use std::{
+    io::{self, ErrorKind},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::prelude::FromRawFd,
+    ptr,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use crate::error::invalid_route;
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
+use async_stream::stream;
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::platform_impl::illumos::{bind::*, mib};
+use crate::platform_impl::runtime::{self, AsyncReadExt, AsyncWriteExt, Task, UnixStream};
+use crate::{Capabilities, DumpFilter, HandleBuilder, Route, RouteChange, RouteKind};
+
+// illumos has a PF_ROUTE socket for changes and events, but its sockaddrs have no length byte
+// and follow each other unpadded, their size is implied by the family, see salen() in
+// usr/src/cmd/cmd-inet/usr.sbin/route.c. There is no sysctl to dump the table, it is read from
+// the MIB of the ip module like netstat does, see mib.rs.
+
+const HDR_SIZE: usize = mem::size_of::<rt_msghdr>();
+
+pub(crate) struct Handle {
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    tx: broadcast::Sender<RouteChange>,
+    listen_handle: Option<Task>,
+}
+
+impl Handle {
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
+        let listen_handle = match config.listen {
+            true => Some(Task::spawn(Self::listen(tx.clone(), route_socket()?))),
+            false => None,
+        };
+
+        Ok(Self { tx, listen_handle })
+    }
+
+    // The listener only reads from the routing socket, it's stopped right away and joined so the
+    // socket is closed once this returns.
+    pub(crate) async fn close(mut self) -> io::Result<()> {
+        let Some(listen_handle) = &mut self.listen_handle else {
+            return Ok(());
+        };
+        listen_handle.abort();
+        listen_handle.join().await
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        // changing routes needs PRIV_SYS_IP_CONFIG, which root has in the global zone and in
+        // exclusive-ip zones
+        Ok(Capabilities {
+            modify_routes: unsafe { libc::geteuid() } == 0,
+            modify_rules: false,
+        })
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        Ok(self.list().await?.into_iter().find(|route| {
+            route.destination.is_unspecified()
+                && route.prefix == 0
+                && route
+                    .gateway
+                    .map_or(false, |gateway| !gateway.is_unspecified())
+        }))
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
+        change_route(RTM_DELETE, route).await
+    }
+
+    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
+        change_route(RTM_ADD, route).await
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        self.list_filtered(&DumpFilter::new()).await
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let mut routes = mib::list_routes()?;
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
+    }
+
+    pub(crate) async fn get_route(&self, destination: IpAddr) -> io::Result<Route> {
+        let mut hdr: rt_msghdr = unsafe { mem::zeroed() };
+        hdr.rtm_type = RTM_GET;
+        hdr.rtm_addrs = RTA_DST | RTA_IFP;
+        // an empty link address asks the kernel to include the interface in the reply
+        let addrs = [ip_to_sockaddr(destination), link_to_sockaddr(0)];
+        let reply = request(route_socket()?, hdr, &addrs).await?;
+        let hdr = read_header(&reply)
+            .ok_or_else(|| io::Error::new(ErrorKind::Other, "Unexpected message len"))?;
+        message_to_route(&hdr, &reply[HDR_SIZE..])
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "No route found for destination"))
+    }
+
+    async fn listen(tx: broadcast::Sender<RouteChange>, mut sock: UnixStream) {
+        let mut buf = [0u8; 2048];
+        loop {
+            let read = match sock.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let Some(hdr) = read_header(&buf[..read]) else {
+                continue;
+            };
+            let change: fn(Route) -> RouteChange = match hdr.rtm_type {
+                RTM_ADD => RouteChange::Add,
+                RTM_DELETE => RouteChange::Delete,
+                RTM_CHANGE => RouteChange::Change,
+                _ => continue,
+            };
+            let len = (hdr.rtm_msglen as usize).min(read);
+            if let Some(route) = message_to_route(&hdr, &buf[HDR_SIZE..len]) {
+                _ = tx.send(change(route));
+            }
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Some(listen_handle) = &self.listen_handle {
+            listen_handle.abort();
+        }
+    }
+}
+
+fn route_socket() -> io::Result<UnixStream> {
+    let fd = unsafe { libc::socket(libc::PF_ROUTE, libc::SOCK_RAW, libc::AF_UNSPEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    runtime::async_socket(route_fd)
+}
+
+fn code_to_error(err: i32) -> io::Error {
+    match err {
+        ESRCH => io::Error::new(ErrorKind::NotFound, "no such route"),
+        _ => io::Error::from_raw_os_error(err),
+    }
+}
+
+// messages are only byte aligned in the read buffers
+fn read_header(buf: &[u8]) -> Option<rt_msghdr> {
+    if buf.len() < HDR_SIZE {
+        return None;
+    }
+    let hdr: rt_msghdr = unsafe { ptr::read_unaligned(buf.as_ptr() as *const rt_msghdr) };
+    (hdr.rtm_version == RTM_VERSION).then_some(hdr)
+}
+
+fn sa_family(sa: &[u8]) -> Option<u16> {
+    Some(u16::from_ne_bytes(sa.get(..2)?.try_into().ok()?))
+}
+
+fn sa_size(family: u16) -> usize {
+    match family as i32 {
+        libc::AF_INET => mem::size_of::<libc::sockaddr_in>(),
+        libc::AF_INET6 => mem::size_of::<libc::sockaddr_in6>(),
+        _ if family == AF_LINK => SOCKADDR_DL_SIZE,
+        _ => mem::size_of::<libc::sockaddr>(),
+    }
+}
+
+// split the sockaddrs of a message by their RTAX_* index
+fn route_addrs(rtm_addrs: i32, mut msg: &[u8]) -> [Option<&[u8]>; RTAX_MAX] {
+    let mut addrs = [None; RTAX_MAX];
+    for (idx, addr) in addrs.iter_mut().enumerate() {
+        if rtm_addrs & (1 << idx) == 0 {
+            continue;
+        }
+        let Some(family) = sa_family(msg) else {
+            break;
+        };
+        let size = sa_size(family).min(msg.len());
+        *addr = Some(&msg[..size]);
+        msg = &msg[size..];
+    }
+    addrs
+}
+
+fn sa_to_ip(sa: &[u8]) -> Option<IpAddr> {
+    match sa_family(sa)? as i32 {
+        libc::AF_INET => {
+            let octets: [u8; 4] = sa.get(4..8)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        libc::AF_INET6 => {
+            let octets: [u8; 16] = sa.get(8..24)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+// netmasks are complete sockaddrs, unlike on the BSDs
+fn sa_to_prefix(sa: &[u8], destination: IpAddr) -> u8 {
+    let (offset, len) = match destination {
+        IpAddr::V4(_) => (4, 4),
+        IpAddr::V6(_) => (8, 16),
+    };
+    sa.iter()
+        .skip(offset)
+        .take(len)
+        .map(|byte| byte.count_ones())
+        .sum::<u32>() as u8
+}
+
+fn ip_to_sockaddr(ip: IpAddr) -> Vec<u8> {
+    let (mut sa, family) = match ip {
+        IpAddr::V4(addr) => {
+            let mut sa = vec![0u8; mem::size_of::<libc::sockaddr_in>()];
+            sa[4..8].copy_from_slice(&addr.octets());
+            (sa, libc::AF_INET as u16)
+        }
+        IpAddr::V6(addr) => {
+            let mut sa = vec![0u8; mem::size_of::<libc::sockaddr_in6>()];
+            sa[8..24].copy_from_slice(&addr.octets());
+            (sa, libc::AF_INET6 as u16)
+        }
+    };
+    sa[..2].copy_from_slice(&family.to_ne_bytes());
+    sa
+}
+
+fn link_to_sockaddr(ifindex: u32) -> Vec<u8> {
+    let mut sa = vec![0u8; SOCKADDR_DL_SIZE];
+    sa[..2].copy_from_slice(&AF_LINK.to_ne_bytes());
+    sa[2..4].copy_from_slice(&(ifindex as u16).to_ne_bytes());
+    sa
+}
+
+fn message_to_route(hdr: &rt_msghdr, msg: &[u8]) -> Option<Route> {
+    let flags = hdr.rtm_flags;
+    let addrs = route_addrs(hdr.rtm_addrs, msg);
+    let destination = sa_to_ip(addrs[RTAX_DST]?)?;
+    let prefix = match addrs[RTAX_NETMASK] {
+        Some(mask) if flags & RTF_HOST == 0 => sa_to_prefix(mask, destination),
+        _ => match destination {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        },
+    };
+
+    let mut route = Route::new(destination, prefix).with_ifindex(hdr.rtm_index as u32);
+    // interface routes have a link address as gateway
+    route.gateway = addrs[RTAX_GATEWAY].and_then(sa_to_ip);
+    route.kind = if flags & RTF_BLACKHOLE != 0 {
+        RouteKind::Blackhole
+    } else if flags & RTF_REJECT != 0 {
+        RouteKind::Unreachable
+    } else {
+        RouteKind::Unicast
+    };
+    Some(route)
+}
+
+static RTM_SEQ: AtomicI32 = AtomicI32::new(1);
+
+// Send a message with the sockaddrs in `addrs` on `sock` and return the reply of the kernel.
+async fn request(
+    mut sock: UnixStream,
+    mut hdr: rt_msghdr,
+    addrs: &[Vec<u8>],
+) -> io::Result<Vec<u8>> {
+    let body = addrs.concat();
+    let seq = RTM_SEQ.fetch_add(1, Ordering::Relaxed);
+    hdr.rtm_msglen = (HDR_SIZE + body.len()) as u16;
+    hdr.rtm_version = RTM_VERSION;
+    hdr.rtm_seq = seq;
+    let mut msg = unsafe {
+        std::slice::from_raw_parts(&hdr as *const rt_msghdr as *const u8, HDR_SIZE).to_vec()
+    };
+    msg.extend_from_slice(&body);
+
+    if let Err(e) = sock.write_all(&msg).await {
+        // the kernel fails the write with the error of the request, e.g. EEXIST
+        return Err(match e.raw_os_error() {
+            Some(err) => code_to_error(err),
+            None => e,
+        });
+    }
+
+    // the socket also receives every other routing message, wait for the reply to ours
+    let pid = unsafe { libc::getpid() };
+    let mut buf = [0u8; 2048];
+    loop {
+        let read = sock.read(&mut buf).await?;
+        let Some(reply) = read_header(&buf[..read]) else {
+            continue;
+        };
+        if reply.rtm_pid != pid || reply.rtm_seq != seq {
+            continue;
+        }
+        if reply.rtm_errno != 0 {
+            return Err(code_to_error(reply.rtm_errno));
+        }
+        return Ok(buf[..(reply.rtm_msglen as usize).min(read)].to_vec());
+    }
+}
+
+async fn change_route(rtm_type: u8, route: &Route) -> io::Result<()> {
+    let max_prefix = match route.destination {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    let mut flags = RTF_UP | RTF_STATIC;
+    let mut rtm_addrs = RTA_DST;
+    let mut addrs = vec![ip_to_sockaddr(route.destination)];
+
+    let gateway = match (route.gateway, route.ifindex) {
+        (Some(gateway), _) => {
+            flags |= RTF_GATEWAY;
+            Some(ip_to_sockaddr(gateway))
+        }
+        // deleting only needs a gateway to pick one of several paths
+        _ if rtm_type == RTM_DELETE => None,
+        (None, Some(ifindex)) => Some(link_to_sockaddr(ifindex)),
+        // like `route add -blackhole`, dropping routes still need a next hop, use loopback
+        (None, None) if route.kind != RouteKind::Unicast => {
+            Some(ip_to_sockaddr(match route.destination {
+                IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            }))
+        }
+        (None, None) => return Err(invalid_route("routes need a gateway or an ifindex")),
+    };
+    if let Some(gateway) = gateway {
+        rtm_addrs |= RTA_GATEWAY;
+        addrs.push(gateway);
+    }
+
+    if route.prefix >= max_prefix {
+        flags |= RTF_HOST;
+    } else {
+        rtm_addrs |= RTA_NETMASK;
+        addrs.push(ip_to_sockaddr(route.mask()));
+    }
+
+    // pin a route with a gateway to the given interface, like `route add -ifp`
+    if let (Some(_), Some(ifindex)) = (route.gateway, route.ifindex) {
+        if rtm_type == RTM_ADD {
+            rtm_addrs |= RTA_IFP;
+            addrs.push(link_to_sockaddr(ifindex));
+        }
+    }
+
+    if rtm_type == RTM_ADD {
+        flags |= match route.kind {
+            RouteKind::Unicast => 0,
+            RouteKind::Blackhole => RTF_BLACKHOLE,
+            RouteKind::Unreachable | RouteKind::Prohibit => RTF_REJECT,
+            RouteKind::Other => return Err(invalid_route("routes of other kinds can't be added")),
+        };
+    }
+
+    let mut hdr: rt_msghdr = unsafe { mem::zeroed() };
+    hdr.rtm_type = rtm_type;
+    hdr.rtm_flags = flags;
+    hdr.rtm_addrs = rtm_addrs;
+    request(route_socket()?, hdr, &addrs).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_splits_unpadded_sockaddrs() {
+        let msg = [
+            ip_to_sockaddr("10.1.0.0".parse().unwrap()),
+            link_to_sockaddr(2),
+            ip_to_sockaddr("255.255.0.0".parse().unwrap()),
+        ]
+        .concat();
+
+        let mut hdr: rt_msghdr = unsafe { mem::zeroed() };
+        hdr.rtm_flags = RTF_UP;
+        hdr.rtm_addrs = RTA_DST | RTA_GATEWAY | RTA_NETMASK;
+        hdr.rtm_index = 2;
+
+        let route = message_to_route(&hdr, &msg).unwrap();
+        assert_eq!(route.destination, "10.1.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(route.prefix, 16);
+        assert_eq!(route.gateway, None);
+        assert_eq!(route.ifindex, Some(2));
+    }
+}
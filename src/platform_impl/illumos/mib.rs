@@ -0,0 +1,210 @@
+// The routing table is read like `netstat -r` does: a T_SVR4_OPTMGMT_REQ for every table of the
+// MIB2_IP level is sent down a stream to the ip module, which answers with one T_OPTMGMT_ACK and
+// a data part per table.
+
+use std::{
+    ffi::CStr,
+    io::{self, ErrorKind},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        raw::{c_char, c_int},
+    },
+    ptr,
+};
+
+use crate::platform_impl::illumos::bind::*;
+use crate::{Route, RouteKind};
+
+/// Read the ipv4 and ipv6 routing tables.
+pub(crate) fn list_routes() -> io::Result<Vec<Route>> {
+    let fd = unsafe { libc::open(b"/dev/arp\0".as_ptr() as *const c_char, libc::O_RDWR) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut routes = vec![];
+    for (level, name, data) in read_tables(&fd)? {
+        match (level, name) {
+            (MIB2_IP, MIB2_IP_ROUTE) => routes.extend(
+                entries::<mib2_ipRouteEntry>(&data)
+                    .iter()
+                    .filter_map(ipv4_entry_to_route),
+            ),
+            (MIB2_IP6, MIB2_IP6_ROUTE) => routes.extend(
+                entries::<mib2_ipv6RouteEntry>(&data)
+                    .iter()
+                    .filter_map(ipv6_entry_to_route),
+            ),
+            _ => {}
+        }
+    }
+    Ok(routes)
+}
+
+// Request every table of the MIB2_IP level and return them as (level, name, data).
+fn read_tables(fd: &OwnedFd) -> io::Result<Vec<(u32, u32, Vec<u8>)>> {
+    #[repr(C)]
+    struct Request {
+        req: T_optmgmt,
+        hdr: opthdr,
+    }
+
+    let mut request = Request {
+        req: T_optmgmt {
+            PRIM_type: T_SVR4_OPTMGMT_REQ,
+            OPT_length: mem::size_of::<opthdr>() as i32,
+            OPT_offset: mem::size_of::<T_optmgmt>() as i32,
+            MGMT_flags: T_CURRENT,
+        },
+        // a name of 0 asks for every table
+        hdr: opthdr {
+            level: MIB2_IP,
+            name: 0,
+            len: 0,
+        },
+    };
+    let ctl = strbuf {
+        maxlen: mem::size_of::<Request>() as c_int,
+        len: mem::size_of::<Request>() as c_int,
+        buf: &mut request as *mut Request as *mut c_char,
+    };
+    if unsafe { putmsg(fd.as_raw_fd(), &ctl, ptr::null(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut tables = vec![];
+    // u64s to align the headers in the control part
+    let mut buf = [0u64; 64];
+    loop {
+        let mut ctl = strbuf {
+            maxlen: mem::size_of_val(&buf) as c_int,
+            len: 0,
+            buf: buf.as_mut_ptr() as *mut c_char,
+        };
+        let mut flags = 0;
+        let ret = unsafe { getmsg(fd.as_raw_fd(), &mut ctl, ptr::null_mut(), &mut flags) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = ctl.len.max(0) as usize;
+        let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, len) };
+
+        if len >= mem::size_of::<T_error_ack>() {
+            let ack: T_error_ack = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const _) };
+            if ack.PRIM_type == T_ERROR_ACK {
+                return Err(io::Error::from_raw_os_error(ack.UNIX_error));
+            }
+        }
+        if len < mem::size_of::<T_optmgmt>() || ret & MORECTL != 0 {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "Unexpected reply to the MIB request",
+            ));
+        }
+        let ack: T_optmgmt = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const _) };
+        let offset = ack.OPT_offset.max(0) as usize;
+        if ack.PRIM_type != T_OPTMGMT_ACK
+            || ack.MGMT_flags != T_SUCCESS
+            || offset + mem::size_of::<opthdr>() > len
+        {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "Unexpected reply to the MIB request",
+            ));
+        }
+        let hdr: opthdr = unsafe { ptr::read_unaligned(bytes[offset..].as_ptr() as *const _) };
+
+        // the final ack has no data part
+        if ret == 0 && hdr.len == 0 {
+            return Ok(tables);
+        }
+        if ret != MOREDATA {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "Unexpected reply to the MIB request",
+            ));
+        }
+
+        let mut data = vec![0u8; hdr.len as usize];
+        let mut databuf = strbuf {
+            maxlen: data.len() as c_int,
+            len: 0,
+            buf: data.as_mut_ptr() as *mut c_char,
+        };
+        let mut flags = 0;
+        if unsafe { getmsg(fd.as_raw_fd(), ptr::null_mut(), &mut databuf, &mut flags) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        data.truncate(databuf.len.max(0) as usize);
+        tables.push((hdr.level, hdr.name, data));
+    }
+}
+
+// the data part is only byte aligned
+fn entries<T: Copy>(data: &[u8]) -> Vec<T> {
+    data.chunks_exact(mem::size_of::<T>())
+        .map(|entry| unsafe { ptr::read_unaligned(entry.as_ptr() as *const T) })
+        .collect()
+}
+
+fn ifindex(name: &Octet_t) -> Option<u32> {
+    let len = (name.o_length.max(0) as usize).min(OCTET_LENGTH);
+    let mut name = name.o_bytes[..len].to_vec();
+    name.push(0);
+    let name = CStr::from_bytes_until_nul(&name).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        ifindex => Some(ifindex),
+    }
+}
+
+// Routes of the local addresses, clones for destinations in use and the like are skipped, as by
+// `netstat -r` without -a.
+fn kind(ire_type: c_int, flags: c_int) -> Option<RouteKind> {
+    if ire_type & (IRE_IF_CLONE | IRE_NOROUTE) != 0 {
+        return None;
+    }
+    Some(
+        if ire_type & (IRE_BROADCAST | IRE_LOCAL | IRE_LOOPBACK | IRE_MULTICAST) != 0 {
+            RouteKind::Other
+        } else if flags & RTF_BLACKHOLE != 0 {
+            RouteKind::Blackhole
+        } else if flags & RTF_REJECT != 0 {
+            RouteKind::Unreachable
+        } else {
+            RouteKind::Unicast
+        },
+    )
+}
+
+fn ipv4_entry_to_route(entry: &mib2_ipRouteEntry) -> Option<Route> {
+    let kind = kind(entry.re_ire_type, entry.re_flags)?;
+    // the addresses are in network byte order
+    let destination = Ipv4Addr::from(entry.ipRouteDest.to_ne_bytes());
+    let prefix = entry.ipRouteMask.count_ones() as u8;
+
+    let mut route = Route::new(destination.into(), prefix);
+    route.ifindex = ifindex(&entry.ipRouteIfIndex);
+    // interface routes have the address of the interface as next hop
+    if entry.re_flags & RTF_GATEWAY != 0 {
+        route.gateway = Some(Ipv4Addr::from(entry.ipRouteNextHop.to_ne_bytes()).into());
+    }
+    route.kind = kind;
+    Some(route)
+}
+
+fn ipv6_entry_to_route(entry: &mib2_ipv6RouteEntry) -> Option<Route> {
+    let kind = kind(entry.re_ire_type, entry.re_flags)?;
+    let destination = Ipv6Addr::from(entry.ipv6RouteDest);
+
+    let mut route = Route::new(destination.into(), entry.ipv6RoutePfxLength as u8);
+    route.ifindex = ifindex(&entry.ipv6RouteIfIndex);
+    if entry.re_flags & RTF_GATEWAY != 0 {
+        route.gateway = Some(IpAddr::from(entry.ipv6RouteNextHop));
+    }
+    route.kind = kind;
+    Some(route)
+}
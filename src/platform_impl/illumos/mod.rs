@@ -0,0 +1,5 @@
+mod bind;
+pub(crate) mod illumos;
+mod mib;
+
+pub(crate) use illumos::Handle;
@@ -0,0 +1,351 @@
+use std::{
+    io::{self, ErrorKind},
+    mem,
+    net::IpAddr,
+    ptr,
+};
+
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
+use async_stream::stream;
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+#[cfg(feature = "nw-path-monitor")]
+use crate::platform_impl::ios::path_monitor::PathMonitor;
+#[cfg(not(feature = "nw-path-monitor"))]
+use crate::platform_impl::runtime::{self, AsyncReadExt, Task, UnixStream};
+use crate::{Capabilities, DumpFilter, HandleBuilder, IpVersion, Route, RouteChange, RouteKind};
+
+// iOS has the routing socket and sysctl interface of macOS, but net/route.h isn't part of the
+// SDK so the definitions come from libc. Apps run in a sandbox that can deny reading the table
+// and never allows changing it. With the `nw-path-monitor` feature changes of the default
+// route are followed through NWPathMonitor instead of the routing socket.
+
+const HDR_SIZE: usize = mem::size_of::<libc::rt_msghdr>();
+
+const RTAX_DST: usize = 0;
+const RTAX_GATEWAY: usize = 1;
+const RTAX_NETMASK: usize = 2;
+const RTAX_MAX: usize = 8;
+
+pub(crate) struct Handle {
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
+    tx: broadcast::Sender<RouteChange>,
+    #[cfg(not(feature = "nw-path-monitor"))]
+    listen_handle: Option<Task>,
+    #[cfg(feature = "nw-path-monitor")]
+    _monitor: Option<PathMonitor>,
+}
+
+impl Handle {
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
+
+        // without access to the routing socket the stream never yields
+        #[cfg(not(feature = "nw-path-monitor"))]
+        let listen_handle = config
+            .listen
+            .then(route_socket)
+            .and_then(Result::ok)
+            .map(|sock| Task::spawn(Self::listen(tx.clone(), sock)));
+
+        Ok(Self {
+            #[cfg(feature = "nw-path-monitor")]
+            _monitor: config
+                .listen
+                .then(|| PathMonitor::start(tx.clone()))
+                .transpose()?,
+            tx,
+            #[cfg(not(feature = "nw-path-monitor"))]
+            listen_handle,
+        })
+    }
+
+    pub(crate) async fn close(self) -> io::Result<()> {
+        // the path monitor is cancelled when dropped
+        #[cfg(not(feature = "nw-path-monitor"))]
+        {
+            let mut this = self;
+            if let Some(listen_handle) = &mut this.listen_handle {
+                listen_handle.abort();
+                listen_handle.join().await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        Ok(Capabilities {
+            modify_routes: false,
+            modify_rules: false,
+        })
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        Ok(self.list().await?.into_iter().find(|route| {
+            route.destination.is_unspecified()
+                && route.prefix == 0
+                && route
+                    .gateway
+                    .map_or(false, |gateway| !gateway.is_unspecified())
+        }))
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, _route: &Route) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn add(&self, _route: &Route) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        list_routes(0)
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let family = match filter.family {
+            Some(IpVersion::V4) => libc::AF_INET,
+            Some(IpVersion::V6) => libc::AF_INET6,
+            None => 0,
+        };
+        let mut routes = list_routes(family)?;
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
+    }
+
+    #[cfg(not(feature = "nw-path-monitor"))]
+    async fn listen(tx: broadcast::Sender<RouteChange>, mut sock: UnixStream) {
+        let mut buf = [0u8; 2048];
+        loop {
+            let read = match sock.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let Some(hdr) = read_header(&buf[..read]) else {
+                continue;
+            };
+            let change: fn(Route) -> RouteChange = match hdr.rtm_type as i32 {
+                libc::RTM_ADD => RouteChange::Add,
+                libc::RTM_DELETE => RouteChange::Delete,
+                libc::RTM_CHANGE => RouteChange::Change,
+                _ => continue,
+            };
+            let len = (hdr.rtm_msglen as usize).min(read);
+            if let Some(route) = message_to_route(&hdr, &buf[HDR_SIZE..len]) {
+                _ = tx.send(change(route));
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "nw-path-monitor"))]
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Some(listen_handle) = &self.listen_handle {
+            listen_handle.abort();
+        }
+    }
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        "routes can't be modified on iOS, use a NEPacketTunnelProvider instead",
+    )
+}
+
+#[cfg(not(feature = "nw-path-monitor"))]
+fn route_socket() -> io::Result<UnixStream> {
+    use std::os::unix::prelude::FromRawFd;
+
+    let fd = unsafe { libc::socket(libc::PF_ROUTE, libc::SOCK_RAW, libc::AF_UNSPEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    runtime::async_socket(route_fd)
+}
+
+// messages are only byte aligned in the read buffers
+fn read_header(buf: &[u8]) -> Option<libc::rt_msghdr> {
+    if buf.len() < HDR_SIZE {
+        return None;
+    }
+    let hdr: libc::rt_msghdr =
+        unsafe { ptr::read_unaligned(buf.as_ptr() as *const libc::rt_msghdr) };
+    (hdr.rtm_version as i32 == libc::RTM_VERSION).then_some(hdr)
+}
+
+// the sockaddrs following the header are padded to a multiple of 4 bytes, see ROUNDUP() in
+// route.c
+fn sa_size(sa_len: u8) -> usize {
+    if sa_len == 0 {
+        4
+    } else {
+        1 + ((sa_len as usize - 1) | 3)
+    }
+}
+
+// split the sockaddrs of a message by their RTAX_* index
+fn route_addrs(rtm_addrs: i32, mut msg: &[u8]) -> [Option<&[u8]>; RTAX_MAX] {
+    let mut addrs = [None; RTAX_MAX];
+    for (idx, addr) in addrs.iter_mut().enumerate() {
+        if rtm_addrs & (1 << idx) == 0 {
+            continue;
+        }
+        let Some(&sa_len) = msg.first() else {
+            break;
+        };
+        *addr = Some(&msg[..(sa_len as usize).min(msg.len())]);
+        msg = &msg[sa_size(sa_len).min(msg.len())..];
+    }
+    addrs
+}
+
+fn sa_to_ip(sa: &[u8]) -> Option<IpAddr> {
+    match *sa.get(1)? as i32 {
+        libc::AF_INET => {
+            let octets: [u8; 4] = sa.get(4..8)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        libc::AF_INET6 => {
+            let mut octets: [u8; 16] = sa.get(8..24)?.try_into().ok()?;
+            // the kernel embeds the scope id of link-local addresses in bytes 2 and 3
+            let link_local = octets[0] == 0xfe && octets[1] & 0xc0 == 0x80;
+            let local_multicast = octets[0] == 0xff && matches!(octets[1] & 0x0f, 1 | 2);
+            if link_local || local_multicast {
+                octets[2] = 0;
+                octets[3] = 0;
+            }
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+// the kernel trims trailing zero bytes from netmasks
+fn sa_to_prefix(sa: &[u8], destination: IpAddr) -> u8 {
+    let (offset, len) = match destination {
+        IpAddr::V4(_) => (4, 4),
+        IpAddr::V6(_) => (8, 16),
+    };
+    sa.iter()
+        .skip(offset)
+        .take(len)
+        .map(|byte| byte.count_ones())
+        .sum::<u32>() as u8
+}
+
+fn message_to_route(hdr: &libc::rt_msghdr, msg: &[u8]) -> Option<Route> {
+    let flags = hdr.rtm_flags;
+    if flags & (libc::RTF_WASCLONED | libc::RTF_LLINFO) != 0 {
+        return None;
+    }
+
+    let addrs = route_addrs(hdr.rtm_addrs, msg);
+    let destination = sa_to_ip(addrs[RTAX_DST]?)?;
+    let prefix = match addrs[RTAX_NETMASK] {
+        Some(mask) if flags & libc::RTF_HOST == 0 => sa_to_prefix(mask, destination),
+        _ => match destination {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        },
+    };
+
+    let mut route = Route::new(destination, prefix).with_ifindex(hdr.rtm_index as u32);
+    route.gateway = addrs[RTAX_GATEWAY].and_then(sa_to_ip);
+    route.kind = if flags & libc::RTF_BLACKHOLE != 0 {
+        RouteKind::Blackhole
+    } else if flags & libc::RTF_REJECT != 0 {
+        RouteKind::Unreachable
+    } else {
+        RouteKind::Unicast
+    };
+    Some(route)
+}
+
+fn list_routes(family: i32) -> io::Result<Vec<Route>> {
+    let mut mib = [
+        libc::CTL_NET,
+        libc::PF_ROUTE,
+        0,
+        family,
+        libc::NET_RT_DUMP,
+        0,
+    ];
+    let mut len = 0;
+    // the sandbox fails these with EPERM
+    if unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as u32,
+            ptr::null_mut(),
+            &mut len,
+            ptr::null_mut(),
+            0,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    let mut buf = vec![0u8; len];
+    if unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as u32,
+            buf.as_mut_ptr() as *mut _,
+            &mut len,
+            ptr::null_mut(),
+            0,
+        )
+    } < 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(len);
+
+    let mut routes = vec![];
+    let mut offset = 0;
+    while let Some(hdr) = read_header(&buf[offset..]) {
+        let msg_len = hdr.rtm_msglen as usize;
+        if msg_len < HDR_SIZE || offset + msg_len > buf.len() {
+            break;
+        }
+        if let Some(route) = message_to_route(&hdr, &buf[offset + HDR_SIZE..offset + msg_len]) {
+            routes.push(route);
+        }
+        offset += msg_len;
+    }
+    Ok(routes)
+}
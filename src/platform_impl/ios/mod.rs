@@ -0,0 +1,5 @@
+pub(crate) mod ios;
+#[cfg(feature = "nw-path-monitor")]
+mod path_monitor;
+
+pub(crate) use ios::Handle;
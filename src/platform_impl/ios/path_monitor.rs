@@ -0,0 +1,155 @@
+// NWPathMonitor is available to sandboxed apps and reports the gateways and interfaces of the
+// path the system currently uses, which is turned into a default route.
+
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::raw::c_char,
+    rc::Rc,
+    sync::Mutex,
+};
+
+use block2::{Block, RcBlock};
+use tokio::sync::broadcast;
+
+use crate::{Route, RouteChange};
+
+#[allow(non_camel_case_types)]
+type nw_object_t = *mut c_void;
+#[allow(non_camel_case_types)]
+type dispatch_queue_t = *mut c_void;
+
+#[link(name = "Network", kind = "framework")]
+extern "C" {
+    fn nw_path_monitor_create() -> nw_object_t;
+    fn nw_path_monitor_set_queue(monitor: nw_object_t, queue: dispatch_queue_t);
+    fn nw_path_monitor_set_update_handler(
+        monitor: nw_object_t,
+        update_handler: &Block<dyn Fn(nw_object_t)>,
+    );
+    fn nw_path_monitor_start(monitor: nw_object_t);
+    fn nw_path_monitor_cancel(monitor: nw_object_t);
+    fn nw_path_enumerate_gateways(
+        path: nw_object_t,
+        enumerate_block: &Block<dyn Fn(nw_object_t) -> bool>,
+    );
+    fn nw_path_enumerate_interfaces(
+        path: nw_object_t,
+        enumerate_block: &Block<dyn Fn(nw_object_t) -> bool>,
+    );
+    fn nw_endpoint_get_address(endpoint: nw_object_t) -> *const libc::sockaddr;
+    fn nw_interface_get_index(interface: nw_object_t) -> u32;
+    fn nw_release(obj: *mut c_void);
+}
+
+extern "C" {
+    fn dispatch_queue_create(label: *const c_char, attr: *mut c_void) -> dispatch_queue_t;
+    fn dispatch_release(object: *mut c_void);
+}
+
+pub(crate) struct PathMonitor {
+    monitor: nw_object_t,
+    queue: dispatch_queue_t,
+}
+
+// the monitor is only touched again to cancel it
+unsafe impl Send for PathMonitor {}
+unsafe impl Sync for PathMonitor {}
+
+impl PathMonitor {
+    /// Start sending the changes of the default route to `tx`.
+    pub(crate) fn start(tx: broadcast::Sender<RouteChange>) -> io::Result<Self> {
+        let monitor = unsafe { nw_path_monitor_create() };
+        if monitor.is_null() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "failed to create NWPathMonitor",
+            ));
+        }
+        let queue =
+            unsafe { dispatch_queue_create(b"net-route\0".as_ptr() as _, std::ptr::null_mut()) };
+
+        // the handler runs on the serial queue, one update at a time
+        let current = Mutex::new(None);
+        let handler = RcBlock::new(move |path: nw_object_t| {
+            let route = default_route(path);
+            let mut current = current.lock().unwrap();
+            if *current == route {
+                return;
+            }
+            _ = tx.send(match (current.take(), route.clone()) {
+                (None, Some(route)) => RouteChange::Add(route),
+                (Some(old), None) => RouteChange::Delete(old),
+                (_, Some(route)) => RouteChange::Change(route),
+                (None, None) => unreachable!(),
+            });
+            *current = route;
+        });
+        unsafe {
+            nw_path_monitor_set_queue(monitor, queue);
+            nw_path_monitor_set_update_handler(monitor, &handler);
+            nw_path_monitor_start(monitor);
+        }
+        Ok(Self { monitor, queue })
+    }
+}
+
+impl Drop for PathMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            nw_path_monitor_cancel(self.monitor);
+            nw_release(self.monitor);
+            dispatch_release(self.queue);
+        }
+    }
+}
+
+// the first gateway of `path` through its first interface
+fn default_route(path: nw_object_t) -> Option<Route> {
+    let gateway = Rc::new(RefCell::new(None));
+    let found = gateway.clone();
+    let enumerate = RcBlock::new(move |endpoint: nw_object_t| {
+        let addr = unsafe { nw_endpoint_get_address(endpoint) };
+        *found.borrow_mut() = unsafe { sockaddr_to_ip(addr) };
+        found.borrow().is_none()
+    });
+    unsafe { nw_path_enumerate_gateways(path, &enumerate) };
+    let gateway = gateway.take()?;
+
+    let ifindex = Rc::new(RefCell::new(None));
+    let found = ifindex.clone();
+    let enumerate = RcBlock::new(move |interface: nw_object_t| {
+        *found.borrow_mut() = Some(unsafe { nw_interface_get_index(interface) });
+        false
+    });
+    unsafe { nw_path_enumerate_interfaces(path, &enumerate) };
+
+    let destination = match gateway {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let mut route = Route::new(destination, 0).with_gateway(gateway);
+    route.ifindex = ifindex.take();
+    Some(route)
+}
+
+unsafe fn sockaddr_to_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    if addr.is_null() {
+        return None;
+    }
+    match (*addr).sa_family as i32 {
+        libc::AF_INET => {
+            let addr = &*(addr as *const libc::sockaddr_in);
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                addr.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let addr = &*(addr as *const libc::sockaddr_in6);
+            Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
@@ -1,245 +1,555 @@
-use crate::{Route, RouteChange, Rule};
+use super::procfs;
+use crate::{
+    Capabilities, DumpFilter, HandleBuilder, IpVersion, ListCursor, NextHop, Route, RouteChange,
+    RouteChunk, RouteKind, RouteProtocol, RouteScope, Vrf,
+};
+#[cfg(feature = "rules")]
+use crate::{IpProtocol, Rule};
 use std::io::{self, Error};
 
+use crate::error::{invalid_route, is_dump_interrupted};
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
 use async_stream::stream;
-use futures::{channel::mpsc::UnboundedReceiver, stream::TryStreamExt};
-use futures::{Stream, StreamExt};
-use netlink_packet_core::{NetlinkMessage, NetlinkPayload};
+use futures::StreamExt;
+use futures::{
+    channel::mpsc::UnboundedReceiver,
+    stream::{BoxStream, Stream, TryStreamExt},
+};
+#[cfg(feature = "rules")]
+use netlink_packet_core::NLM_F_REPLACE;
+use netlink_packet_core::{
+    NetlinkMessage, NetlinkPayload, NLM_F_ACK, NLM_F_CREATE, NLM_F_DUMP, NLM_F_DUMP_INTR,
+    NLM_F_ECHO, NLM_F_EXCL, NLM_F_REQUEST,
+};
+use netlink_packet_route::link::{InfoData, InfoVrf, LinkAttribute, LinkInfo, LinkMessage};
+#[cfg(feature = "rules")]
 use netlink_packet_route::rule::{RuleAttribute, RuleMessage};
 use netlink_packet_route::{
-    route::{RouteAddress, RouteAttribute, RouteMessage},
+    route::{RouteAddress, RouteAttribute, RouteMessage, RouteNextHop, RouteType},
     AddressFamily, RouteNetlinkMessage,
 };
-use netlink_sys::{AsyncSocket, SocketAddr};
+use netlink_packet_utils::nla::{DefaultNla, Nla};
+#[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+use netlink_sys::AsyncSocket;
+use netlink_sys::SocketAddr;
+use std::fs::File;
+use std::future::Future;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use tokio::{sync::broadcast, task::JoinHandle};
+use std::ops::ControlFlow;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch, Mutex};
 
-use rtnetlink::{
-    constants::{RTMGRP_IPV4_ROUTE, RTMGRP_IPV6_ROUTE},
-    new_connection,
-};
+use super::netlink::{new_connection, DumpSocket, Requests, Responses};
+use super::runtime::{self, Task};
+
+// not modeled by netlink-packet-route yet
+const RTA_NH_ID: u16 = 30;
+
+const CAP_NET_ADMIN: u32 = 12;
+const RT_TABLE_UNSPEC: u8 = 0;
+#[cfg(feature = "rules")]
+const RT_TABLE_MAIN: u8 = 254;
+
+// how often the table is dumped when route events can't be received
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// how often a dump is repeated when the routing table changed during it
+const DUMP_RETRIES: u32 = 3;
 
 pub struct Handle {
-    handle: rtnetlink::Handle,
-    join_handle: JoinHandle<()>,
-    listen_handle: JoinHandle<()>,
+    handle: Requests,
+    // The kernel runs one dump per socket at a time and fails the others with EBUSY, so the dumps
+    // of the handle and the poller take turns.
+    dumps: Arc<Mutex<()>>,
+    fd: RawFd,
+    // where the sockets of chunked listings are opened
+    netns: Option<PathBuf>,
+    owner: Option<RouteProtocol>,
+    ack: bool,
+    echo: bool,
+    join_handle: Option<Task>,
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
     tx: broadcast::Sender<RouteChange>,
+    #[cfg(feature = "events")]
+    membership: Arc<Membership>,
+    // dropped to stop the listener and the poller
+    _shutdown: watch::Sender<()>,
 }
 
 impl Handle {
-    pub(crate) fn new() -> io::Result<Self> {
-        let (mut connection, handle, messages) = new_connection()?;
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (mut connection, handle, messages) = match &config.netns {
+            Some(path) => in_netns(path, new_connection)??,
+            None => new_connection()?,
+        };
 
-        // These flags specify what kinds of broadcast messages we want to listen for.
-        let mgroup_flags = RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE;
+        // The socket is bound without multicast groups, they are only joined while there are
+        // streams of route events, see `Membership`.
+        let addr = SocketAddr::new(0, 0);
+        let events = match connection.socket_mut().socket_mut().bind(&addr) {
+            // Android 11+ doesn't allow apps to bind route sockets. Requests still work but route
+            // events have to be found by polling.
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => false,
+            res => res.map(|_| true)?,
+        };
+        let fd = connection.socket_mut().socket_mut().as_raw_fd();
+        #[cfg(feature = "events")]
+        let membership = Arc::new(Membership {
+            fd,
+            groups: match config.listen && events {
+                true => config.multicast_groups,
+                false => 0,
+            },
+            streams: std::sync::Mutex::new(Some(0)),
+        });
+
+        // Strict checking needs linux 4.20, older kernels just keep ignoring dump filters.
+        match set_socket_option(
+            fd,
+            libc::SOL_NETLINK,
+            libc::NETLINK_GET_STRICT_CHK,
+            config.strict_checking as libc::c_int,
+        ) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOPROTOOPT) => {}
+            res => res?,
+        }
+        // Extended acks carry a human readable reason for failed requests, available since 4.12.
+        match set_socket_option(fd, libc::SOL_NETLINK, libc::NETLINK_EXT_ACK, 1) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOPROTOOPT) => {}
+            res => res?,
+        }
+
+        if let Some(size) = config.recv_buffer_size {
+            set_buffer_size(fd, libc::SO_RCVBUFFORCE, libc::SO_RCVBUF, size)?;
+        }
+        if let Some(size) = config.send_buffer_size {
+            set_buffer_size(fd, libc::SO_SNDBUFFORCE, libc::SO_SNDBUF, size)?;
+        }
 
-        // A netlink socket address is created with said flags.
-        let addr = SocketAddr::new(0, mgroup_flags);
-        // Said address is bound so new conenctions and thus new message broadcasts can be received.
-        connection.socket_mut().socket_mut().bind(&addr)?;
-        let (tx, _) = broadcast::channel::<RouteChange>(16);
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
+        let (shutdown, _) = watch::channel(());
+        let dumps = Arc::new(Mutex::new(()));
 
-        let join_handle = tokio::spawn(connection);
-        let listen_handle = tokio::spawn(Self::listen(messages, tx.clone()));
+        // A single task drives the socket and forwards route events. The listener finishes once
+        // the connection drops its end of the message channel or the handle is dropped, the
+        // connection is only dropped after the poller finished its last dump.
+        let listener = Self::listen(messages, tx.clone(), shutdown.subscribe());
+        let poller = Self::poll(
+            handle.clone(),
+            dumps.clone(),
+            tx.clone(),
+            shutdown.subscribe(),
+            config.listen && !events,
+        );
+        let join_handle = Task::spawn(async move {
+            tokio::select! {
+                _ = connection => {}
+                _ = async { futures::join!(listener, poller) } => {}
+            }
+        });
 
         Ok(Self {
             handle,
-            join_handle,
-            listen_handle,
+            dumps,
+            fd,
+            netns: config.netns.clone(),
+            owner: config.owner_protocol,
+            ack: config.ack,
+            echo: config.echo,
+            join_handle: Some(join_handle),
             tx,
+            #[cfg(feature = "events")]
+            membership,
+            _shutdown: shutdown,
         })
     }
 
-    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
-        let mut routes = self.handle.route().get(rtnetlink::IpVersion::V4).execute();
+    pub(crate) async fn close(mut self) -> io::Result<()> {
+        let join_handle = self.join_handle.take();
+        // Every request made through the handle has been answered, dropping it stops the listener
+        // and lets the poller finish its dump before the connection goes away.
+        drop(self);
+        match join_handle {
+            Some(mut join_handle) => join_handle.join().await,
+            None => Ok(()),
+        }
+    }
 
-        while let Some(route) = routes
-            .try_next()
-            .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            if route.destination_prefix().is_none() {
-                return Ok(Some(route.into()));
+    fn request(
+        &self,
+        message: RouteNetlinkMessage,
+        flags: u16,
+    ) -> NetlinkMessage<RouteNetlinkMessage> {
+        let mut req = NetlinkMessage::from(message);
+        req.header.flags = NLM_F_REQUEST | flags;
+        if self.ack {
+            req.header.flags |= NLM_F_ACK;
+        }
+        if self.echo {
+            req.header.flags |= NLM_F_ECHO;
+        }
+        req
+    }
+
+    /// Send a request modifying the routing table, waiting for the kernel's confirmation
+    /// unless both acks and echo are disabled.
+    async fn execute(&self, message: RouteNetlinkMessage, flags: u16) -> io::Result<()> {
+        let req = self.request(message, flags);
+        if !self.ack && !self.echo {
+            // the kernel only answers on failure, which we don't wait for
+            return self.send_unanswered(req);
+        }
+        let mut response = self.handle.request(req)?;
+        let mut answered = false;
+        while let Some(msg) = response.next().await {
+            answered = true;
+            if let NetlinkPayload::Error(e) = msg.payload {
+                if e.code.is_some() {
+                    let e = ext_ack_error(&e, msg.header.flags);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %e, errno = ?errno(&e), "netlink request failed");
+                    return Err(e);
+                }
             }
         }
+        // The connection drops the request when sending it fails, e.g. with a full send buffer
+        // while many requests are in flight. Without an answer it can't be told applied.
+        match answered {
+            true => Ok(()),
+            false => Err(Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the netlink request was not answered",
+            )),
+        }
+    }
 
-        let mut routes = self.handle.route().get(rtnetlink::IpVersion::V6).execute();
+    // Send a request without waiting for its answer. It bypasses the connection, which would wait
+    // for an answer that never comes for a request that succeeded. The answer to a failed request
+    // has sequence number 0 like events, the listener logs and drops it.
+    fn send_unanswered(&self, mut req: NetlinkMessage<RouteNetlinkMessage>) -> io::Result<()> {
+        req.header.sequence_number = 0;
+        req.finalize();
+        let mut buf = vec![0; req.header.length as usize];
+        req.serialize(&mut buf);
+        // to the kernel, the socket isn't connected anywhere else
+        let sent = unsafe { libc::send(self.fd, buf.as_ptr().cast(), buf.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
 
-        while let Some(route) = routes
-            .try_next()
-            .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            if route.destination_prefix().is_none() {
-                return Ok(Some(route.into()));
+    pub(crate) async fn cleanup_owned(&self) -> io::Result<()> {
+        let owner = self.owner.ok_or_else(|| {
+            Error::new(io::ErrorKind::InvalidInput, "no owner protocol configured")
+        })?;
+        let filter = DumpFilter::default().with_protocol(owner);
+
+        for family in [AddressFamily::Inet, AddressFamily::Inet6] {
+            for msg in self.dump_route_messages(family, &filter).await? {
+                match self.execute(RouteNetlinkMessage::DelRoute(msg), 0).await {
+                    // removed by someone else in the meantime
+                    Err(e) if errno(&e) == Some(libc::ESRCH) => {}
+                    res => res?,
+                }
             }
         }
-        Ok(None)
+        Ok(())
     }
 
-    pub(crate) async fn list_rules(&self) -> io::Result<Vec<RuleMessage>> {
-        let mut rules = vec![];
-        let mut rule_messages = self.handle.rule().get(rtnetlink::IpVersion::V4).execute();
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        let status = std::fs::read_to_string("/proc/self/status")?;
+        let effective = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+            .ok_or_else(|| {
+                Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no effective capabilities in /proc/self/status",
+                )
+            })?;
+        let net_admin = effective & (1 << CAP_NET_ADMIN) != 0;
 
-        while let Some(rule) = rule_messages
-            .try_next()
-            .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            rules.push(rule.into());
+        Ok(Capabilities {
+            modify_routes: net_admin,
+            modify_rules: net_admin,
+        })
+    }
+
+    pub(crate) fn set_strict_checking(&self, enable: bool) -> io::Result<()> {
+        set_socket_option(
+            self.fd,
+            libc::SOL_NETLINK,
+            libc::NETLINK_GET_STRICT_CHK,
+            enable as libc::c_int,
+        )
+    }
+
+    pub(crate) fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        set_buffer_size(self.fd, libc::SO_RCVBUFFORCE, libc::SO_RCVBUF, size)
+    }
+
+    pub(crate) fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        set_buffer_size(self.fd, libc::SO_SNDBUFFORCE, libc::SO_SNDBUF, size)
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        match self.dump_default_route().await {
+            // dumps are restricted for apps on Android 11+
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                let route = procfs::list_routes().ok().and_then(|routes| {
+                    routes
+                        .into_iter()
+                        .find(|route| route.prefix == 0 && route.gateway.is_some())
+                });
+                match route {
+                    Some(route) => Ok(Some(route)),
+                    None => procfs::default_route_by_connect(),
+                }
+            }
+            res => res,
         }
+    }
 
-        let mut rule_messages = self.handle.rule().get(rtnetlink::IpVersion::V6).execute();
+    async fn dump_default_route(&self) -> io::Result<Option<Route>> {
+        let (v4, v6) = futures::try_join!(
+            self.dump_default_route_of(AddressFamily::Inet),
+            self.dump_default_route_of(AddressFamily::Inet6),
+        )?;
+        Ok(v4.or(v6))
+    }
 
-        while let Some(rule) = rule_messages
-            .try_next()
-            .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            rules.push(rule.into());
+    async fn dump_default_route_of(&self, family: AddressFamily) -> io::Result<Option<Route>> {
+        retry_interrupted(move || async move {
+            let request = RouteNetlinkMessage::GetRoute(route_dump(family));
+            let mut messages = self.dump_apart(request).await?;
+
+            while let Some(msg) = messages.try_next().await? {
+                if let RouteNetlinkMessage::NewRoute(route) = msg {
+                    // dropping the socket drops the rest of the dump
+                    if route.destination_prefix().is_none() {
+                        return route.try_into().map(Some);
+                    }
+                }
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    pub(crate) async fn get_route(&self, destination: IpAddr) -> io::Result<Route> {
+        self.lookup_route(destination, None, None, None).await
+    }
+
+    pub(crate) async fn lookup_route(
+        &self,
+        destination: IpAddr,
+        source: Option<IpAddr>,
+        iif: Option<u32>,
+        mark: Option<u32>,
+    ) -> io::Result<Route> {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = match destination {
+            IpAddr::V4(_) => AddressFamily::Inet,
+            IpAddr::V6(_) => AddressFamily::Inet6,
+        };
+        msg.header.destination_prefix_length = max_prefix(destination);
+        msg.attributes
+            .push(RouteAttribute::Destination(ip_to_addr(destination)));
+
+        if let Some(source) = source {
+            if source.is_ipv4() != destination.is_ipv4() {
+                return Err(invalid_route("source version must match destination"));
+            }
+            msg.header.source_prefix_length = max_prefix(source);
+            msg.attributes
+                .push(RouteAttribute::Source(ip_to_addr(source)));
+        }
+        if let Some(iif) = iif {
+            msg.attributes.push(RouteAttribute::Iif(iif));
+        }
+        if let Some(mark) = mark {
+            msg.attributes.push(RouteAttribute::Mark(mark));
         }
+
+        // a single lookup is sent without NLM_F_DUMP
+        let mut req = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(msg));
+        req.header.flags = NLM_F_REQUEST;
+
+        let mut response = self.handle.request(req)?;
+        while let Some(msg) = response.next().await {
+            match msg.payload {
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(msg)) => {
+                    return msg.try_into()
+                }
+                NetlinkPayload::Error(e) => return Err(ext_ack_error(&e, msg.header.flags)),
+                _ => {}
+            }
+        }
+        Err(Error::new(
+            io::ErrorKind::NotFound,
+            "No route found for destination",
+        ))
+    }
+
+    #[cfg(feature = "rules")]
+    pub(crate) async fn list_rules(&self) -> io::Result<Vec<Rule>> {
+        Ok(self
+            .list_rule_messages()
+            .await?
+            .into_iter()
+            .map(Rule::from)
+            .collect())
+    }
+
+    #[cfg(feature = "rules")]
+    pub(crate) async fn list_rule_messages(&self) -> io::Result<Vec<RuleMessage>> {
+        let (mut rules, v6) = futures::try_join!(
+            self.dump_rules(AddressFamily::Inet),
+            self.dump_rules(AddressFamily::Inet6),
+        )?;
+        rules.extend(v6);
         Ok(rules)
     }
 
+    #[cfg(feature = "rules")]
+    async fn dump_rules(&self, family: AddressFamily) -> io::Result<Vec<RuleMessage>> {
+        retry_interrupted(move || async move {
+            let mut request = RuleMessage::default();
+            request.header.family = family;
+            let mut messages = self
+                .dump_apart(RouteNetlinkMessage::GetRule(request))
+                .await?;
+
+            let mut rules = vec![];
+            while let Some(msg) = messages.try_next().await? {
+                if let RouteNetlinkMessage::NewRule(rule) = msg {
+                    rules.push(rule);
+                }
+            }
+            Ok(rules)
+        })
+        .await
+    }
+
+    #[cfg(feature = "rules")]
     pub(crate) async fn add_rules(&self, rules: Vec<Rule>) -> io::Result<()> {
         for rule in rules {
-            let mut req = self.handle.rule().add();
+            let mut msg = RuleMessage::default();
             // the default action is unspec, which doesn't work here
-            req.message_mut().header.action = netlink_packet_route::rule::RuleAction::ToTable;
-            // the src & dst is not supported in the common api, so we have to handle it manully
+            msg.header.action = netlink_packet_route::rule::RuleAction::ToTable;
+            msg.header.table = RT_TABLE_MAIN;
+            msg.header.family = if rule.v6 {
+                AddressFamily::Inet6
+            } else {
+                AddressFamily::Inet
+            };
             if let Some(input_interface) = rule.input_interface {
-                req = req.input_interface(input_interface);
+                msg.attributes.push(RuleAttribute::Iifname(input_interface));
             }
             if let Some(output_interface) = rule.output_interface {
-                req = req.output_interface(output_interface);
+                msg.attributes
+                    .push(RuleAttribute::Oifname(output_interface));
             }
             if let Some(table_id) = rule.table_id {
-                req = req.table_id(table_id);
+                // the header only has room for the first 256 tables
+                match u8::try_from(table_id) {
+                    Ok(table) => msg.header.table = table,
+                    Err(_) => msg.attributes.push(RuleAttribute::Table(table_id)),
+                }
             }
             if let Some(priority) = rule.priority {
-                req = req.priority(priority);
+                msg.attributes.push(RuleAttribute::Priority(priority));
             }
             if let Some((fw_mark, fw_mask)) = rule.fw_mark_mask {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::FwMark(fw_mark));
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::FwMask(fw_mask));
+                msg.attributes.push(RuleAttribute::FwMark(fw_mark));
+                msg.attributes.push(RuleAttribute::FwMask(fw_mask));
             }
             if let Some(suppress_prefixlength) = rule.suppress_prefixlength {
-                req.message_mut()
-                    .attributes
+                msg.attributes
                     .push(RuleAttribute::SuppressPrefixLen(suppress_prefixlength));
             }
             if let Some(protocol) = rule.protocol {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::IpProtocol(protocol));
+                msg.attributes.push(RuleAttribute::IpProtocol(
+                    netlink_packet_route::IpProtocol::from(protocol.as_raw() as i32),
+                ));
             }
-            req = req.replace();
-            if rule.v6 {
-                let mut req = req.v6();
-                if let Some((src, prefix)) = rule.src {
-                    if let IpAddr::V6(src) = src {
-                        req = req.source_prefix(src, prefix);
-                    }
+            // addresses of the other version are left out
+            if let Some((src, prefix)) = rule.src {
+                if src.is_ipv6() == rule.v6 {
+                    msg.header.src_len = prefix;
+                    msg.attributes.push(RuleAttribute::Source(src));
                 }
-                if let Some((dst, prefix)) = rule.dst {
-                    if let IpAddr::V6(dst) = dst {
-                        req = req.destination_prefix(dst, prefix);
-                    }
-                }
-                req.execute()
-                    .await
-                    .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?;
-            } else {
-                let mut req = req.v4();
-                if let Some((src, prefix)) = rule.src {
-                    if let IpAddr::V4(src) = src {
-                        req = req.source_prefix(src, prefix);
-                    }
-                }
-                if let Some((dst, prefix)) = rule.dst {
-                    if let IpAddr::V4(dst) = dst {
-                        req = req.destination_prefix(dst, prefix);
-                    }
+            }
+            if let Some((dst, prefix)) = rule.dst {
+                if dst.is_ipv6() == rule.v6 {
+                    msg.header.dst_len = prefix;
+                    msg.attributes.push(RuleAttribute::Destination(dst));
                 }
-                req.execute()
-                    .await
-                    .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?;
             }
+            self.execute(
+                RouteNetlinkMessage::NewRule(msg),
+                NLM_F_CREATE | NLM_F_REPLACE,
+            )
+            .await?;
         }
         Ok(())
     }
 
+    #[cfg(feature = "rules")]
     pub async fn delete_rules(&self, rules: Vec<Rule>) -> io::Result<()> {
         let mut failed = vec![];
         for rule in rules {
             let original_rule = rule.clone();
-            let message = RuleMessage::default();
-            let mut req = self.handle.rule().del(message);
-            req.message_mut().header.action = netlink_packet_route::rule::RuleAction::ToTable;
+            let mut msg = RuleMessage::default();
+            msg.header.action = netlink_packet_route::rule::RuleAction::ToTable;
             if let Some(src) = rule.src {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Source(src.0));
-                req.message_mut().header.src_len = src.1;
+                msg.attributes.push(RuleAttribute::Source(src.0));
+                msg.header.src_len = src.1;
             }
             if let Some(dst) = rule.dst {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Destination(dst.0));
-                req.message_mut().header.dst_len = dst.1;
+                msg.attributes.push(RuleAttribute::Destination(dst.0));
+                msg.header.dst_len = dst.1;
             }
             if let Some(ifname) = rule.input_interface {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Iifname(ifname));
+                msg.attributes.push(RuleAttribute::Iifname(ifname));
             }
             if let Some(ifname) = rule.output_interface {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Oifname(ifname));
+                msg.attributes.push(RuleAttribute::Oifname(ifname));
             }
             if let Some(table_id) = rule.table_id {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Table(table_id));
+                msg.attributes.push(RuleAttribute::Table(table_id));
             }
             if let Some(priority) = rule.priority {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::Priority(priority));
+                msg.attributes.push(RuleAttribute::Priority(priority));
             }
             if let Some((fw_mark, fw_mask)) = rule.fw_mark_mask {
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::FwMark(fw_mark));
-                req.message_mut()
-                    .attributes
-                    .push(RuleAttribute::FwMask(fw_mask));
+                msg.attributes.push(RuleAttribute::FwMark(fw_mark));
+                msg.attributes.push(RuleAttribute::FwMask(fw_mask));
             }
             if let Some(suppress_prefixlength) = rule.suppress_prefixlength {
-                req.message_mut()
-                    .attributes
+                msg.attributes
                     .push(RuleAttribute::SuppressPrefixLen(suppress_prefixlength));
             }
             if rule.v6 {
-                req.message_mut().header.family = AddressFamily::Inet6;
+                msg.header.family = AddressFamily::Inet6;
             } else {
-                req.message_mut().header.family = AddressFamily::Inet;
+                msg.header.family = AddressFamily::Inet;
             }
-            match req.execute().await {
-                Ok(_) => (),
-                Err(e) => {
-                    failed.push((original_rule, e));
-                }
+            if let Err(e) = self.execute(RouteNetlinkMessage::DelRule(msg), 0).await {
+                failed.push((original_rule, e));
             }
         }
-        if !failed.is_empty() {
+        if let Some((_, first)) = failed.first() {
             return Err(Error::new(
-                io::ErrorKind::Other,
+                first.kind(),
                 format!("Failed to delete rules: {:?}", failed),
             ));
         }
@@ -247,38 +557,282 @@ impl Handle {
     }
 
     pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
-        let mut routes = vec![];
-        let mut route_messages = self.handle.route().get(rtnetlink::IpVersion::V4).execute();
+        self.list_filtered(&DumpFilter::default()).await
+    }
 
-        while let Some(route) = route_messages
-            .try_next()
+    pub(crate) async fn list_by_protocol(&self, protocol: RouteProtocol) -> io::Result<Vec<Route>> {
+        self.list_filtered(&DumpFilter::default().with_protocol(protocol))
             .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            routes.push(route.into());
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        match self.dump_filtered(filter).await {
+            // dumps are restricted for apps on Android 11+
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                let mut routes = procfs::list_routes()?;
+                routes.retain(|route| filter.matches(route));
+                Ok(routes)
+            }
+            res => res,
         }
+    }
 
-        let mut route_messages = self.handle.route().get(rtnetlink::IpVersion::V6).execute();
+    async fn dump_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        // both families are dumped at the same time, each on a socket of its own
+        let dump = |version, wanted| async move {
+            if wanted {
+                self.dump_routes(version, filter).await
+            } else {
+                Ok(vec![])
+            }
+        };
+        let (mut routes, v6) = futures::try_join!(
+            dump(AddressFamily::Inet, filter.includes(IpVersion::V4)),
+            dump(AddressFamily::Inet6, filter.includes(IpVersion::V6)),
+        )?;
+        routes.extend(v6);
+        Ok(routes)
+    }
+
+    pub(crate) async fn for_each_route(
+        &self,
+        filter: &DumpFilter,
+        mut visit: impl FnMut(&Route) -> ControlFlow<()>,
+    ) -> io::Result<()> {
+        // unlike list_filtered the families are dumped one after the other, as they share `visit`
+        for (family, version) in [
+            (AddressFamily::Inet, IpVersion::V4),
+            (AddressFamily::Inet6, IpVersion::V6),
+        ] {
+            if !filter.includes(version) {
+                continue;
+            }
+            let result = self
+                .visit_dump(family, filter, DumpOn::SharedSocket, |_, route| {
+                    visit(&route)
+                })
+                .await;
+            match result {
+                Ok(ControlFlow::Continue(())) => {}
+                Ok(ControlFlow::Break(())) => break,
+                // dumps are restricted for apps on Android 11+, which fail before the first route
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    for route in procfs::list_routes()? {
+                        if filter.matches(&route) && visit(&route).is_break() {
+                            break;
+                        }
+                    }
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 
-        while let Some(route) = route_messages
-            .try_next()
+    pub(crate) fn list_chunks(
+        &self,
+        filter: DumpFilter,
+        chunk_size: usize,
+        resume: ListCursor,
+    ) -> impl Stream<Item = io::Result<RouteChunk>> + Send + 'static {
+        let dump = ChunkedDump {
+            netns: self.netns.clone(),
+            strict: self.strict_checking(),
+            filter,
+            chunk_size: chunk_size.max(1),
+            cursor: resume,
+            socket: None,
+            received: None,
+            interrupted: false,
+            failed: None,
+        };
+        futures::stream::unfold(Some(dump), |dump| async move {
+            let mut dump = dump?;
+            match dump.next_chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(dump))),
+                Ok(None) => None,
+                // the listing ends with its first error
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    pub(crate) async fn list_vrfs(&self) -> io::Result<Vec<Vrf>> {
+        retry_interrupted(move || async move {
+            let _turn = self.dumps.lock().await;
+            let request = RouteNetlinkMessage::GetLink(LinkMessage::default());
+            let mut messages = dump_request(&self.handle, request)?;
+
+            let mut vrfs = vec![];
+            while let Some(msg) = messages.try_next().await? {
+                if let RouteNetlinkMessage::NewLink(link) = msg {
+                    vrfs.extend(link_to_vrf(link));
+                }
+            }
+            Ok(vrfs)
+        })
+        .await
+    }
+
+    async fn vrf(&self, name: &str) -> io::Result<Vrf> {
+        self.list_vrfs()
+            .await?
+            .into_iter()
+            .find(|vrf| vrf.name == name)
+            .ok_or_else(|| Error::new(io::ErrorKind::NotFound, format!("No VRF named {}", name)))
+    }
+
+    pub(crate) async fn list_in_vrf(&self, vrf: &str) -> io::Result<Vec<Route>> {
+        let table = self.vrf(vrf).await?.table;
+        self.list_filtered(&DumpFilter::default().with_table(table))
             .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            routes.push(route.into());
+    }
+
+    pub(crate) async fn add_in_vrf(&self, route: &Route, vrf: &str) -> io::Result<()> {
+        let route = route.clone().with_table(self.vrf(vrf).await?.table);
+        self.add(&route).await
+    }
+
+    async fn dump_routes(
+        &self,
+        family: AddressFamily,
+        filter: &DumpFilter,
+    ) -> io::Result<Vec<Route>> {
+        self.dump(family, filter, |_, route| route).await
+    }
+
+    async fn dump_route_messages(
+        &self,
+        family: AddressFamily,
+        filter: &DumpFilter,
+    ) -> io::Result<Vec<RouteMessage>> {
+        self.dump(family, filter, |msg, _| msg).await
+    }
+
+    // Dump the routes matching `filter`, keeping what `keep` takes from every message and the
+    // route it was converted into. Dumps again when the table changed during the dump. Each dump
+    // runs on a socket of its own, so the families can be dumped at the same time.
+    async fn dump<T>(
+        &self,
+        family: AddressFamily,
+        filter: &DumpFilter,
+        keep: impl Fn(RouteMessage, Route) -> T,
+    ) -> io::Result<Vec<T>> {
+        let keep = &keep;
+        retry_interrupted(move || async move {
+            let mut kept = vec![];
+            self.visit_dump(family, filter, DumpOn::OwnSocket, |msg, route| {
+                kept.push(keep(msg, route));
+                ControlFlow::Continue(())
+            })
+            .await?;
+            Ok(kept)
+        })
+        .await
+    }
+
+    // Hand every message matching `filter` to `visit` together with the route it was converted
+    // into, as soon as it was received. Each message is converted once, without copying it.
+    // Stops early, dropping the rest of the dump, when `visit` breaks. Fails with
+    // `Error::DumpInterrupted` after the last message if the table changed during the dump.
+    async fn visit_dump(
+        &self,
+        family: AddressFamily,
+        filter: &DumpFilter,
+        on: DumpOn,
+        mut visit: impl FnMut(RouteMessage, Route) -> ControlFlow<()>,
+    ) -> io::Result<ControlFlow<()>> {
+        let request = RouteNetlinkMessage::GetRoute(filtered_route_dump(family, filter));
+        // the turn on the shared socket is held until the dump was read
+        let (_turn, mut route_messages) = match on {
+            DumpOn::OwnSocket => (None, self.dump_apart(request).await?),
+            DumpOn::SharedSocket => (
+                Some(self.dumps.lock().await),
+                dump_request(&self.handle, request)?,
+            ),
+        };
+
+        loop {
+            let route = match route_messages.try_next().await {
+                Ok(Some(RouteNetlinkMessage::NewRoute(route))) => route,
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                // dumping a table that doesn't exist fails instead of returning nothing
+                Err(e) if filter.table.is_some() && errno(&e) == Some(libc::ENOENT) => break,
+                Err(e) => return Err(e),
+            };
+            // routes of other families (e.g. MPLS) can't be represented and are skipped
+            match Route::try_from(&route) {
+                Ok(converted) if filter.matches(&converted) => {
+                    if visit(route, converted).is_break() {
+                        finish(route_messages).await;
+                        return Ok(ControlFlow::Break(()));
+                    }
+                }
+                _ => {}
+            }
         }
-        Ok(routes)
+        Ok(ControlFlow::Continue(()))
+    }
+
+    // Whether strict checking is enabled on the shared socket, for the sockets of dumps.
+    fn strict_checking(&self) -> bool {
+        socket_option(self.fd, libc::SOL_NETLINK, libc::NETLINK_GET_STRICT_CHK)
+            .map_or(false, |strict| strict != 0)
     }
 
-    pub(crate) fn route_listen_stream(&self) -> impl Stream<Item = RouteChange> {
+    // Send a dump request on a socket of its own, returning the messages of the answer as they
+    // arrive. The socket is opened like the shared one and closed with the dump.
+    async fn dump_apart(&self, message: RouteNetlinkMessage) -> io::Result<Dump> {
+        let mut socket = open_dump_socket(self.netns.as_deref(), self.strict_checking())?;
+        let mut req = NetlinkMessage::from(message);
+        req.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+        req.header.sequence_number = 1;
+        socket.send(req).await?;
+
+        let responses = futures::stream::unfold(Some(socket), |socket| async move {
+            let mut socket = socket?;
+            match socket.recv().await {
+                Ok(msg) => {
+                    let last = matches!(
+                        msg.payload,
+                        NetlinkPayload::Done(_) | NetlinkPayload::Error(_)
+                    );
+                    Some((Ok(msg), (!last).then_some(socket)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        Ok(Dump {
+            responses: responses.boxed(),
+            interrupted: false,
+        })
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
         let mut rx = self.tx.subscribe();
+        let subscription = self.membership.subscribe();
         stream! {
+            let _subscription = subscription;
             loop {
                 match rx.recv().await {
-                    Ok(ev) => yield ev,
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
                     Err(e) => match e {
                         broadcast::error::RecvError::Closed => break,
-                        broadcast::error::RecvError::Lagged(_) => continue,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
                     }
                 }
             }
@@ -286,196 +840,803 @@ impl Handle {
     }
 
     pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
-        let route_handle = self.handle.route();
-        let mut routes = match route.destination {
-            IpAddr::V4(_) => route_handle.get(rtnetlink::IpVersion::V4),
-            IpAddr::V6(_) => route_handle.get(rtnetlink::IpVersion::V6),
+        let msg = delete_message(route)?;
+        match self.execute(RouteNetlinkMessage::DelRoute(msg), 0).await {
+            Err(e) if errno(&e) == Some(libc::ESRCH) => Err(Error::new(
+                io::ErrorKind::NotFound,
+                "No matching route found to delete",
+            )),
+            res => res,
         }
-        .execute();
+    }
 
-        while let Some(msg) = routes
-            .try_next()
-            .await
-            .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?
-        {
-            let other_route: Route = msg.clone().into();
-            if other_route.destination == route.destination
-                && other_route.prefix == route.prefix
-                && other_route.metric == route.metric
-            {
-                route_handle
-                    .del(msg)
-                    .execute()
-                    .await
-                    .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))?;
-                return Ok(());
+    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
+        let message = self.add_message(route)?;
+        self.execute(
+            RouteNetlinkMessage::NewRoute(message),
+            NLM_F_CREATE | NLM_F_EXCL,
+        )
+        .await
+    }
+
+    /// Encode the requests `add` and `delete` would send for `route`, for dry runs.
+    pub(crate) fn encode_add(&self, route: &Route) -> io::Result<Vec<u8>> {
+        let message = self.add_message(route)?;
+        Ok(self.encode(
+            RouteNetlinkMessage::NewRoute(message),
+            NLM_F_CREATE | NLM_F_EXCL,
+        ))
+    }
+
+    pub(crate) fn encode_delete(&self, route: &Route) -> io::Result<Vec<u8>> {
+        let message = delete_message(route)?;
+        Ok(self.encode(RouteNetlinkMessage::DelRoute(message), 0))
+    }
+
+    fn encode(&self, message: RouteNetlinkMessage, flags: u16) -> Vec<u8> {
+        let mut req = self.request(message, flags);
+        req.finalize();
+        let mut buf = vec![0; req.header.length as usize];
+        req.serialize(&mut buf);
+        buf
+    }
+
+    fn add_message(&self, route: &Route) -> io::Result<RouteMessage> {
+        // the addresses besides the destination have to be of its version
+        let same_version = |addr: IpAddr, what: &str| {
+            if addr.is_ipv4() == route.destination.is_ipv4() {
+                Ok(ip_to_addr(addr))
+            } else {
+                Err(invalid_route(format!(
+                    "{what} version must match destination"
+                )))
+            }
+        };
+
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = match route.destination {
+            IpAddr::V4(_) => AddressFamily::Inet,
+            IpAddr::V6(_) => AddressFamily::Inet6,
+        };
+        msg.header.destination_prefix_length = route.prefix;
+        msg.header.protocol = self.owner.unwrap_or(route.protocol).as_raw().into();
+        msg.header.scope = route.scope.as_raw().into();
+        msg.header.kind = route_type(route.kind)?;
+        // the header only has room for the first 256 tables
+        match u8::try_from(route.table) {
+            Ok(table) => msg.header.table = table,
+            Err(_) => {
+                msg.header.table = RT_TABLE_UNSPEC;
+                msg.attributes.push(RouteAttribute::Table(route.table));
             }
         }
+        msg.attributes
+            .push(RouteAttribute::Destination(ip_to_addr(route.destination)));
 
-        Err(Error::new(
-            io::ErrorKind::NotFound,
-            "No matching route found to delete",
-        ))
+        if let Some(ifindex) = route.ifindex {
+            msg.attributes.push(RouteAttribute::Oif(ifindex));
+        }
+        if let Some(metric) = route.metric {
+            msg.attributes.push(RouteAttribute::Priority(metric));
+        }
+        if let Some(id) = route.nexthop_id {
+            msg.attributes.push(RouteAttribute::Other(DefaultNla::new(
+                RTA_NH_ID,
+                id.to_ne_bytes().to_vec(),
+            )));
+        }
+        if let Some(gateway) = route.gateway {
+            let gateway = same_version(gateway, "gateway")?;
+            msg.attributes.push(RouteAttribute::Gateway(gateway));
+        }
+        if let Some(src_hint) = route.source_hint {
+            let src_hint = same_version(src_hint, "source hint")?;
+            msg.attributes.push(RouteAttribute::PrefSource(src_hint));
+        }
+        if let Some(src) = route.source {
+            let src = same_version(src, "source")?;
+            msg.header.source_prefix_length = route.source_prefix;
+            msg.attributes.push(RouteAttribute::Source(src));
+        }
+        Ok(msg)
     }
 
-    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
-        let route_handle = self.handle.route();
-        match route.destination {
-            IpAddr::V4(addr) => {
-                let mut msg = route_handle
-                    .add()
-                    .v4()
-                    .table_id(route.table.into())
-                    .destination_prefix(addr, route.prefix);
-
-                if let Some(ifindex) = route.ifindex {
-                    msg = msg.output_interface(ifindex);
-                }
-
-                if let Some(metric) = route.metric {
-                    msg = msg.priority(metric);
-                }
-
-                if let Some(gateway) = route.gateway {
-                    msg = match gateway {
-                        IpAddr::V4(addr) => msg.gateway(addr),
-                        IpAddr::V6(_) => {
-                            return Err(Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "gateway version must match destination",
-                            ))
+    // Report the differences between dumps as events, for when the multicast groups can't be
+    // joined. Nothing is dumped while there are no listeners.
+    async fn poll(
+        handle: Requests,
+        dumps: Arc<Mutex<()>>,
+        tx: broadcast::Sender<RouteChange>,
+        mut shutdown: watch::Receiver<()>,
+        needed: bool,
+    ) {
+        if !needed {
+            return;
+        }
+        let mut known: Option<Vec<Route>> = None;
+        loop {
+            if tx.receiver_count() == 0 {
+                known = None;
+            } else {
+                let routes = {
+                    let _turn = dumps.lock().await;
+                    retry_interrupted(|| dump_all(&handle)).await
+                };
+                match routes {
+                    Ok(routes) => {
+                        if let Some(known) = &known {
+                            for route in known.iter().filter(|route| !routes.contains(route)) {
+                                _ = tx.send(RouteChange::Delete(route.clone()));
+                            }
+                            for route in routes.iter().filter(|route| !known.contains(route)) {
+                                _ = tx.send(RouteChange::Add(route.clone()));
+                            }
                         }
-                    };
+                        known = Some(routes);
+                    }
+                    Err(_e) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(error = %_e, "dumping routes to find changes failed");
+                    }
                 }
+            }
+            tokio::select! {
+                _ = runtime::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.changed() => return,
+            }
+        }
+    }
 
-                if let Some(src_hint) = route.source_hint {
-                    msg = match src_hint {
-                        IpAddr::V4(addr) => msg.pref_source(addr),
-                        IpAddr::V6(_) => {
-                            return Err(Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "source hint version must match destination",
-                            ))
-                        }
-                    };
+    async fn listen(
+        mut messages: UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
+        tx: broadcast::Sender<RouteChange>,
+        mut shutdown: watch::Receiver<()>,
+    ) {
+        loop {
+            let message = tokio::select! {
+                message = messages.next() => message,
+                _ = shutdown.changed() => None,
+            };
+            let Some((message, _)) = message else {
+                break;
+            };
+            let change = match message.payload {
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(msg)) => {
+                    msg.try_into().ok().map(RouteChange::Add)
                 }
-
-                if let Some(src) = route.source {
-                    msg = match src {
-                        IpAddr::V4(addr) => msg.source_prefix(addr, route.source_prefix),
-                        IpAddr::V6(_) => {
-                            return Err(Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "source version must match destination",
-                            ))
-                        }
-                    };
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::DelRoute(msg)) => {
+                    msg.try_into().ok().map(RouteChange::Delete)
+                }
+                // a request sent without an ack failed, see `Handle::send_unanswered`
+                #[cfg(feature = "tracing")]
+                NetlinkPayload::Error(e) if e.code.is_some() => {
+                    let e = ext_ack_error(&e, message.header.flags);
+                    tracing::warn!(error = %e, "a request sent without an ack failed");
+                    None
                 }
-                msg.execute()
-                    .await
-                    .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))
+                _ => None,
+            };
+            if let Some(change) = change {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(?change, "route changed");
+                _ = tx.send(change);
             }
-            IpAddr::V6(addr) => {
-                let mut msg = route_handle
-                    .add()
-                    .v6()
-                    .table_id(route.table.into())
-                    .destination_prefix(addr, route.prefix);
+        }
+    }
+}
 
-                if let Some(ifindex) = route.ifindex {
-                    msg = msg.output_interface(ifindex);
-                }
+impl Drop for Handle {
+    fn drop(&mut self) {
+        #[cfg(feature = "events")]
+        self.membership.close();
+        if let Some(join_handle) = &self.join_handle {
+            join_handle.abort();
+        }
+    }
+}
 
-                if let Some(metric) = route.metric {
-                    msg = msg.priority(metric);
-                }
+// Joins the multicast groups route events are sent to while there are streams, so handles only
+// used to change routes don't receive every route event of the system.
+#[cfg(feature = "events")]
+struct Membership {
+    fd: RawFd,
+    groups: u32,
+    // the number of streams, `None` once the socket is closed as its descriptor may be reused
+    streams: std::sync::Mutex<Option<usize>>,
+}
 
-                if let Some(gateway) = route.gateway {
-                    msg = match gateway {
-                        IpAddr::V6(addr) => msg.gateway(addr),
-                        IpAddr::V4(_) => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "gateway version must match destination",
-                            ))
-                        }
-                    };
-                }
+#[cfg(feature = "events")]
+impl Membership {
+    // Count a new stream, joining the groups for the first one.
+    fn subscribe(self: &Arc<Self>) -> Subscription {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(streams) = streams.as_mut() {
+            if *streams == 0 {
+                self.set(libc::NETLINK_ADD_MEMBERSHIP);
+            }
+            *streams += 1;
+        }
+        Subscription(self.clone())
+    }
 
-                if let Some(src_hint) = route.source_hint {
-                    msg = match src_hint {
-                        IpAddr::V6(addr) => msg.pref_source(addr),
-                        IpAddr::V4(_) => {
-                            return Err(Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "source hint version must match destination",
-                            ))
-                        }
-                    };
+    fn close(&self) {
+        *self.streams.lock().unwrap() = None;
+    }
+
+    fn set(&self, option: libc::c_int) {
+        // the groups are numbered from 1, bit 0 of the mask is group 1
+        for group in (1..=32).filter(|group| self.groups & 1 << (group - 1) != 0) {
+            if let Err(_e) = set_socket_option(self.fd, libc::SOL_NETLINK, option, group) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_e, group, "changing the multicast groups failed");
+            }
+        }
+    }
+}
+
+// Held by a stream of route events, leaves the groups once the last one is dropped.
+#[cfg(feature = "events")]
+struct Subscription(Arc<Membership>);
+
+#[cfg(feature = "events")]
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut streams = self.0.streams.lock().unwrap();
+        if let Some(streams) = streams.as_mut() {
+            *streams -= 1;
+            if *streams == 0 {
+                self.0.set(libc::NETLINK_DROP_MEMBERSHIP);
+            }
+        }
+    }
+}
+
+// A request dumping the routes of `family`.
+fn route_dump(family: AddressFamily) -> RouteMessage {
+    let mut msg = RouteMessage::default();
+    msg.header.address_family = family;
+    msg
+}
+
+// A request dumping the routes of `family` that match `filter`. The kernel only honours the
+// filter when strict checking is enabled on the socket, so `filter.matches` still has to be
+// applied to the answer. The scope can't be part of the request as strict checking rejects dump
+// requests with a non zero scope.
+fn filtered_route_dump(family: AddressFamily, filter: &DumpFilter) -> RouteMessage {
+    let mut msg = route_dump(family);
+    if let Some(protocol) = filter.protocol {
+        msg.header.protocol = protocol.as_raw().into();
+    }
+    if let Some(table) = filter.table {
+        msg.attributes.push(RouteAttribute::Table(table));
+    }
+    if let Some(oif) = filter.oif {
+        msg.attributes.push(RouteAttribute::Oif(oif));
+    }
+    msg
+}
+
+// The families in the order chunked listings dump them, indexed by `ListCursor::family`.
+const CHUNKED_FAMILIES: [(AddressFamily, IpVersion); 2] = [
+    (AddressFamily::Inet, IpVersion::V4),
+    (AddressFamily::Inet6, IpVersion::V6),
+];
+
+// A listing in chunks, see `Handle::list_chunks`. The families are dumped one after the other on
+// a socket of the listing's own.
+struct ChunkedDump {
+    netns: Option<PathBuf>,
+    // strict checking like on the shared socket
+    strict: bool,
+    filter: DumpFilter,
+    chunk_size: usize,
+    cursor: ListCursor,
+    socket: Option<DumpSocket>,
+    // the route messages received from the dump of the cursor's family, `None` before it started
+    received: Option<u64>,
+    interrupted: bool,
+    // reported after the chunk that was completed along with it
+    failed: Option<io::Error>,
+}
+
+impl ChunkedDump {
+    // The next chunk, `None` once every family was dumped.
+    async fn next_chunk(&mut self) -> io::Result<Option<RouteChunk>> {
+        if let Some(e) = self.failed.take() {
+            return Err(e);
+        }
+        let mut routes = vec![];
+        loop {
+            let Some(&(family, version)) = CHUNKED_FAMILIES.get(self.cursor.family as usize) else {
+                return Ok(None);
+            };
+            if !self.filter.includes(version) {
+                self.next_family();
+                continue;
+            }
+            let received = match self.received {
+                Some(received) => received,
+                None => self.start(family).await?,
+            };
+            let socket = self.socket.as_mut().expect("the dump was started");
+            let msg = socket.recv().await?;
+            self.interrupted |= msg.header.flags & NLM_F_DUMP_INTR != 0;
+            let end = match msg.payload {
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(route)) => {
+                    let received = received + 1;
+                    self.received = Some(received);
+                    // passed before the listing was resumed, not even converted
+                    if received <= self.cursor.position {
+                        continue;
+                    }
+                    self.cursor.position = received;
+                    match Route::try_from(&route) {
+                        Ok(route) if self.filter.matches(&route) => routes.push(route),
+                        _ => {}
+                    }
+                    false
+                }
+                NetlinkPayload::Done(_) => true,
+                // dumping a table that doesn't exist fails instead of returning nothing
+                NetlinkPayload::Error(e)
+                    if self.filter.table.is_some() && e.raw_code() == -libc::ENOENT =>
+                {
+                    true
+                }
+                NetlinkPayload::Error(e) if e.code.is_some() => {
+                    return Err(ext_ack_error(&e, msg.header.flags))
                 }
+                _ => false,
+            };
+            if end {
+                if std::mem::take(&mut self.interrupted) {
+                    self.failed = Some(crate::Error::DumpInterrupted.into());
+                }
+                self.next_family();
+            }
+            if routes.len() >= self.chunk_size || (end && !routes.is_empty()) {
+                return Ok(Some(RouteChunk {
+                    routes,
+                    cursor: self.cursor,
+                }));
+            }
+            if let Some(e) = self.failed.take() {
+                return Err(e);
+            }
+        }
+    }
 
-                if let Some(src) = route.source {
-                    msg = match src {
-                        IpAddr::V6(addr) => msg.source_prefix(addr, route.source_prefix),
-                        IpAddr::V4(_) => {
-                            return Err(Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "source version must match destination",
-                            ))
-                        }
-                    };
+    // Send the dump request of `family`, opening the socket first.
+    async fn start(&mut self, family: AddressFamily) -> io::Result<u64> {
+        if self.socket.is_none() {
+            // filtered by the kernel where it can if strict
+            self.socket = Some(open_dump_socket(self.netns.as_deref(), self.strict)?);
+        }
+        let request = filtered_route_dump(family, &self.filter);
+        let mut request = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(request));
+        request.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+        request.header.sequence_number = 1;
+        let socket = self.socket.as_mut().expect("the socket was opened");
+        socket.send(request).await?;
+        self.received = Some(0);
+        Ok(0)
+    }
+
+    fn next_family(&mut self) {
+        self.cursor = match self.cursor.family as usize + 1 {
+            family if family < CHUNKED_FAMILIES.len() => ListCursor {
+                family: family as u8,
+                position: 0,
+            },
+            _ => ListCursor::END,
+        };
+        self.received = None;
+    }
+}
+
+// Which socket a dump runs on. The kernel runs one dump per socket at a time, dumps running at the
+// same time as others, e.g. one per family, need a socket of their own.
+#[derive(Clone, Copy)]
+enum DumpOn {
+    OwnSocket,
+    // taking turns with the other dumps of the shared socket
+    SharedSocket,
+}
+
+// Open a socket for a dump of its own, in the namespace at `netns` if there is one, with the
+// reason of failed requests and strict checking if `strict`.
+fn open_dump_socket(netns: Option<&Path>, strict: bool) -> io::Result<DumpSocket> {
+    let socket = match netns {
+        Some(path) => in_netns(path, DumpSocket::open)??,
+        None => DumpSocket::open()?,
+    };
+    for (option, value) in [
+        (libc::NETLINK_GET_STRICT_CHK, strict),
+        (libc::NETLINK_EXT_ACK, true),
+    ] {
+        match set_socket_option(socket.as_raw_fd(), libc::SOL_NETLINK, option, value as _) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOPROTOOPT) => {}
+            res => res?,
+        }
+    }
+    Ok(socket)
+}
+
+// The messages answering a dump request as they arrive, up to the last one.
+type Answers = BoxStream<'static, io::Result<NetlinkMessage<RouteNetlinkMessage>>>;
+
+// Send a dump request on the shared socket, returning the messages of the answer as they arrive.
+fn dump_request(handle: &Requests, message: RouteNetlinkMessage) -> io::Result<Dump> {
+    let mut req = NetlinkMessage::from(message);
+    req.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+    let responses: Responses = handle.request(req)?;
+    Ok(Dump {
+        responses: responses.map(Ok).boxed(),
+        interrupted: false,
+    })
+}
+
+// The messages answering a dump request. The kernel marks the messages of a dump during which
+// the table changed, or only the done message when it changed after the last route. Such a dump
+// fails with `Error::DumpInterrupted` once it was read to the end, so the socket is ready for
+// the next dump.
+struct Dump<S = Answers> {
+    responses: S,
+    interrupted: bool,
+}
+
+impl<S> Stream for Dump<S>
+where
+    S: Stream<Item = io::Result<NetlinkMessage<RouteNetlinkMessage>>> + Unpin,
+{
+    type Item = io::Result<RouteNetlinkMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let msg = match futures::ready!(self.responses.poll_next_unpin(cx)) {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    let interrupted = std::mem::take(&mut self.interrupted);
+                    let interrupted =
+                        interrupted.then(|| Err(crate::Error::DumpInterrupted.into()));
+                    return Poll::Ready(interrupted);
                 }
-                msg.execute()
-                    .await
-                    .map_err(|e| Error::new(io::ErrorKind::Other, e.to_string()))
+            };
+            self.interrupted |= msg.header.flags & NLM_F_DUMP_INTR != 0;
+            match msg.payload {
+                NetlinkPayload::InnerMessage(inner) => return Poll::Ready(Some(Ok(inner))),
+                NetlinkPayload::Error(e) if e.code.is_some() => {
+                    return Poll::Ready(Some(Err(ext_ack_error(&e, msg.header.flags))))
+                }
+                _ => {}
             }
         }
     }
+}
 
-    async fn listen(
-        mut messages: UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
-        tx: broadcast::Sender<RouteChange>,
-    ) {
-        while let Some((message, _)) = messages.next().await {
-            if let NetlinkPayload::InnerMessage(msg) = message.payload {
-                match msg {
-                    RouteNetlinkMessage::NewRoute(msg) => _ = tx.send(RouteChange::Add(msg.into())),
-                    RouteNetlinkMessage::DelRoute(msg) => {
-                        _ = tx.send(RouteChange::Delete(msg.into()))
+// Run `dump` again while it fails because the table changed during it, at most `DUMP_RETRIES`
+// times.
+async fn retry_interrupted<T, F>(mut dump: impl FnMut() -> F) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    let mut retries = 0;
+    loop {
+        match dump().await {
+            Err(e) if is_dump_interrupted(&e) && retries < DUMP_RETRIES => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("the routing table changed during a dump, dumping again");
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Read the rest of a dump that was stopped early, the socket can't start another one before.
+async fn finish(mut messages: impl Stream + Unpin) {
+    while messages.next().await.is_some() {}
+}
+
+// Dump every route of both families, falling back to procfs where dumps aren't allowed.
+async fn dump_all(handle: &Requests) -> io::Result<Vec<Route>> {
+    let mut routes = vec![];
+    for family in [AddressFamily::Inet, AddressFamily::Inet6] {
+        let mut messages = dump_request(handle, RouteNetlinkMessage::GetRoute(route_dump(family)))?;
+        loop {
+            match messages.try_next().await {
+                Ok(Some(RouteNetlinkMessage::NewRoute(msg))) => {
+                    routes.extend(Route::try_from(&msg).ok())
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::PermissionDenied {
+                        return procfs::list_routes();
                     }
-                    _ => (),
+                    return Err(e);
                 }
             }
         }
     }
+    Ok(routes)
 }
 
-impl Drop for Handle {
-    fn drop(&mut self) {
-        self.join_handle.abort();
-        self.listen_handle.abort();
+// Run `f` with the calling thread switched to the network namespace at `path`, sockets keep the
+// namespace they were created in.
+fn in_netns<T>(path: &Path, f: impl FnOnce() -> T) -> io::Result<T> {
+    let current = File::open("/proc/thread-self/ns/net")?;
+    let target = File::open(path)?;
+    if unsafe { libc::setns(target.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let res = f();
+    if unsafe { libc::setns(current.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+        // the thread can't be left in the other namespace
+        panic!(
+            "failed to restore the network namespace: {}",
+            io::Error::last_os_error()
+        );
+    }
+    Ok(res)
+}
+
+fn socket_option(fd: RawFd, level: libc::c_int, name: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            level,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+// The FORCE variants ignore the rmem_max/wmem_max limits but need CAP_NET_ADMIN
+fn set_buffer_size(
+    fd: RawFd,
+    force_name: libc::c_int,
+    name: libc::c_int,
+    size: usize,
+) -> io::Result<()> {
+    let size = libc::c_int::try_from(size)
+        .map_err(|_| Error::new(io::ErrorKind::InvalidInput, "buffer size too large"))?;
+    match set_socket_option(fd, libc::SOL_SOCKET, force_name, size) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            set_socket_option(fd, libc::SOL_SOCKET, name, size)
+        }
+        res => res,
+    }
+}
+
+const NLM_F_CAPPED: u16 = 0x100;
+const NLM_F_ACK_TLVS: u16 = 0x200;
+const NLMSGERR_ATTR_MSG: u16 = 1;
+const NLMSG_HDRLEN: usize = 16;
+
+/// An errno the kernel answered a request with, together with the extended error message if it
+/// attached one. Kept apart from errors of system calls so it can become `Error::Netlink`.
+#[derive(Debug)]
+struct NetlinkError {
+    source: io::Error,
+    message: Option<String>,
+}
+
+impl std::fmt::Display for NetlinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}: {}", self.source, message),
+            None => self.source.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for NetlinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Convert an error message into an `io::Error`, including the extended ack message if the
+/// kernel sent one.
+fn ext_ack_error(e: &netlink_packet_core::ErrorMessage, flags: u16) -> io::Error {
+    let source = e.to_io();
+    let message = ext_ack_message(&e.header, flags);
+    Error::new(source.kind(), NetlinkError { source, message })
+}
+
+/// The errno of an error returned by a request.
+fn errno(e: &io::Error) -> Option<i32> {
+    e.raw_os_error().or_else(|| netlink_errno(e))
+}
+
+/// The errno of an error the kernel answered a netlink request with, `None` for other errors.
+pub(crate) fn netlink_errno(e: &io::Error) -> Option<i32> {
+    e.get_ref()?
+        .downcast_ref::<NetlinkError>()?
+        .source
+        .raw_os_error()
+}
+
+/// Find the `NLMSGERR_ATTR_MSG` attribute in the payload of an error message, which is the
+/// offending request (or only its header if capped) followed by the extended ack attributes.
+fn ext_ack_message(payload: &[u8], flags: u16) -> Option<String> {
+    if flags & NLM_F_ACK_TLVS == 0 || payload.len() < NLMSG_HDRLEN {
+        return None;
     }
+    let request_len = if flags & NLM_F_CAPPED != 0 {
+        NLMSG_HDRLEN
+    } else {
+        u32::from_ne_bytes(payload[..4].try_into().ok()?) as usize
+    };
+
+    let mut attrs = payload.get(request_len..)?;
+    while attrs.len() >= 4 {
+        let len = u16::from_ne_bytes([attrs[0], attrs[1]]) as usize;
+        let kind = u16::from_ne_bytes([attrs[2], attrs[3]]);
+        if len < 4 || len > attrs.len() {
+            break;
+        }
+        if kind == NLMSGERR_ATTR_MSG {
+            let value = &attrs[4..len];
+            let value = value.split(|b| *b == 0).next().unwrap_or(value);
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+        // attributes are padded to 4 bytes
+        attrs = attrs.get((len + 3) & !3..).unwrap_or_default();
+    }
+    None
 }
 
-fn addr_to_ip(addr: RouteAddress) -> Option<IpAddr> {
-    match addr {
+fn set_socket_option(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Build a request deleting `route` directly, the kernel removes the first route in
+/// `route.table` matching every field that is set.
+fn delete_message(route: &Route) -> io::Result<RouteMessage> {
+    let mut msg = RouteMessage::default();
+    msg.header.address_family = match route.destination {
+        IpAddr::V4(_) => AddressFamily::Inet,
+        IpAddr::V6(_) => AddressFamily::Inet6,
+    };
+    msg.header.destination_prefix_length = route.prefix;
+    // same as `ip route del`: match routes of any scope, protocol and type
+    msg.header.scope = RouteScope::NoWhere.as_raw().into();
+    msg.header.protocol = RouteProtocol::Unspec.as_raw().into();
+    msg.header.kind = RouteType::Unspec;
+    msg.header.table = u8::try_from(route.table).unwrap_or(RT_TABLE_UNSPEC);
+    msg.attributes.push(RouteAttribute::Table(route.table));
+
+    if route.prefix > 0 {
+        msg.attributes
+            .push(RouteAttribute::Destination(ip_to_addr(route.destination)));
+    }
+    if let Some(gateway) = route.gateway {
+        if gateway.is_ipv4() != route.destination.is_ipv4() {
+            return Err(invalid_route("gateway version must match destination"));
+        }
+        msg.attributes
+            .push(RouteAttribute::Gateway(ip_to_addr(gateway)));
+    }
+    if let Some(ifindex) = route.ifindex {
+        msg.attributes.push(RouteAttribute::Oif(ifindex));
+    }
+    if let Some(metric) = route.metric {
+        msg.attributes.push(RouteAttribute::Priority(metric));
+    }
+    Ok(msg)
+}
+
+fn route_type(kind: RouteKind) -> io::Result<RouteType> {
+    kind.as_raw()
+        .map(RouteType::from)
+        .ok_or_else(|| invalid_route("routes of other kinds can't be added"))
+}
+
+fn ip_to_addr(ip: IpAddr) -> RouteAddress {
+    match ip {
+        IpAddr::V4(addr) => RouteAddress::Inet(addr),
+        IpAddr::V6(addr) => RouteAddress::Inet6(addr),
+    }
+}
+
+fn max_prefix(ip: IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn addr_to_ip(addr: &RouteAddress) -> Option<IpAddr> {
+    match *addr {
         RouteAddress::Inet(addr) => Some(addr.into()),
         RouteAddress::Inet6(addr) => Some(addr.into()),
         _ => None,
     }
 }
 
-impl From<RouteMessage> for Route {
-    fn from(msg: RouteMessage) -> Self {
+fn link_to_vrf(link: LinkMessage) -> Option<Vrf> {
+    let mut name = None;
+    let mut table = None;
+
+    for attr in link.attributes {
+        match attr {
+            LinkAttribute::IfName(ifname) => name = Some(ifname),
+            LinkAttribute::LinkInfo(infos) => {
+                for info in infos {
+                    if let LinkInfo::Data(InfoData::Vrf(data)) = info {
+                        for vrf_attr in data {
+                            if let InfoVrf::TableId(id) = vrf_attr {
+                                table = Some(id);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(Vrf {
+        name: name?,
+        ifindex: link.header.index,
+        table: table?,
+    })
+}
+
+impl TryFrom<RouteMessage> for Route {
+    type Error = io::Error;
+
+    /// Fails for routes of address families other than ipv4 and v6, e.g. MPLS routes.
+    fn try_from(msg: RouteMessage) -> io::Result<Self> {
+        Self::try_from(&msg)
+    }
+}
+
+// Parses the attributes in place, so only the next hops of multipath routes are allocated. Dumps
+// of full BGP tables spend most of their time here.
+impl TryFrom<&RouteMessage> for Route {
+    type Error = io::Error;
+
+    /// Fails for routes of address families other than ipv4 and v6, e.g. MPLS routes.
+    fn try_from(msg: &RouteMessage) -> io::Result<Self> {
         let mut gateway = None;
         let mut source = None;
         let mut source_hint = None;
         let mut destination = None;
         let mut ifindex = None;
         let mut metric = None;
+        let mut nexthop_id = None;
+        let mut nexthops = vec![];
         let mut table = msg.header.table as u32;
 
-        for attr in msg.attributes {
+        for attr in &msg.attributes {
             match attr {
                 RouteAttribute::Source(addr) => {
                     source = addr_to_ip(addr);
@@ -490,24 +1651,38 @@ impl From<RouteMessage> for Route {
                     gateway = addr_to_ip(addr);
                 }
                 RouteAttribute::Oif(i) => {
-                    ifindex = Some(i);
+                    ifindex = Some(*i);
                 }
                 RouteAttribute::Priority(priority) => {
-                    metric = Some(priority);
+                    metric = Some(*priority);
                 }
                 RouteAttribute::Table(real_table) => {
-                    table = real_table;
+                    table = *real_table;
+                }
+                RouteAttribute::MultiPath(hops) => {
+                    nexthops = hops.iter().map(NextHop::from).collect();
+                }
+                RouteAttribute::Other(nla) if nla.kind() == RTA_NH_ID && nla.value_len() == 4 => {
+                    let mut id = [0u8; 4];
+                    nla.emit_value(&mut id);
+                    nexthop_id = Some(u32::from_ne_bytes(id));
                 }
                 _ => {}
             }
         }
         // rtnetlink gives None instead of 0.0.0.0 for the default route, but we'll convert to 0 here to make it match the other platforms
-        let destination = destination.unwrap_or_else(|| match msg.header.address_family {
-            AddressFamily::Inet => Ipv4Addr::UNSPECIFIED.into(),
-            AddressFamily::Inet6 => Ipv6Addr::UNSPECIFIED.into(),
-            _ => panic!("invalid destination family"),
-        });
-        Self {
+        let destination = match (destination, msg.header.address_family) {
+            (Some(destination), _) => destination,
+            (None, AddressFamily::Inet) => Ipv4Addr::UNSPECIFIED.into(),
+            (None, AddressFamily::Inet6) => Ipv6Addr::UNSPECIFIED.into(),
+            (None, family) => {
+                return Err(Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported address family {:?}", family),
+                ))
+            }
+        };
+        Ok(Self {
             destination,
             prefix: msg.header.destination_prefix_length,
             source,
@@ -516,8 +1691,68 @@ impl From<RouteMessage> for Route {
             gateway,
             ifindex,
             table,
+            protocol: RouteProtocol::from_raw(msg.header.protocol.into()),
+            nexthops,
+            nexthop_id,
+            scope: RouteScope::from_raw(msg.header.scope.into()),
+            kind: RouteKind::from_raw(msg.header.kind.into()),
             metric,
+        })
+    }
+}
+
+impl From<RouteNextHop> for NextHop {
+    fn from(hop: RouteNextHop) -> Self {
+        Self::from(&hop)
+    }
+}
+
+impl From<&RouteNextHop> for NextHop {
+    fn from(hop: &RouteNextHop) -> Self {
+        let gateway = hop.attributes.iter().find_map(|attr| match attr {
+            RouteAttribute::Gateway(addr) => addr_to_ip(addr),
+            _ => None,
+        });
+        Self {
+            gateway,
+            ifindex: hop.interface_index,
+            // the kernel stores the weight minus one
+            weight: hop.hops as u16 + 1,
+        }
+    }
+}
+
+#[cfg(feature = "rules")]
+impl From<RuleMessage> for Rule {
+    fn from(msg: RuleMessage) -> Self {
+        let mut rule = Rule {
+            v6: msg.header.family == AddressFamily::Inet6,
+            // tables above 255 are only carried by the attribute
+            table_id: Some(msg.header.table as u32).filter(|table| *table != 0),
+            ..Default::default()
+        };
+        let mut fw_mark = None;
+        let mut fw_mask = None;
+        for attr in msg.attributes {
+            match attr {
+                RuleAttribute::Source(addr) => rule.src = Some((addr, msg.header.src_len)),
+                RuleAttribute::Destination(addr) => rule.dst = Some((addr, msg.header.dst_len)),
+                RuleAttribute::Iifname(name) => rule.input_interface = Some(name),
+                RuleAttribute::Oifname(name) => rule.output_interface = Some(name),
+                RuleAttribute::Table(table) => rule.table_id = Some(table),
+                RuleAttribute::Priority(priority) => rule.priority = Some(priority),
+                RuleAttribute::FwMark(mark) => fw_mark = Some(mark),
+                RuleAttribute::FwMask(mask) => fw_mask = Some(mask),
+                RuleAttribute::SuppressPrefixLen(len) => rule.suppress_prefixlength = Some(len),
+                RuleAttribute::IpProtocol(protocol) => {
+                    rule.protocol = Some(IpProtocol::from_raw(i32::from(protocol) as u8))
+                }
+                _ => {}
+            }
         }
+        // a mark without a mask is compared in full
+        rule.fw_mark_mask = fw_mark.map(|mark| (mark, fw_mask.unwrap_or(u32::MAX)));
+        rule
     }
 }
 
@@ -531,8 +1766,7 @@ impl RouteExt for RouteMessage {
             .iter()
             .flat_map(|attr| {
                 if let RouteAttribute::Destination(addr) = attr {
-                    addr_to_ip(addr.clone())
-                        .map(|addr| (addr, self.header.destination_prefix_length))
+                    addr_to_ip(addr).map(|addr| (addr, self.header.destination_prefix_length))
                 } else {
                     None
                 }
@@ -556,25 +1790,175 @@ impl RouteExt for RouteMessage {
 
 #[cfg(test)]
 mod tests {
-    use netlink_packet_route::IpProtocol;
+    use netlink_packet_core::{DoneMessage, NetlinkHeader, NLM_F_MULTIPART};
 
     use super::*;
+    #[cfg(feature = "rules")]
     use crate::Rule;
 
+    #[test]
+    fn it_fails_interrupted_dumps_at_the_end() {
+        let route = |flags| {
+            let mut msg =
+                NetlinkMessage::from(RouteNetlinkMessage::NewRoute(RouteMessage::default()));
+            msg.header.flags = flags;
+            msg
+        };
+        let responses = futures::stream::iter([route(0), route(NLM_F_DUMP_INTR), route(0)].map(Ok));
+        let results: Vec<_> = futures::executor::block_on(
+            Dump {
+                responses,
+                interrupted: false,
+            }
+            .collect(),
+        );
+
+        // every route is still handed out, so the dump is read to the end
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|result| result.is_ok()));
+        let e = results.into_iter().last().unwrap().unwrap_err();
+        assert!(is_dump_interrupted(&e));
+    }
+
+    #[test]
+    fn it_fails_dumps_interrupted_after_the_last_route() {
+        let mut route =
+            NetlinkMessage::from(RouteNetlinkMessage::NewRoute(RouteMessage::default()));
+        route.header.flags = NLM_F_MULTIPART;
+        // the table changed after the last batch of routes, only the done message tells
+        let mut done = NetlinkMessage::new(
+            NetlinkHeader::default(),
+            NetlinkPayload::Done(DoneMessage::default()),
+        );
+        done.header.flags = NLM_F_MULTIPART | NLM_F_DUMP_INTR;
+        let responses = futures::stream::iter([route, done].map(Ok));
+        let results: Vec<_> = futures::executor::block_on(
+            Dump {
+                responses,
+                interrupted: false,
+            }
+            .collect(),
+        );
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(is_dump_interrupted(results[1].as_ref().unwrap_err()));
+    }
+
+    #[test]
+    fn it_preserves_netlink_errno() {
+        let mut msg = netlink_packet_core::ErrorMessage::default();
+        msg.code = std::num::NonZeroI32::new(-libc::EEXIST);
+
+        let err = ext_ack_error(&msg, 0);
+        assert_eq!(netlink_errno(&err), Some(libc::EEXIST));
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn it_parses_ext_ack_messages() {
+        let mut payload = vec![0u8; NLMSG_HDRLEN];
+        payload[..4].copy_from_slice(&(NLMSG_HDRLEN as u32).to_ne_bytes());
+        let text = b"Nexthop has invalid gateway\0";
+        payload.extend_from_slice(&((4 + text.len()) as u16).to_ne_bytes());
+        payload.extend_from_slice(&NLMSGERR_ATTR_MSG.to_ne_bytes());
+        payload.extend_from_slice(text);
+
+        assert_eq!(
+            ext_ack_message(&payload, NLM_F_ACK_TLVS).as_deref(),
+            Some("Nexthop has invalid gateway")
+        );
+        assert_eq!(
+            ext_ack_message(&payload, NLM_F_ACK_TLVS | NLM_F_CAPPED).as_deref(),
+            Some("Nexthop has invalid gateway")
+        );
+        assert_eq!(ext_ack_message(&payload, 0), None);
+    }
+
+    #[test]
+    fn it_rejects_non_ip_routes() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Mpls;
+        assert!(Route::try_from(msg).is_err());
+
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        let route = Route::try_from(msg).unwrap();
+        assert_eq!(route.destination, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn it_converts_route_messages_by_reference() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.header.destination_prefix_length = 24;
+        let mut hop = RouteNextHop::default();
+        hop.interface_index = 3;
+        hop.hops = 1;
+        hop.attributes = vec![RouteAttribute::Gateway(RouteAddress::Inet(Ipv4Addr::new(
+            10, 0, 0, 1,
+        )))];
+        msg.attributes = vec![
+            RouteAttribute::Destination(RouteAddress::Inet(Ipv4Addr::new(192, 168, 1, 0))),
+            RouteAttribute::Oif(2),
+            RouteAttribute::Priority(100),
+            RouteAttribute::Table(1000),
+            RouteAttribute::MultiPath(vec![hop]),
+        ];
+
+        let route = Route::try_from(&msg).unwrap();
+        assert_eq!(route.destination, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 0)));
+        assert_eq!(route.prefix, 24);
+        assert_eq!(route.ifindex, Some(2));
+        assert_eq!(route.metric, Some(100));
+        assert_eq!(route.table, 1000);
+        assert_eq!(route.nexthops.len(), 1);
+        assert_eq!(
+            route.nexthops[0].gateway,
+            Some(Ipv4Addr::new(10, 0, 0, 1).into())
+        );
+        assert_eq!(route.nexthops[0].weight, 2);
+        assert_eq!(Route::try_from(msg).unwrap(), route);
+    }
+
+    #[cfg(feature = "rules")]
+    #[test]
+    fn it_converts_rule_messages() {
+        let mut msg = RuleMessage::default();
+        msg.header.family = AddressFamily::Inet;
+        msg.header.dst_len = 32;
+        msg.header.table = 254;
+        msg.attributes = vec![
+            RuleAttribute::Destination(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8))),
+            RuleAttribute::Table(2001),
+            RuleAttribute::FwMark(7),
+            RuleAttribute::IpProtocol(netlink_packet_route::IpProtocol::Icmp),
+        ];
+
+        let rule = Rule::from(msg);
+        assert_eq!(rule.dst, Some((IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 32)));
+        assert_eq!(rule.table_id, Some(2001));
+        assert_eq!(rule.fw_mark_mask, Some((7, u32::MAX)));
+        assert_eq!(rule.protocol, Some(IpProtocol::Icmp));
+        assert!(!rule.v6);
+    }
+
+    #[cfg(feature = "rules")]
     #[tokio::test]
     async fn test_rule_list() {
         // list all rules on linux
-        let handle = Handle::new().unwrap();
+        let handle = Handle::new(&HandleBuilder::new()).unwrap();
         let res = handle.list_rules().await.unwrap();
         for rule in res {
             println!("{:?}", rule);
         }
     }
 
+    #[cfg(feature = "rules")]
     #[tokio::test]
     async fn test_rule_add() {
         // list all rules on linux
-        let handle = Handle::new().unwrap();
+        let handle = Handle::new(&HandleBuilder::new()).unwrap();
         let mut rule = Rule::default();
         rule.dst = Some(("8.8.8.8".parse().unwrap(), 32));
         rule.table_id = Some(2001);
@@ -582,10 +1966,11 @@ mod tests {
         let _ = handle.add_rules(vec![rule]).await.unwrap();
     }
 
+    #[cfg(feature = "rules")]
     #[tokio::test]
     async fn test_rule_del() {
         // list all rules on linux
-        let handle = Handle::new().unwrap();
+        let handle = Handle::new(&HandleBuilder::new()).unwrap();
         let mut rule = Rule::default();
         rule.dst = Some(("8.8.8.8".parse().unwrap(), 32));
         rule.table_id = Some(2001);
@@ -593,10 +1978,11 @@ mod tests {
         let _ = handle.delete_rules(vec![rule]).await.unwrap();
     }
 
+    #[cfg(feature = "rules")]
     #[tokio::test]
     async fn test_rule_add_icmp() {
         // list all rules on linux
-        let handle = Handle::new().unwrap();
+        let handle = Handle::new(&HandleBuilder::new()).unwrap();
         let mut rule = Rule::default();
         rule.dst = Some(("8.8.8.8".parse().unwrap(), 32));
         rule.table_id = Some(2001);
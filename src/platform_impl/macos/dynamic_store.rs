@@ -0,0 +1,35 @@
+// configd orders the network services and publishes the interface and router of the first one
+// that is usable under State:/Network/Global/IPv4 (and IPv6). This is the interface that
+// SystemConfiguration and the system UI report as primary, which isn't necessarily the one with
+// the lowest metric as routes on macOS don't carry one.
+
+use std::net::IpAddr;
+
+use core_foundation::{
+    base::{CFType, TCFType},
+    dictionary::CFDictionary,
+    string::CFString,
+};
+use system_configuration::dynamic_store::SCDynamicStoreBuilder;
+
+use crate::IpVersion;
+
+/// The name and router of the primary interface for `version`, `None` if there is no
+/// usable network service.
+pub(crate) fn primary_interface(version: IpVersion) -> Option<(String, Option<IpAddr>)> {
+    let key = match version {
+        IpVersion::V4 => "State:/Network/Global/IPv4",
+        IpVersion::V6 => "State:/Network/Global/IPv6",
+    };
+    let store = SCDynamicStoreBuilder::new("net-route").build();
+    let global = store.get(key)?.downcast_into::<CFDictionary>()?;
+    let string = |name: &'static str| {
+        let value = global.find(CFString::from_static_string(name).as_CFTypeRef())?;
+        let value = unsafe { CFType::wrap_under_get_rule(*value) };
+        Some(value.downcast::<CFString>()?.to_string())
+    };
+
+    let name = string("PrimaryInterface")?;
+    let router = string("Router").and_then(|router| router.parse().ok());
+    Some((name, router))
+}
@@ -4,19 +4,26 @@ use std::{
     mem,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     os::unix::prelude::FromRawFd,
+    sync::atomic::{AtomicI32, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use crate::error::invalid_route;
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
 use async_stream::stream;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixStream,
-    sync::broadcast,
-    task::JoinHandle,
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::platform_impl::macos::{bind::*, dynamic_store};
+use crate::platform_impl::runtime::{self, AsyncReadExt, AsyncWriteExt, Task, UnixStream};
+use crate::{
+    Capabilities, DumpFilter, HandleBuilder, IpVersion, PrimaryInterface, Route, RouteChange,
+    RouteFlags, RouteKind, RouteMetrics,
 };
 
-use crate::platform_impl::macos::bind::*;
-use crate::{Route, RouteChange};
-
 // see https://opensource.apple.com/source/network_cmds/network_cmds-606.40.2/netstat.tproj/route.c.auto.html
 // for example C code of how the MacOS route API works.
 
@@ -30,29 +37,72 @@ pub fn ifname_to_index(name: &str) -> Option<u32> {
     }
 }
 
+fn index_to_ifname(ifindex: u32) -> Option<String> {
+    let mut name = [0 as std::os::raw::c_char; IF_NAMESIZE as usize];
+    let ret = unsafe { if_indextoname(ifindex, name.as_mut_ptr()) };
+    if ret.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Fill in the ifindex of a route that only names its interface.
+fn resolve_ifname(route: &Route) -> io::Result<Route> {
+    let mut route = route.clone();
+    if let (None, Some(name)) = (route.ifindex, &route.ifname) {
+        let ifindex = ifname_to_index(name).ok_or_else(|| {
+            io::Error::new(ErrorKind::NotFound, format!("no interface named {name}"))
+        })?;
+        route.ifindex = Some(ifindex);
+    }
+    Ok(route)
+}
+
 pub(crate) struct Handle {
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
     tx: broadcast::Sender<RouteChange>,
-    listen_handle: JoinHandle<()>,
+    listen_handle: Option<Task>,
 }
 
 impl Handle {
-    pub(crate) fn new() -> io::Result<Self> {
+    pub(crate) fn new(config: &HandleBuilder) -> io::Result<Self> {
         // TODO wait until user registers a listener to open the socket
-        let (tx, _) = broadcast::channel::<RouteChange>(16);
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
 
-        let fd = unsafe { socket(PF_ROUTE as i32, SOCK_RAW as i32, AF_UNSPEC as i32) };
-        if fd < 0 {
-            return Err(io::Error::last_os_error());
-        }
-        let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
-        route_fd.set_nonblocking(true)?;
-        let tokio_fd: UnixStream = route_fd.try_into()?;
+        let listen_handle = if config.listen {
+            let fd = unsafe { socket(PF_ROUTE as i32, SOCK_RAW as i32, AF_UNSPEC as i32) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+            let sock = runtime::async_socket(route_fd)?;
 
-        let listen_handle = tokio::spawn(Self::listen(tx.clone(), tokio_fd));
+            Some(Task::spawn(Self::listen(tx.clone(), sock)))
+        } else {
+            None
+        };
 
         Ok(Self { tx, listen_handle })
     }
 
+    // The listener only reads from the routing socket, it's stopped right away and joined so the
+    // socket is closed once this returns.
+    pub(crate) async fn close(mut self) -> io::Result<()> {
+        let Some(listen_handle) = &mut self.listen_handle else {
+            return Ok(());
+        };
+        listen_handle.abort();
+        listen_handle.join().await
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        Ok(Capabilities {
+            modify_routes: unsafe { geteuid() } == 0,
+            modify_rules: false,
+        })
+    }
+
     pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
         for route in self.list().await? {
             if (route.destination == Ipv4Addr::UNSPECIFIED
@@ -67,15 +117,27 @@ impl Handle {
         Ok(None)
     }
 
-    pub(crate) fn route_listen_stream(&self) -> impl futures::Stream<Item = RouteChange> {
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
         let mut rx = self.tx.subscribe();
         stream! {
             loop {
                 match rx.recv().await {
-                    Ok(ev) => yield ev,
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
                     Err(e) => match e {
                         broadcast::error::RecvError::Closed => break,
-                        broadcast::error::RecvError::Lagged(_) => continue,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
                     }
                 }
             }
@@ -83,22 +145,69 @@ impl Handle {
     }
 
     pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
-        add_or_del_route(route.destination, route.mask(), None, None, false).await
+        add_or_del_route(&resolve_ifname(route)?, false).await
     }
 
     pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
-        add_or_del_route(
-            route.destination,
-            route.mask(),
-            route.gateway,
-            route.ifindex,
-            true,
-        )
-        .await
+        add_or_del_route(&resolve_ifname(route)?, true).await
     }
 
     pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
-        list_routes().await
+        list_routes(0).await
+    }
+
+    pub(crate) async fn get_route(&self, destination: IpAddr) -> io::Result<Route> {
+        get_route(destination, None).await
+    }
+
+    pub(crate) async fn default_route_for_interface(
+        &self,
+        ifindex: u32,
+    ) -> io::Result<Option<Route>> {
+        for destination in [
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        ] {
+            match get_route(destination, Some(ifindex)).await {
+                // a scoped lookup can fall back to a route of another interface
+                Ok(route) if route.prefix == 0 && route.ifindex == Some(ifindex) => {
+                    return Ok(Some(route))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) async fn primary_interface(
+        &self,
+        version: IpVersion,
+    ) -> io::Result<Option<PrimaryInterface>> {
+        let Some((name, router)) = dynamic_store::primary_interface(version) else {
+            return Ok(None);
+        };
+        // the service can outlive its interface for a moment while it is torn down
+        let Some(ifindex) = ifname_to_index(&name) else {
+            return Ok(None);
+        };
+        Ok(Some(PrimaryInterface {
+            name,
+            ifindex,
+            router,
+        }))
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let family = match filter.family {
+            Some(IpVersion::V4) => AF_INET,
+            Some(IpVersion::V6) => AF_INET6,
+            None => 0,
+        };
+        let mut routes = list_routes(family).await?;
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
     }
 
     async fn listen(tx: broadcast::Sender<RouteChange>, mut sock: UnixStream) {
@@ -117,12 +226,15 @@ impl Handle {
             let route = message_to_route(hdr, &buf[HDR_SIZE..read]);
 
             if let Some(route) = route {
-                _ = tx.send(match hdr.rtm_type as u32 {
+                let change = match hdr.rtm_type as u32 {
                     RTM_ADD => RouteChange::Add(route),
                     RTM_DELETE => RouteChange::Delete(route),
                     RTM_CHANGE => RouteChange::Change(route),
                     _ => continue,
-                });
+                };
+                #[cfg(feature = "tracing")]
+                tracing::trace!(?change, "route changed");
+                _ = tx.send(change);
             }
         }
     }
@@ -130,7 +242,9 @@ impl Handle {
 
 impl Drop for Handle {
     fn drop(&mut self) {
-        self.listen_handle.abort();
+        if let Some(listen_handle) = &self.listen_handle {
+            listen_handle.abort();
+        }
     }
 }
 
@@ -228,12 +342,20 @@ fn message_to_route(hdr: &rt_msghdr, msg: &[u8]) -> Option<Route> {
         }
     }
 
-    Some(Route {
-        destination,
-        prefix,
-        gateway,
-        ifindex: Some(hdr.rtm_index as u32),
-    })
+    let mut route = Route::new(destination, prefix).with_ifindex(hdr.rtm_index as u32);
+    route.gateway = gateway;
+    route.ifname = index_to_ifname(hdr.rtm_index as u32);
+    route.ifscope = hdr.rtm_flags as u32 & RTF_IFSCOPE != 0;
+    route.flags = RouteFlags::from_bits(hdr.rtm_flags as u32);
+    route.metrics = metrics_from_rt(&hdr.rtm_rmx);
+    route.kind = if route.flags.contains(RouteFlags::BLACKHOLE) {
+        RouteKind::Blackhole
+    } else if route.flags.contains(RouteFlags::REJECT) {
+        RouteKind::Unreachable
+    } else {
+        RouteKind::Unicast
+    };
+    Some(route)
 }
 
 #[repr(C)]
@@ -259,6 +381,52 @@ impl Default for sockaddr_dl {
     }
 }
 
+// rmx_rtt is in units of 1 / RTM_RTTUNIT seconds
+const RTM_RTTUNIT: u64 = 1_000_000;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+fn metrics_from_rt(rmx: &rt_metrics) -> RouteMetrics {
+    let non_zero = |value: u32| (value != 0).then_some(value);
+    RouteMetrics {
+        mtu: non_zero(rmx.rmx_mtu),
+        hopcount: non_zero(rmx.rmx_hopcount),
+        // rmx_expire is the absolute time the route expires at
+        expire: (rmx.rmx_expire > 0)
+            .then(|| Duration::from_secs((rmx.rmx_expire as u64).saturating_sub(now_secs()))),
+        rtt: non_zero(rmx.rmx_rtt)
+            .map(|rtt| Duration::from_micros(rtt as u64 * 1_000_000 / RTM_RTTUNIT)),
+    }
+}
+
+// the metrics and the RTV_* bits of the ones that are set, for rtm_rmx and rtm_inits
+fn metrics_to_rt(metrics: &RouteMetrics) -> (rt_metrics, u32) {
+    let mut rmx = rt_metrics::default();
+    let mut inits = 0;
+    if let Some(mtu) = metrics.mtu {
+        rmx.rmx_mtu = mtu;
+        inits |= RTV_MTU;
+    }
+    if let Some(hopcount) = metrics.hopcount {
+        rmx.rmx_hopcount = hopcount;
+        inits |= RTV_HOPCOUNT;
+    }
+    if let Some(expire) = metrics.expire {
+        rmx.rmx_expire = (now_secs() + expire.as_secs()).min(i32::MAX as u64) as i32;
+        inits |= RTV_EXPIRE;
+    }
+    if let Some(rtt) = metrics.rtt {
+        rmx.rmx_rtt =
+            (rtt.as_micros() as u64 * RTM_RTTUNIT / 1_000_000).min(u32::MAX as u64) as u32;
+        inits |= RTV_RTT;
+    }
+    (rmx, inits)
+}
+
 impl Default for rt_metrics {
     fn default() -> Self {
         unsafe { mem::zeroed() }
@@ -311,7 +479,7 @@ fn sa_to_link(sa: &sockaddr) -> Option<(Option<[u8; 6]>, u16)> {
     }
 }
 
-fn try_get_msg_buf() -> io::Result<(Vec<u8>, usize)> {
+fn try_get_msg_buf(family: u32) -> io::Result<(Vec<u8>, usize)> {
     const MAX_RETRYS: usize = 3;
 
     for _ in 0..MAX_RETRYS {
@@ -321,7 +489,7 @@ fn try_get_msg_buf() -> io::Result<(Vec<u8>, usize)> {
         mib[0] = CTL_NET;
         mib[1] = AF_ROUTE;
         mib[2] = 0;
-        mib[3] = 0; // family: ipv4 & ipv6
+        mib[3] = family; // 0 for both ipv4 & ipv6
         mib[4] = NET_RT_DUMP;
         // mib[5] flags: 0
 
@@ -366,8 +534,8 @@ fn try_get_msg_buf() -> io::Result<(Vec<u8>, usize)> {
     ))
 }
 
-async fn list_routes() -> io::Result<Vec<Route>> {
-    let (mut msgs_buf, len) = try_get_msg_buf()?;
+async fn list_routes(family: u32) -> io::Result<Vec<Route>> {
+    let (mut msgs_buf, len) = try_get_msg_buf(family)?;
 
     let mut routes = vec![];
     let mut offset = 0;
@@ -412,14 +580,139 @@ fn code_to_error(err: i32) -> io::Error {
     io::Error::new(kind, format!("rtm_errno {}", err))
 }
 
-async fn add_or_del_route(
-    dst: IpAddr,
-    dst_mask: IpAddr,
-    gateway: Option<IpAddr>,
-    ifindex: Option<u32>,
-    add: bool,
-) -> io::Result<()> {
+fn route_socket() -> io::Result<UnixStream> {
+    let fd = unsafe { socket(PF_ROUTE as i32, SOCK_RAW as i32, AF_UNSPEC as i32) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    runtime::async_socket(route_fd)
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+fn ip_to_sockaddr(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(addr) => {
+            let sa_in = sockaddr_in {
+                sin_len: mem::size_of::<sockaddr_in>() as u8,
+                sin_family: AF_INET as u8,
+                sin_port: 0,
+                sin_addr: in_addr {
+                    s_addr: u32::from_ne_bytes(addr.octets()),
+                },
+                sin_zero: [0i8; 8],
+            };
+            as_bytes(&sa_in).to_vec()
+        }
+        IpAddr::V6(addr) => {
+            let sa_in6 = sockaddr_in6 {
+                sin6_len: mem::size_of::<sockaddr_in6>() as u8,
+                sin6_family: AF_INET6 as u8,
+                sin6_port: 0,
+                sin6_flowinfo: 0,
+                sin6_addr: in6_addr {
+                    __u6_addr: unsafe { mem::transmute(addr.octets()) },
+                },
+                sin6_scope_id: 0,
+            };
+            as_bytes(&sa_in6).to_vec()
+        }
+    }
+}
+
+static RTM_SEQ: AtomicI32 = AtomicI32::new(1);
+
+// Ask the kernel for the route it would use for `destination`, like `route get`. With `ifscope`
+// only routes scoped to that interface are considered.
+async fn get_route(destination: IpAddr, ifscope: Option<u32>) -> io::Result<Route> {
+    const HDR_SIZE: usize = mem::size_of::<rt_msghdr>();
+
+    let mut attrs = ip_to_sockaddr(destination);
+    // an empty link address asks the kernel to include the interface of the route in the reply
+    attrs.extend_from_slice(as_bytes(&sockaddr_dl::default()));
+
+    let mut rtm_flags = RTF_UP | RTF_GATEWAY | RTF_HOST | RTF_STATIC;
+    let mut rtm_index = 0;
+    if let Some(ifindex) = ifscope {
+        rtm_flags |= RTF_IFSCOPE;
+        rtm_index = ifindex as u16;
+    }
+    let seq = RTM_SEQ.fetch_add(1, Ordering::Relaxed);
+    let hdr = rt_msghdr {
+        rtm_msglen: (HDR_SIZE + attrs.len()) as u16,
+        rtm_version: RTM_VERSION as u8,
+        rtm_type: RTM_GET as u8,
+        rtm_index,
+        rtm_flags: rtm_flags as i32,
+        rtm_addrs: (RTA_DST | RTA_IFP) as i32,
+        rtm_pid: 0,
+        rtm_seq: seq,
+        rtm_errno: 0,
+        rtm_use: 0,
+        rtm_inits: 0,
+        rtm_rmx: rt_metrics::default(),
+    };
+    let mut msg = as_bytes(&hdr).to_vec();
+    msg.extend_from_slice(&attrs);
+
+    let mut sock = route_socket()?;
+    if let Err(e) = sock.write_all(&msg).await {
+        // the kernel fails the write with ESRCH if there is no matching route
+        return Err(match e.raw_os_error() {
+            Some(err) => code_to_error(err),
+            None => e,
+        });
+    }
+
+    // the socket also receives every other routing message, wait for the reply to ours
+    let pid = unsafe { getpid() };
+    let mut buf = [0u8; 2048];
+    loop {
+        let read = sock.read(&mut buf).await?;
+        if read < HDR_SIZE {
+            continue;
+        }
+        let reply: &rt_msghdr = unsafe { &*(buf.as_ptr() as *const rt_msghdr) };
+        if reply.rtm_type as u32 != RTM_GET || reply.rtm_pid != pid || reply.rtm_seq != seq {
+            continue;
+        }
+        if reply.rtm_errno != 0 {
+            return Err(code_to_error(reply.rtm_errno));
+        }
+        let len = (reply.rtm_msglen as usize).min(read);
+        return message_to_route(reply, &buf[HDR_SIZE..len])
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "No route found for destination"));
+    }
+}
+
+async fn add_or_del_route(route: &Route, add: bool) -> io::Result<()> {
+    let dst = route.destination;
+    let dst_mask = route.mask();
+    let (mut gateway, ifindex) = if add {
+        (route.gateway, route.ifindex)
+    } else {
+        (None, None)
+    };
+
     let mut rtm_flags = (RTF_STATIC | RTF_UP) as i32;
+    if add {
+        rtm_flags |= match route.kind {
+            RouteKind::Unicast => 0,
+            RouteKind::Blackhole => RTF_BLACKHOLE as i32,
+            RouteKind::Unreachable | RouteKind::Prohibit => RTF_REJECT as i32,
+            RouteKind::Other => return Err(invalid_route("routes of other kinds can't be added")),
+        };
+        // like `route add -blackhole`, dropping routes still need a next hop, use loopback
+        if route.kind != RouteKind::Unicast && gateway.is_none() && ifindex.is_none() {
+            gateway = Some(match dst {
+                IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+    }
     // TODO not sure about this !add
     if gateway.is_some() || !add {
         rtm_flags |= RTF_GATEWAY as i32;
@@ -432,20 +725,37 @@ async fn add_or_del_route(
 
     let rtm_type = if add { RTM_ADD } else { RTM_DELETE } as u8;
 
+    let (rtm_rmx, rtm_inits) = if add {
+        metrics_to_rt(&route.metrics)
+    } else {
+        (rt_metrics::default(), 0)
+    };
+
+    // scoped routes are only used for traffic bound to the interface in rtm_index, and have to
+    // be deleted with the same scope
+    let mut rtm_index = 0;
+    if route.ifscope {
+        let Some(scope) = route.ifindex else {
+            return Err(invalid_route("scoped routes need an ifindex"));
+        };
+        rtm_flags |= RTF_IFSCOPE as i32;
+        rtm_index = scope as u16;
+    }
+
     let mut rtmsg = m_rtmsg {
         hdr: rt_msghdr {
             rtm_msglen: 128,
             rtm_version: RTM_VERSION as u8,
             rtm_type,
-            rtm_index: 0,
+            rtm_index,
             rtm_flags,
             rtm_addrs: rtm_addrs as i32,
             rtm_pid: 0,
             rtm_seq: 1,
             rtm_errno: 0,
             rtm_use: 0,
-            rtm_inits: 0,
-            rtm_rmx: rt_metrics::default(),
+            rtm_inits,
+            rtm_rmx,
         },
         attrs: [0u8; 128],
     };
@@ -602,7 +912,7 @@ async fn add_or_del_route(
         unsafe { std::slice::from_raw_parts(ptr, len) }
     };
     let route_fd = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
-    let mut f: UnixStream = route_fd.try_into()?;
+    let mut f = runtime::async_socket(route_fd)?;
 
     f.write_all(slice).await?;
 
@@ -1,4 +1,5 @@
 mod bind;
+mod dynamic_store;
 pub(crate) mod macos;
 
 pub(crate) use macos::Handle;
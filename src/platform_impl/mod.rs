@@ -1,3 +1,8 @@
+#[cfg(all(target_os = "ios", not(doc)))]
+mod ios;
+#[cfg(all(target_os = "ios", not(doc)))]
+pub(crate) use ios::Handle as PlatformHandle;
+
 #[cfg(all(target_os = "macos", not(doc)))]
 mod macos;
 #[cfg(all(target_os = "macos", not(doc)))]
@@ -5,15 +10,93 @@ pub use macos::ifname_to_index;
 #[cfg(all(target_os = "macos", not(doc)))]
 pub(crate) use macos::Handle as PlatformHandle;
 
-#[cfg(all(target_os = "linux", not(doc)))]
+#[cfg(all(
+    any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"),
+    not(doc)
+))]
+mod bsd;
+#[cfg(all(
+    any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"),
+    not(doc)
+))]
+pub(crate) use bsd::Handle as PlatformHandle;
+
+#[cfg(all(
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris"
+    ),
+    not(doc)
+))]
+mod runtime;
+
+#[cfg(all(target_os = "fuchsia", not(doc)))]
+mod fuchsia;
+#[cfg(all(target_os = "fuchsia", not(doc)))]
+pub(crate) use fuchsia::Handle as PlatformHandle;
+
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(doc)))]
+mod illumos;
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(doc)))]
+pub(crate) use illumos::Handle as PlatformHandle;
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
 mod linux;
-#[cfg(all(target_os = "linux", not(doc)))]
-pub(crate) use linux::Handle as PlatformHandle;
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
+mod netlink;
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
+mod procfs;
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
+pub(crate) use linux::{netlink_errno, Handle as PlatformHandle};
 
+#[cfg(all(target_os = "windows", not(doc)))]
+mod persistent_routes;
 #[cfg(all(target_os = "windows", not(doc)))]
 mod windows;
 #[cfg(all(target_os = "windows", not(doc)))]
 pub(crate) use self::windows::Handle as PlatformHandle;
 
+#[cfg(all(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia",
+        target_os = "windows"
+    )),
+    not(doc)
+))]
+mod stub;
+#[cfg(all(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "fuchsia",
+        target_os = "windows"
+    )),
+    not(doc)
+))]
+pub(crate) use stub::Handle as PlatformHandle;
+
 #[cfg(doc)]
 pub(crate) struct PlatformHandle;
@@ -0,0 +1,805 @@
+// The netlink connection the Linux backend sends its requests over and receives route events
+// from. By default it is the one of the rtnetlink crate, with the `raw-netlink` feature a small one
+// built directly on netlink-sys, which leaves out rtnetlink and netlink-proto. Without an async
+// runtime, i.e. with only `runtime-thread`, a thread of its own drives the socket.
+
+use std::io;
+
+use futures::channel::mpsc::UnboundedReceiver;
+use netlink_packet_core::NetlinkMessage;
+use netlink_packet_route::RouteNetlinkMessage;
+use netlink_sys::SocketAddr;
+
+#[cfg(all(
+    feature = "raw-netlink",
+    any(feature = "runtime-tokio", feature = "runtime-smol")
+))]
+pub(crate) use raw::{Connection, Requests, Responses};
+#[cfg(all(
+    feature = "rtnetlink",
+    not(feature = "raw-netlink"),
+    any(feature = "runtime-tokio", feature = "runtime-smol")
+))]
+pub(crate) use stack::{Connection, Requests, Responses};
+#[cfg(all(
+    feature = "runtime-thread",
+    not(any(feature = "runtime-tokio", feature = "runtime-smol"))
+))]
+pub(crate) use thread::{Connection, Requests, Responses};
+
+#[cfg(all(
+    any(feature = "runtime-tokio", feature = "runtime-smol"),
+    not(any(feature = "rtnetlink", feature = "raw-netlink"))
+))]
+compile_error!("enable one of the rtnetlink and raw-netlink features");
+
+/// The messages received without being requested, e.g. route events.
+pub(crate) type Events = UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>;
+
+/// Open a netlink route socket and the connection driving it, which has to be polled for requests
+/// to be answered.
+pub(crate) fn new_connection() -> io::Result<(Connection, Requests, Events)> {
+    #[cfg(all(
+        feature = "raw-netlink",
+        any(feature = "runtime-tokio", feature = "runtime-smol")
+    ))]
+    return raw::new_connection();
+    #[cfg(all(
+        feature = "rtnetlink",
+        not(feature = "raw-netlink"),
+        any(feature = "runtime-tokio", feature = "runtime-smol")
+    ))]
+    {
+        let (mut connection, handle, events) =
+            rtnetlink::new_connection_with_socket::<super::runtime::Socket>()?;
+        // like the built-in connections, a request is answered by its ack or done message too,
+        // which carries whether the table changed during a dump
+        connection.set_forward_ack(true);
+        connection.set_forward_done(true);
+        Ok((connection, Requests(handle), events))
+    }
+    #[cfg(all(
+        feature = "runtime-thread",
+        not(any(feature = "runtime-tokio", feature = "runtime-smol"))
+    ))]
+    return thread::new_connection();
+}
+
+/// A netlink socket of its own for a single large dump. It is only read as fast as the dump is
+/// consumed, the kernel holds off while the receive buffer is full. With only `runtime-thread`
+/// reading blocks the thread, there is no async runtime to wait on.
+pub(crate) struct DumpSocket {
+    #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+    socket: super::runtime::Socket,
+    #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+    socket: netlink_sys::Socket,
+    // the datagram being read and where its next message starts
+    buf: Vec<u8>,
+    offset: usize,
+}
+
+impl DumpSocket {
+    pub(crate) fn open() -> io::Result<Self> {
+        #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+        let socket = {
+            use netlink_sys::AsyncSocket;
+            super::runtime::Socket::new(netlink_sys::protocols::NETLINK_ROUTE)?
+        };
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        let socket = netlink_sys::Socket::new(netlink_sys::protocols::NETLINK_ROUTE)?;
+        Ok(Self {
+            socket,
+            buf: vec![],
+            offset: 0,
+        })
+    }
+
+    pub(crate) fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        use std::os::fd::AsRawFd;
+        #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+        return netlink_sys::AsyncSocket::socket_ref(&self.socket).as_raw_fd();
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        return self.socket.as_raw_fd();
+    }
+
+    /// Send a request, dropping what is left of the answer to the previous one.
+    pub(crate) async fn send(
+        &mut self,
+        mut message: NetlinkMessage<RouteNetlinkMessage>,
+    ) -> io::Result<()> {
+        message.finalize();
+        let mut buf = vec![0; message.header.length as usize];
+        message.serialize(&mut buf);
+        #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+        futures::future::poll_fn(|cx| {
+            netlink_sys::AsyncSocket::poll_send(&mut self.socket, cx, &buf)
+        })
+        .await?;
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        self.socket.send(&buf, 0)?;
+        self.buf.clear();
+        self.offset = 0;
+        Ok(())
+    }
+
+    /// The next message received, waiting for the kernel to send more.
+    pub(crate) async fn recv(&mut self) -> io::Result<NetlinkMessage<RouteNetlinkMessage>> {
+        use netlink_packet_core::NetlinkBuffer;
+        loop {
+            let rest = self.buf.get(self.offset..).unwrap_or_default();
+            if let Ok(header) = NetlinkBuffer::new_checked(rest) {
+                let len = header.length() as usize;
+                let message = NetlinkMessage::<RouteNetlinkMessage>::deserialize(&rest[..len]);
+                // messages are aligned to 4 bytes
+                self.offset = (self.offset + len + 3) & !3;
+                match message {
+                    Ok(message) => return Ok(message),
+                    Err(_e) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(error = %_e, "skipping a netlink message that can't be parsed");
+                        continue;
+                    }
+                }
+            }
+            #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
+            let (buf, _) = futures::future::poll_fn(|cx| {
+                netlink_sys::AsyncSocket::poll_recv_from_full(&mut self.socket, cx)
+            })
+            .await?;
+            #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+            let (buf, _) = self.socket.recv_from_full()?;
+            self.buf = buf;
+            self.offset = 0;
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "rtnetlink",
+    not(feature = "raw-netlink"),
+    any(feature = "runtime-tokio", feature = "runtime-smol")
+))]
+mod stack {
+    use std::io;
+
+    use futures::{stream::BoxStream, StreamExt};
+    use netlink_packet_core::NetlinkMessage;
+    use netlink_packet_route::RouteNetlinkMessage;
+
+    use super::super::runtime::Socket;
+
+    pub(crate) type Connection = rtnetlink::proto::Connection<RouteNetlinkMessage, Socket>;
+
+    /// The answers to a request, ending with the last one.
+    pub(crate) type Responses = BoxStream<'static, NetlinkMessage<RouteNetlinkMessage>>;
+
+    /// Sends requests over a connection, clones share it.
+    #[derive(Clone)]
+    pub(crate) struct Requests(pub(super) rtnetlink::Handle);
+
+    impl Requests {
+        pub(crate) fn request(
+            &self,
+            message: NetlinkMessage<RouteNetlinkMessage>,
+        ) -> io::Result<Responses> {
+            match self.0.clone().request(message) {
+                Ok(responses) => Ok(responses.boxed()),
+                Err(e) => Err(io::Error::new(io::ErrorKind::BrokenPipe, e.to_string())),
+            }
+        }
+    }
+}
+
+// Numbers the requests of the built-in connections and hands them the answers with their
+// sequence number, everything else received is an event.
+#[cfg(any(
+    all(
+        feature = "raw-netlink",
+        any(feature = "runtime-tokio", feature = "runtime-smol")
+    ),
+    all(
+        feature = "runtime-thread",
+        not(any(feature = "runtime-tokio", feature = "runtime-smol"))
+    )
+))]
+mod dispatch {
+    use std::collections::HashMap;
+
+    use futures::channel::mpsc::UnboundedSender;
+    use netlink_packet_core::{
+        NetlinkBuffer, NetlinkMessage, NetlinkPayload, NLM_F_ACK, NLM_F_MULTIPART,
+    };
+    use netlink_packet_route::RouteNetlinkMessage;
+    use netlink_sys::SocketAddr;
+
+    pub(super) struct Request {
+        pub(super) message: NetlinkMessage<RouteNetlinkMessage>,
+        pub(super) responses: UnboundedSender<NetlinkMessage<RouteNetlinkMessage>>,
+    }
+
+    // A request waiting for its answers.
+    struct Pending {
+        responses: UnboundedSender<NetlinkMessage<RouteNetlinkMessage>>,
+        // answered by an ack or an error once the kernel is done with it
+        ack: bool,
+    }
+
+    pub(super) struct Dispatcher {
+        pending: HashMap<u32, Pending>,
+        sequence_number: u32,
+        events: UnboundedSender<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
+    }
+
+    impl Dispatcher {
+        pub(super) fn new(
+            events: UnboundedSender<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
+        ) -> Self {
+            Self {
+                pending: HashMap::new(),
+                sequence_number: 0,
+                events,
+            }
+        }
+
+        // Number and serialize a request, which then waits for its answers.
+        pub(super) fn register(&mut self, request: Request) -> Vec<u8> {
+            let Request {
+                mut message,
+                responses,
+            } = request;
+            // 0 is left to unsolicited messages
+            self.sequence_number = self.sequence_number.wrapping_add(1).max(1);
+            message.header.sequence_number = self.sequence_number;
+            message.header.port_number = 0;
+            message.finalize();
+            let mut buf = vec![0; message.header.length as usize];
+            message.serialize(&mut buf);
+
+            // requesters that stopped waiting, e.g. for changes sent without an ack
+            self.pending
+                .retain(|_, pending| !pending.responses.is_closed());
+            let ack = message.header.flags & NLM_F_ACK != 0;
+            self.pending
+                .insert(self.sequence_number, Pending { responses, ack });
+            buf
+        }
+
+        // The last request couldn't be sent, it is answered by closing its stream.
+        pub(super) fn forget_last(&mut self) {
+            self.pending.remove(&self.sequence_number);
+        }
+
+        // Whether no request waits for answers anymore.
+        pub(super) fn is_idle(&mut self) -> bool {
+            self.pending
+                .retain(|_, pending| !pending.responses.is_closed());
+            self.pending.is_empty()
+        }
+
+        pub(super) fn dispatch(&mut self, mut buf: &[u8], addr: SocketAddr) {
+            while let Ok(header) = NetlinkBuffer::new_checked(buf) {
+                let len = header.length() as usize;
+                let message = NetlinkMessage::<RouteNetlinkMessage>::deserialize(&buf[..len]);
+                // messages are aligned to 4 bytes
+                buf = buf.get((len + 3) & !3..).unwrap_or_default();
+                match message {
+                    Ok(message) => self.forward(message, addr),
+                    Err(_e) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(error = %_e, "skipping a netlink message that can't be parsed");
+                    }
+                }
+            }
+        }
+
+        fn forward(&mut self, message: NetlinkMessage<RouteNetlinkMessage>, addr: SocketAddr) {
+            let seq = message.header.sequence_number;
+            // events are sent to multicast groups, even those caused by our requests
+            if addr.multicast_groups() != 0 || seq == 0 {
+                _ = self.events.unbounded_send((message, addr));
+                return;
+            }
+            // the rest of the answer to a request nobody waits for anymore
+            let Some(pending) = self.pending.get(&seq) else {
+                return;
+            };
+            let last = match &message.payload {
+                NetlinkPayload::Done(_) | NetlinkPayload::Error(_) => true,
+                _ => message.header.flags & NLM_F_MULTIPART == 0 && !pending.ack,
+            };
+            // the done message too, it's flagged when the table changed during the dump
+            let delivered = pending.responses.unbounded_send(message).is_ok();
+            if last || !delivered {
+                self.pending.remove(&seq);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use futures::channel::mpsc;
+        use netlink_packet_core::{
+            DoneMessage, ErrorMessage, NetlinkHeader, NLM_F_DUMP, NLM_F_MULTIPART, NLM_F_REQUEST,
+        };
+        use netlink_packet_route::route::RouteMessage;
+
+        use super::*;
+
+        fn serialize(mut message: NetlinkMessage<RouteNetlinkMessage>) -> Vec<u8> {
+            message.finalize();
+            let mut buf = vec![0; message.header.length as usize];
+            message.serialize(&mut buf);
+            buf
+        }
+
+        #[test]
+        fn it_hands_answers_to_their_request() {
+            let (events, mut events_rx) = mpsc::unbounded();
+            let mut dispatcher = Dispatcher::new(events);
+            let (responses, mut rx) = mpsc::unbounded();
+            let mut message =
+                NetlinkMessage::from(RouteNetlinkMessage::GetRoute(RouteMessage::default()));
+            message.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+            dispatcher.register(Request { message, responses });
+
+            let mut answer =
+                NetlinkMessage::from(RouteNetlinkMessage::NewRoute(RouteMessage::default()));
+            answer.header.sequence_number = 1;
+            answer.header.flags = NLM_F_MULTIPART;
+            let mut done = NetlinkMessage::new(
+                NetlinkHeader::default(),
+                NetlinkPayload::Done(DoneMessage::default()),
+            );
+            done.header.sequence_number = 1;
+            done.header.flags = NLM_F_MULTIPART;
+            let event =
+                NetlinkMessage::from(RouteNetlinkMessage::NewRoute(RouteMessage::default()));
+
+            let mut buf = serialize(answer);
+            buf.extend(serialize(event));
+            buf.extend(serialize(done));
+            dispatcher.dispatch(&buf, SocketAddr::new(0, 0));
+
+            let answer = rx.try_next().unwrap().unwrap();
+            assert!(matches!(
+                answer.payload,
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(_))
+            ));
+            // the done message is handed out with its flags and ends the answer
+            let done = rx.try_next().unwrap().unwrap();
+            assert!(matches!(done.payload, NetlinkPayload::Done(_)));
+            assert!(rx.try_next().unwrap().is_none());
+            assert!(events_rx.try_next().unwrap().is_some());
+            assert!(dispatcher.is_idle());
+        }
+
+        #[test]
+        fn it_matches_pipelined_acks_to_their_request() {
+            let (events, _events_rx) = mpsc::unbounded();
+            let mut dispatcher = Dispatcher::new(events);
+            let mut rxs: Vec<_> = (0..3)
+                .map(|_| {
+                    let (responses, rx) = mpsc::unbounded();
+                    let mut message = NetlinkMessage::from(RouteNetlinkMessage::NewRoute(
+                        RouteMessage::default(),
+                    ));
+                    message.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+                    dispatcher.register(Request { message, responses });
+                    rx
+                })
+                .collect();
+
+            // answered out of order in one datagram, the second request failed
+            let answer = |seq, code| {
+                let mut error = ErrorMessage::default();
+                error.code = std::num::NonZeroI32::new(code);
+                let mut message =
+                    NetlinkMessage::new(NetlinkHeader::default(), NetlinkPayload::Error(error));
+                message.header.sequence_number = seq;
+                serialize(message)
+            };
+            let mut buf = answer(3, 0);
+            buf.extend(answer(1, 0));
+            buf.extend(answer(2, -libc::EEXIST));
+            dispatcher.dispatch(&buf, SocketAddr::new(0, 0));
+
+            let codes: Vec<_> = rxs
+                .iter_mut()
+                .map(|rx| match rx.try_next().unwrap().unwrap().payload {
+                    NetlinkPayload::Error(e) => e.code.map_or(0, |code| code.get()),
+                    _ => panic!("not an ack"),
+                })
+                .collect();
+            assert_eq!(codes, [0, -libc::EEXIST, 0]);
+            assert!(rxs.iter_mut().all(|rx| rx.try_next().unwrap().is_none()));
+            assert!(dispatcher.is_idle());
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "raw-netlink",
+    any(feature = "runtime-tokio", feature = "runtime-smol")
+))]
+mod raw {
+    use std::{
+        future::Future,
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::{
+        channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+        StreamExt,
+    };
+    use netlink_packet_core::NetlinkMessage;
+    use netlink_packet_route::RouteNetlinkMessage;
+    use netlink_sys::{protocols::NETLINK_ROUTE, AsyncSocket};
+
+    use super::super::runtime::Socket;
+    use super::dispatch::{Dispatcher, Request};
+    use super::Events;
+
+    /// The answers to a request, ending with the last one.
+    pub(crate) type Responses = UnboundedReceiver<NetlinkMessage<RouteNetlinkMessage>>;
+
+    /// Sends requests over a connection, clones share it.
+    #[derive(Clone)]
+    pub(crate) struct Requests(UnboundedSender<Request>);
+
+    impl Requests {
+        pub(crate) fn request(
+            &self,
+            message: NetlinkMessage<RouteNetlinkMessage>,
+        ) -> io::Result<Responses> {
+            let (responses, rx) = mpsc::unbounded();
+            self.0
+                .unbounded_send(Request { message, responses })
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "the netlink connection is closed",
+                    )
+                })?;
+            Ok(rx)
+        }
+    }
+
+    /// Sends the requests and hands out what is received. Finishes once every `Requests` was
+    /// dropped and the pending requests were answered.
+    pub(crate) struct Connection {
+        socket: Socket,
+        requests: UnboundedReceiver<Request>,
+        requests_closed: bool,
+        // the serialized request that didn't fit into the socket yet
+        outgoing: Option<Vec<u8>>,
+        dispatcher: Dispatcher,
+    }
+
+    pub(super) fn new_connection() -> io::Result<(Connection, Requests, Events)> {
+        let (requests_tx, requests) = mpsc::unbounded();
+        let (events, events_rx) = mpsc::unbounded();
+        let connection = Connection {
+            socket: Socket::new(NETLINK_ROUTE)?,
+            requests,
+            requests_closed: false,
+            outgoing: None,
+            dispatcher: Dispatcher::new(events),
+        };
+        Ok((connection, Requests(requests_tx), events_rx))
+    }
+
+    impl Connection {
+        pub(crate) fn socket_mut(&mut self) -> &mut Socket {
+            &mut self.socket
+        }
+    }
+
+    impl Future for Connection {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = self.get_mut();
+            loop {
+                if let Some(buf) = &this.outgoing {
+                    match this.socket.poll_send(cx, buf) {
+                        Poll::Ready(Ok(_)) => this.outgoing = None,
+                        Poll::Ready(Err(_e)) => {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(error = %_e, "sending a netlink request failed");
+                            this.dispatcher.forget_last();
+                            this.outgoing = None;
+                        }
+                        Poll::Pending => break,
+                    }
+                }
+                if this.requests_closed {
+                    break;
+                }
+                match this.requests.poll_next_unpin(cx) {
+                    Poll::Ready(Some(request)) => {
+                        this.outgoing = Some(this.dispatcher.register(request))
+                    }
+                    Poll::Ready(None) => this.requests_closed = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            loop {
+                match this.socket.poll_recv_from_full(cx) {
+                    Poll::Ready(Ok((buf, addr))) => this.dispatcher.dispatch(&buf, addr),
+                    Poll::Ready(Err(_e)) => {
+                        // e.g. ENOBUFS after events were dropped, the socket stays usable
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(error = %_e, "receiving from the netlink socket failed");
+                        cx.waker().wake_by_ref();
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+
+            if this.requests_closed && this.outgoing.is_none() && this.dispatcher.is_idle() {
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "runtime-thread",
+    not(any(feature = "runtime-tokio", feature = "runtime-smol"))
+))]
+mod thread {
+    use std::{
+        future::Future,
+        io,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use futures::channel::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    };
+    use netlink_packet_core::NetlinkMessage;
+    use netlink_packet_route::RouteNetlinkMessage;
+    use netlink_sys::protocols::NETLINK_ROUTE;
+
+    use super::dispatch::{Dispatcher, Request};
+    use super::Events;
+
+    /// The answers to a request, ending with the last one.
+    pub(crate) type Responses = UnboundedReceiver<NetlinkMessage<RouteNetlinkMessage>>;
+
+    /// Sends requests to the thread driving the socket, clones share it.
+    #[derive(Clone)]
+    pub(crate) struct Requests(Arc<Bridge>);
+
+    struct Bridge {
+        requests: UnboundedSender<Request>,
+        // an eventfd waking the thread up from waiting for the socket
+        wake: OwnedFd,
+    }
+
+    impl Bridge {
+        fn wake(&self) {
+            let one = 1u64.to_ne_bytes();
+            // only fails once the counter is about to overflow, then the thread is awake anyway
+            unsafe { libc::write(self.wake.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+        }
+    }
+
+    // the thread finds the requests closed and stops once they are answered
+    impl Drop for Bridge {
+        fn drop(&mut self) {
+            self.requests.close_channel();
+            self.wake();
+        }
+    }
+
+    impl Requests {
+        pub(crate) fn request(
+            &self,
+            message: NetlinkMessage<RouteNetlinkMessage>,
+        ) -> io::Result<Responses> {
+            let (responses, rx) = mpsc::unbounded();
+            self.0
+                .requests
+                .unbounded_send(Request { message, responses })
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "the netlink connection is closed",
+                    )
+                })?;
+            self.0.wake();
+            Ok(rx)
+        }
+    }
+
+    /// The netlink socket, mirroring the socket types of the runtimes.
+    pub(crate) struct Socket(netlink_sys::Socket);
+
+    impl Socket {
+        pub(crate) fn socket_mut(&mut self) -> &mut netlink_sys::Socket {
+            &mut self.0
+        }
+    }
+
+    /// Starts the thread driving the socket when first polled and finishes with it, once every
+    /// `Requests` was dropped and the pending requests were answered.
+    pub(crate) struct Connection {
+        driver: Option<Driver>,
+        done: Option<oneshot::Receiver<()>>,
+    }
+
+    struct Driver {
+        socket: Socket,
+        requests: UnboundedReceiver<Request>,
+        wake: OwnedFd,
+        dispatcher: Dispatcher,
+    }
+
+    pub(super) fn new_connection() -> io::Result<(Connection, Requests, Events)> {
+        let wake = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if wake < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let wake = unsafe { OwnedFd::from_raw_fd(wake) };
+        let socket = netlink_sys::Socket::new(NETLINK_ROUTE)?;
+        socket.set_non_blocking(true)?;
+
+        let (requests_tx, requests) = mpsc::unbounded();
+        let (events, events_rx) = mpsc::unbounded();
+        let bridge = Bridge {
+            requests: requests_tx,
+            wake: wake.try_clone()?,
+        };
+        let connection = Connection {
+            driver: Some(Driver {
+                socket: Socket(socket),
+                requests,
+                wake,
+                dispatcher: Dispatcher::new(events),
+            }),
+            done: None,
+        };
+        Ok((connection, Requests(Arc::new(bridge)), events_rx))
+    }
+
+    impl Connection {
+        pub(crate) fn socket_mut(&mut self) -> &mut Socket {
+            &mut self
+                .driver
+                .as_mut()
+                .expect("the socket is only moved to its thread when polled")
+                .socket
+        }
+    }
+
+    impl Future for Connection {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = self.get_mut();
+            if let Some(driver) = this.driver.take() {
+                let (tx, done) = oneshot::channel();
+                let spawned = std::thread::Builder::new()
+                    .name("net-route-netlink".into())
+                    .spawn(move || {
+                        driver.run();
+                        _ = tx.send(());
+                    });
+                if let Err(_e) = spawned {
+                    #[cfg(feature = "tracing")]
+                    tracing::error!(error = %_e, "spawning the netlink thread failed");
+                    return Poll::Ready(());
+                }
+                this.done = Some(done);
+            }
+            match this.done.as_mut() {
+                Some(done) => Pin::new(done).poll(cx).map(|_| ()),
+                None => Poll::Ready(()),
+            }
+        }
+    }
+
+    impl Driver {
+        fn run(mut self) {
+            let mut requests_closed = false;
+            loop {
+                if !requests_closed {
+                    requests_closed = self.send_requests();
+                }
+                if requests_closed && self.dispatcher.is_idle() {
+                    return;
+                }
+
+                let mut fds = [
+                    libc::pollfd {
+                        fd: self.socket.0.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                    libc::pollfd {
+                        fd: self.wake.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                ];
+                if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    #[cfg(feature = "tracing")]
+                    tracing::error!(error = %e, "waiting for the netlink socket failed");
+                    return;
+                }
+                if fds[0].revents != 0 {
+                    self.receive();
+                }
+            }
+        }
+
+        // Send the requests made since the last call, returning whether the requests are closed.
+        fn send_requests(&mut self) -> bool {
+            let mut counter = [0u8; 8];
+            // resets the eventfd, fails with EAGAIN when nobody woke the thread
+            unsafe { libc::read(self.wake.as_raw_fd(), counter.as_mut_ptr().cast(), 8) };
+            loop {
+                let request = match self.requests.try_next() {
+                    Ok(Some(request)) => request,
+                    Ok(None) => return true,
+                    Err(_) => return false,
+                };
+                let buf = self.dispatcher.register(request);
+                if let Err(_e) = send_all(&self.socket.0, &buf) {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %_e, "sending a netlink request failed");
+                    self.dispatcher.forget_last();
+                }
+            }
+        }
+
+        fn receive(&mut self) {
+            loop {
+                match self.socket.0.recv_from_full() {
+                    Ok((buf, addr)) => self.dispatcher.dispatch(&buf, addr),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_e) => {
+                        // e.g. ENOBUFS after events were dropped, the socket stays usable
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(error = %_e, "receiving from the netlink socket failed");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    // The socket is non-blocking so receiving never stalls the thread, sending waits for room.
+    fn send_all(socket: &netlink_sys::Socket, buf: &[u8]) -> io::Result<()> {
+        loop {
+            match socket.send(buf, 0) {
+                Ok(_) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    let mut fd = libc::pollfd {
+                        fd: socket.as_raw_fd(),
+                        events: libc::POLLOUT,
+                        revents: 0,
+                    };
+                    unsafe { libc::poll(&mut fd, 1, -1) };
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
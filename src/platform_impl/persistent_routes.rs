@@ -0,0 +1,212 @@
+// Routes added with `route -p` are stored as value names in the registry and re-created by the
+// tcpip service on boot. The value names have the form `destination,mask,gateway,metric` and
+// only ipv4 routes can be stored this way.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+};
+
+use winapi::shared::minwindef::HKEY;
+use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_SUCCESS};
+use winapi::um::winnt::{KEY_READ, KEY_SET_VALUE, REG_SZ};
+use winapi::um::winreg::{
+    RegCloseKey, RegCreateKeyExW, RegDeleteValueW, RegEnumValueW, RegSetValueExW,
+    HKEY_LOCAL_MACHINE,
+};
+
+use crate::error::invalid_route;
+use crate::{Route, RouteStore};
+
+const PERSISTENT_ROUTES_KEY: &str =
+    r"SYSTEM\CurrentControlSet\Services\Tcpip\Parameters\PersistentRoutes";
+
+// value names are limited to 16383 characters
+const MAX_VALUE_NAME: usize = 16384;
+
+struct Key(HKEY);
+
+impl Key {
+    fn open(access: u32) -> io::Result<Self> {
+        let name = wide(PERSISTENT_ROUTES_KEY);
+        let mut key: HKEY = std::ptr::null_mut();
+        let ret = unsafe {
+            RegCreateKeyExW(
+                HKEY_LOCAL_MACHINE,
+                name.as_ptr(),
+                0,
+                std::ptr::null_mut(),
+                0,
+                access,
+                std::ptr::null_mut(),
+                &mut key,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret != ERROR_SUCCESS as i32 {
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+        Ok(Self(key))
+    }
+
+    fn value_names(&self) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        let mut buf = vec![0u16; MAX_VALUE_NAME];
+        for index in 0.. {
+            let mut len = buf.len() as u32;
+            let ret = unsafe {
+                RegEnumValueW(
+                    self.0,
+                    index,
+                    buf.as_mut_ptr(),
+                    &mut len,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            match ret as u32 {
+                ERROR_SUCCESS => names.push(String::from_utf16_lossy(&buf[..len as usize])),
+                ERROR_NO_MORE_ITEMS => break,
+                _ => return Err(io::Error::from_raw_os_error(ret)),
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe { RegCloseKey(self.0) };
+    }
+}
+
+/// Read the routes that are re-created on boot.
+pub(crate) fn list() -> io::Result<Vec<Route>> {
+    Ok(Key::open(KEY_READ)?
+        .value_names()?
+        .iter()
+        .filter_map(|name| parse_value_name(name))
+        .collect())
+}
+
+/// Store `route` so it is re-created on boot.
+pub(crate) fn store(route: &Route) -> io::Result<()> {
+    let name = wide(&value_name(route)?);
+    let data = wide("");
+    let key = Key::open(KEY_SET_VALUE)?;
+    let ret = unsafe {
+        RegSetValueExW(
+            key.0,
+            name.as_ptr(),
+            0,
+            REG_SZ,
+            data.as_ptr() as *const u8,
+            (data.len() * 2) as u32,
+        )
+    };
+    if ret != ERROR_SUCCESS as i32 {
+        return Err(io::Error::from_raw_os_error(ret));
+    }
+    Ok(())
+}
+
+/// Remove every stored route with the destination and gateway of `route`, regardless of metric.
+pub(crate) fn remove(route: &Route) -> io::Result<()> {
+    let key = Key::open(KEY_READ | KEY_SET_VALUE)?;
+    for name in key.value_names()? {
+        if !parse_value_name(&name).map_or(false, |stored| same_route(&stored, route)) {
+            continue;
+        }
+        let name = wide(&name);
+        let ret = unsafe { RegDeleteValueW(key.0, name.as_ptr()) };
+        if ret != ERROR_SUCCESS as i32 && ret != ERROR_FILE_NOT_FOUND as i32 {
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `stored` describes the same route as the live `route`, ignoring the metric which
+/// the system adjusts when installing the route.
+pub(crate) fn same_route(stored: &Route, route: &Route) -> bool {
+    let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    stored.destination == route.destination
+        && stored.prefix == route.prefix
+        && stored.gateway.unwrap_or(unspecified) == route.gateway.unwrap_or(unspecified)
+}
+
+fn value_name(route: &Route) -> io::Result<String> {
+    let (destination, mask) = match (route.destination, route.mask()) {
+        (IpAddr::V4(destination), IpAddr::V4(mask)) => (destination, mask),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only ipv4 routes can be persistent",
+            ))
+        }
+    };
+    let gateway = match route.gateway {
+        Some(IpAddr::V4(gateway)) => gateway,
+        None => Ipv4Addr::UNSPECIFIED,
+        Some(IpAddr::V6(_)) => return Err(invalid_route("gateway version must match destination")),
+    };
+    // `route -p` stores a metric of 1 when none is given
+    Ok(format!(
+        "{},{},{},{}",
+        destination,
+        mask,
+        gateway,
+        route.metric.unwrap_or(1)
+    ))
+}
+
+fn parse_value_name(name: &str) -> Option<Route> {
+    let mut fields = name.split(',');
+    let destination: Ipv4Addr = fields.next()?.trim().parse().ok()?;
+    let mask: Ipv4Addr = fields.next()?.trim().parse().ok()?;
+    let gateway: Ipv4Addr = fields.next()?.trim().parse().ok()?;
+    let metric = fields.next().and_then(|metric| metric.trim().parse().ok());
+
+    let mut route =
+        Route::new(destination.into(), u32::from(mask).count_ones() as u8).with_persistent(true);
+    if !gateway.is_unspecified() {
+        route.gateway = Some(gateway.into());
+    }
+    route.metric = metric;
+    route.store = RouteStore::Persistent;
+    Some(route)
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_value_names() {
+        let route = Route::new("10.14.0.0".parse().unwrap(), 24)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_metric(5)
+            .with_persistent(true);
+        let mut stored = route.clone();
+        stored.store = RouteStore::Persistent;
+
+        let name = value_name(&route).unwrap();
+        assert_eq!(name, "10.14.0.0,255.255.255.0,192.168.1.1,5");
+        assert_eq!(parse_value_name(&name), Some(stored));
+    }
+
+    #[test]
+    fn it_rejects_ipv6_routes() {
+        let route = Route::new("fd00::".parse().unwrap(), 8);
+        assert_eq!(
+            value_name(&route).unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
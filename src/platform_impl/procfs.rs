@@ -0,0 +1,209 @@
+// Fallback for systems that don't allow route dumps over netlink, most notably Android 11+ where
+// apps are no longer allowed to dump or bind route sockets.
+
+use std::{
+    ffi::CString,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket},
+};
+
+use crate::Route;
+
+const RTF_UP: u32 = 0x0001;
+const RTF_GATEWAY: u32 = 0x0002;
+
+/// Read the ipv4 and v6 routes from `/proc/net/route` and `/proc/net/ipv6_route`.
+pub(crate) fn list_routes() -> io::Result<Vec<Route>> {
+    let mut routes = parse_ipv4_routes(&std::fs::read_to_string("/proc/net/route")?, ifindex);
+    // the ipv6 table is missing on systems with ipv6 disabled
+    if let Ok(content) = std::fs::read_to_string("/proc/net/ipv6_route") {
+        routes.extend(parse_ipv6_routes(&content, ifindex));
+    }
+    Ok(routes)
+}
+
+/// Find the interface the system would use to reach the internet by connecting a udp socket,
+/// which doesn't send any packets. The gateway can't be learned this way.
+pub(crate) fn default_route_by_connect() -> io::Result<Option<Route>> {
+    for (bind, target) in [
+        ("0.0.0.0:0", "8.8.8.8:53"),
+        ("[::]:0", "[2001:4860:4860::8888]:53"),
+    ] {
+        let socket = UdpSocket::bind(bind)?;
+        if socket.connect(target).is_err() {
+            // no route for this family
+            continue;
+        }
+        let local = socket.local_addr()?.ip();
+        if let Some(ifindex) = ifindex_of_address(local)? {
+            let destination = match local {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            return Ok(Some(Route::new(destination, 0).with_ifindex(ifindex)));
+        }
+    }
+    Ok(None)
+}
+
+fn ifindex(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        idx => Some(idx),
+    }
+}
+
+fn ifindex_of_address(addr: IpAddr) -> io::Result<Option<u32>> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut found = None;
+    let mut cur = addrs;
+    while !cur.is_null() {
+        let ifa = unsafe { &*cur };
+        cur = ifa.ifa_next;
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+        let ifa_addr = unsafe {
+            match (*ifa.ifa_addr).sa_family as i32 {
+                libc::AF_INET => {
+                    let sa = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::from(sa.sin_addr.s_addr.to_ne_bytes())
+                }
+                libc::AF_INET6 => {
+                    let sa = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::from(sa.sin6_addr.s6_addr)
+                }
+                _ => continue,
+            }
+        };
+        if ifa_addr == addr {
+            let name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) };
+            found = name.to_str().ok().and_then(ifindex);
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(found)
+}
+
+// Iface Destination Gateway Flags RefCnt Use Metric Mask MTU Window IRTT
+// addresses are printed as the hex value of the network order u32
+fn parse_ipv4_routes(content: &str, ifindex: impl Fn(&str) -> Option<u32>) -> Vec<Route> {
+    let parse_addr = |s: &str| {
+        u32::from_str_radix(s, 16)
+            .ok()
+            .map(|addr| Ipv4Addr::from(addr.to_ne_bytes()))
+    };
+
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let flags = u32::from_str_radix(fields[3], 16).ok()?;
+            if flags & RTF_UP == 0 {
+                return None;
+            }
+            let destination = parse_addr(fields[1])?;
+            let mask = parse_addr(fields[7])?;
+            let metric = fields[6].parse().ok()?;
+
+            let mut route = Route::new(destination.into(), u32::from(mask).count_ones() as u8)
+                .with_metric(metric);
+            route.ifindex = ifindex(fields[0]);
+            if flags & RTF_GATEWAY != 0 {
+                route.gateway = Some(parse_addr(fields[2])?.into());
+            }
+            Some(route)
+        })
+        .collect()
+}
+
+// Destination PrefixLen Source SourcePrefixLen NextHop Metric RefCnt Use Flags Iface
+// all numbers are hex, addresses without separators
+fn parse_ipv6_routes(content: &str, ifindex: impl Fn(&str) -> Option<u32>) -> Vec<Route> {
+    let parse_addr = |s: &str| u128::from_str_radix(s, 16).ok().map(Ipv6Addr::from);
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            let flags = u32::from_str_radix(fields[8], 16).ok()?;
+            if flags & RTF_UP == 0 {
+                return None;
+            }
+            let destination = parse_addr(fields[0])?;
+            let prefix = u8::from_str_radix(fields[1], 16).ok()?;
+            let metric = u32::from_str_radix(fields[5], 16).ok()?;
+
+            let mut route = Route::new(destination.into(), prefix).with_metric(metric);
+            route.ifindex = ifindex(fields[9]);
+            if flags & RTF_GATEWAY != 0 {
+                route.gateway = Some(parse_addr(fields[4])?.into());
+            }
+            Some(route)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_ifindex(name: &str) -> Option<u32> {
+        match name {
+            "eth0" => Some(2),
+            "lo" => Some(1),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn it_parses_ipv4_routes() {
+        let content =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+            eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+            eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        let routes = parse_ipv4_routes(content, fake_ifindex);
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].destination, "0.0.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(routes[0].prefix, 0);
+        assert_eq!(routes[0].gateway, Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(routes[0].ifindex, Some(2));
+        assert_eq!(routes[0].metric, Some(100));
+        assert_eq!(
+            routes[1].destination,
+            "192.168.1.0".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(routes[1].prefix, 24);
+        assert_eq!(routes[1].gateway, None);
+    }
+
+    #[test]
+    fn it_parses_ipv6_routes() {
+        let content = "fe800000000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001     eth0\n\
+            00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003     eth0\n";
+        let routes = parse_ipv6_routes(content, fake_ifindex);
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].destination, "fe80::".parse::<IpAddr>().unwrap());
+        assert_eq!(routes[0].prefix, 64);
+        assert_eq!(routes[0].metric, Some(256));
+        assert_eq!(routes[0].gateway, None);
+        assert_eq!(routes[1].prefix, 0);
+        assert_eq!(routes[1].gateway, Some("fe80::1".parse().unwrap()));
+        assert_eq!(routes[1].metric, Some(1024));
+    }
+}
@@ -0,0 +1,128 @@
+// The parts of the async runtime the backends depend on: spawning the background tasks, timers and
+// the sockets they read from. tokio is used with the `runtime-tokio` feature, otherwise smol with
+// `runtime-smol`. smol's executor runs on its own threads, so it also works from async-std or any
+// other executor. With only `runtime-thread` every task gets a thread of its own, for programs
+// without an async runtime; the netlink socket is then driven by a thread too, see `netlink`.
+
+#![cfg_attr(all(target_os = "ios", feature = "nw-path-monitor"), allow(dead_code))]
+
+use std::{future::Future, io};
+
+use futures::{
+    channel::oneshot,
+    future::{AbortHandle, Abortable},
+};
+
+#[cfg(not(any(
+    feature = "runtime-tokio",
+    feature = "runtime-smol",
+    feature = "runtime-thread"
+)))]
+compile_error!("enable one of the runtime-tokio, runtime-smol and runtime-thread features");
+#[cfg(all(
+    not(any(feature = "runtime-tokio", feature = "runtime-smol")),
+    not(any(target_os = "linux", target_os = "android"))
+))]
+compile_error!(
+    "runtime-thread only supports Linux and Android, enable runtime-tokio or runtime-smol"
+);
+
+#[cfg(all(
+    not(feature = "runtime-tokio"),
+    feature = "runtime-smol",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) use futures::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(all(
+    feature = "runtime-tokio",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A routing socket that can be read and written asynchronously.
+#[cfg(all(
+    feature = "runtime-tokio",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) type UnixStream = tokio::net::UnixStream;
+#[cfg(all(
+    not(feature = "runtime-tokio"),
+    feature = "runtime-smol",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) type UnixStream = smol::Async<std::os::unix::net::UnixStream>;
+
+/// A background task, which keeps running when dropped.
+pub(crate) struct Task {
+    abort: AbortHandle,
+    done: oneshot::Receiver<()>,
+}
+
+impl Task {
+    pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) -> Self {
+        let (abort, registration) = AbortHandle::new_pair();
+        let (tx, done) = oneshot::channel();
+        let future = async move {
+            _ = Abortable::new(future, registration).await;
+            _ = tx.send(());
+        };
+
+        #[cfg(feature = "runtime-tokio")]
+        tokio::spawn(future);
+        #[cfg(all(not(feature = "runtime-tokio"), feature = "runtime-smol"))]
+        smol::spawn(future).detach();
+        #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+        std::thread::Builder::new()
+            .name("net-route".into())
+            .spawn(move || futures::executor::block_on(future))
+            .expect("failed to spawn a thread for a background task");
+
+        Self { abort, done }
+    }
+
+    /// Stop the task the next time it waits.
+    pub(crate) fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Wait for the task to finish or to be stopped.
+    pub(crate) async fn join(&mut self) -> io::Result<()> {
+        (&mut self.done)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "background task panicked"))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    #[cfg(feature = "runtime-tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(all(not(feature = "runtime-tokio"), feature = "runtime-smol"))]
+    smol::Timer::after(duration).await;
+    #[cfg(not(any(feature = "runtime-tokio", feature = "runtime-smol")))]
+    crate::timeout::sleep(duration).await;
+}
+
+/// Register a socket with the runtime.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn async_socket(socket: std::os::unix::net::UnixStream) -> io::Result<UnixStream> {
+    #[cfg(feature = "runtime-tokio")]
+    {
+        socket.set_nonblocking(true)?;
+        socket.try_into()
+    }
+    #[cfg(all(not(feature = "runtime-tokio"), feature = "runtime-smol"))]
+    smol::Async::new(socket)
+}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    feature = "runtime-tokio"
+))]
+pub(crate) type Socket = netlink_sys::TokioSocket;
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(feature = "runtime-tokio"),
+    feature = "runtime-smol"
+))]
+pub(crate) type Socket = netlink_sys::SmolSocket;
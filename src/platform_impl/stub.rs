@@ -0,0 +1,70 @@
+use std::io;
+#[cfg(feature = "events")]
+use std::sync::Arc;
+
+#[cfg(feature = "events")]
+use crate::{metrics::Counters, RouteChange};
+use crate::{Capabilities, DumpFilter, HandleBuilder, Route};
+
+// The backend of targets without a routing table the crate knows how to reach, e.g. wasm32 or
+// operating systems that aren't supported yet. Creating a handle succeeds so applications can
+// depend on the crate unconditionally, every operation fails with `Error::Unsupported` and
+// `capabilities` reports that nothing can be changed.
+//
+// tokio's networking doesn't build for wasm32, so there the crate has to be used without the
+// default `runtime-tokio` feature.
+
+pub(crate) struct Handle;
+
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the routing table isn't supported on this platform",
+    )
+}
+
+impl Handle {
+    pub(crate) fn new(_config: &HandleBuilder) -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub(crate) async fn close(self) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        Ok(Capabilities {
+            modify_routes: false,
+            modify_rules: false,
+        })
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        _counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        // there are no changes to report, the stream ends right away
+        futures::stream::empty()
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn list_filtered(&self, _filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn add(&self, _route: &Route) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) async fn delete(&self, _route: &Route) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
@@ -1,21 +1,80 @@
+#[cfg(feature = "events")]
+use crate::metrics::Counters;
+#[cfg(feature = "events")]
 use async_stream::stream;
-use futures::Stream;
 use std::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "events")]
+use std::sync::Arc;
+use std::time::Duration;
 use std::{io, net::IpAddr};
 use tokio::sync::broadcast;
 use winapi::ctypes::c_void;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::ifdef::NET_LUID;
 use winapi::shared::netioapi::{
-    CancelMibChangeNotify2, CreateIpForwardEntry2, FreeMibTable, GetIpForwardTable2,
-    MibAddInstance, MibDeleteInstance, MibParameterNotification, NotifyRouteChange2,
-    MIB_NOTIFICATION_TYPE, PMIB_IPFORWARD_TABLE2, DeleteIpForwardEntry2,
+    CancelMibChangeNotify2, CreateIpForwardEntry2, DeleteIpForwardEntry2, FreeMibTable,
+    GetIpForwardEntry2, GetIpForwardTable2, MibAddInstance, MibDeleteInstance,
+    MibParameterNotification, NotifyRouteChange2, MIB_NOTIFICATION_TYPE, PMIB_IPFORWARD_TABLE2,
+};
+use winapi::shared::netioapi::{
+    ConvertInterfaceGuidToLuid, ConvertInterfaceLuidToAlias, GetIfEntry2, GetIfTable2,
+    GetIpInterfaceEntry, InitializeIpInterfaceEntry, SetIpInterfaceEntry, MIB_IF_ROW2,
+    MIB_IPINTERFACE_ROW, PMIB_IF_TABLE2,
 };
 use winapi::shared::netioapi::{InitializeIpForwardEntry, MIB_IPFORWARD_ROW2};
 use winapi::shared::ntdef::HANDLE;
-use winapi::shared::winerror::ERROR_SUCCESS;
+use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_SUCCESS};
 use winapi::shared::ws2def::{AF_INET, AF_INET6, AF_UNSPEC};
+use winapi::shared::ws2ipdef::SOCKADDR_INET;
 use winapi::shared::wtypesbase::BOOLEAN;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::iphlpapi::GetBestInterfaceEx;
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+use winapi::um::securitybaseapi::GetTokenInformation;
+use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+
+use super::persistent_routes;
+use crate::{
+    Capabilities, DumpFilter, ElevationRequired, HandleBuilder, Interface, InterfaceForwarding,
+    InterfaceType, IpVersion, Route, RouteChange, RouteKind, RouteOrigin, RouteStore,
+};
+
+// NL_ROUTE_PROTOCOL
+const MIB_IPPROTO_LOCAL: u32 = 2;
+const MIB_IPPROTO_NETMGMT: u32 = 3;
+const MIB_IPPROTO_NT_AUTOSTATIC: u32 = 10002;
+const MIB_IPPROTO_NT_STATIC: u32 = 10006;
+const MIB_IPPROTO_NT_STATIC_NON_DOD: u32 = 10007;
+
+// NL_ROUTE_ORIGIN
+const NLRO_DHCP: u32 = 2;
+const NLRO_ROUTER_ADVERTISEMENT: u32 = 3;
 
-use crate::{Route, RouteChange};
+// ValidLifetime and PreferredLifetime are in seconds, with this value meaning infinite
+const INFINITE_LIFETIME: u32 = 0xffffffff;
+
+fn lifetime_from_row(lifetime: u32) -> Option<Duration> {
+    (lifetime != INFINITE_LIFETIME).then(|| Duration::from_secs(lifetime.into()))
+}
+
+fn lifetime_to_row(lifetime: Option<Duration>) -> u32 {
+    lifetime.map_or(INFINITE_LIFETIME, |lifetime| {
+        lifetime.as_secs().min(INFINITE_LIFETIME as u64 - 1) as u32
+    })
+}
+
+fn route_origin(protocol: u32, origin: u32) -> RouteOrigin {
+    match (origin, protocol) {
+        (NLRO_DHCP, _) => RouteOrigin::Dhcp,
+        (NLRO_ROUTER_ADVERTISEMENT, _) => RouteOrigin::RouterAdvertisement,
+        (_, MIB_IPPROTO_LOCAL) => RouteOrigin::Local,
+        (_, MIB_IPPROTO_NETMGMT) => RouteOrigin::NetMgmt,
+        (_, MIB_IPPROTO_NT_AUTOSTATIC | MIB_IPPROTO_NT_STATIC | MIB_IPPROTO_NT_STATIC_NON_DOD) => {
+            RouteOrigin::Static
+        }
+        (_, protocol) => RouteOrigin::Other(protocol),
+    }
+}
 
 unsafe fn row_to_route(row: *mut MIB_IPFORWARD_ROW2) -> Option<Route> {
     let dst_family = (*row).DestinationPrefix.Prefix.si_family();
@@ -49,6 +108,14 @@ unsafe fn row_to_route(row: *mut MIB_IPFORWARD_ROW2) -> Option<Route> {
         .with_metric((*row).Metric);
 
     route.gateway = gateway;
+    route.origin = route_origin((*row).Protocol as u32, (*row).Origin as u32);
+    route.publish = (*row).Publish != 0;
+    route.immortal = (*row).Immortal != 0;
+    route.loopback = (*row).Loopback != 0;
+    route.valid_lifetime = lifetime_from_row((*row).ValidLifetime);
+    route.preferred_lifetime = lifetime_from_row((*row).PreferredLifetime);
+    // the interface may already be gone when handling a delete notification
+    route.alias = interface_alias(&(*row).InterfaceLuid).ok();
     Some(route)
 }
 
@@ -59,13 +126,27 @@ unsafe extern "system" fn callback(
 ) {
     let tx = &*(callercontext as *const broadcast::Sender<RouteChange>);
 
-    if let Some(route) = row_to_route(row) {
+    if let Some(mut route) = row_to_route(row) {
+        // The row doesn't say which store it belongs to. A route that was added or changed but
+        // isn't in the active store, or that was deleted but is still in it, concerns the
+        // persistent store.
+        let mut active_row = *row;
+        let active = GetIpForwardEntry2(&mut active_row) == ERROR_SUCCESS;
+        let deleted = notification_type == MibDeleteInstance;
+        route.store = if active != deleted {
+            RouteStore::Active
+        } else {
+            RouteStore::Persistent
+        };
+
         let event = match notification_type {
             n if n == MibParameterNotification => RouteChange::Change(route),
             n if n == MibAddInstance => RouteChange::Add(route),
             n if n == MibDeleteInstance => RouteChange::Delete(route),
             _ => return,
         };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(change = ?event, "route changed");
         _ = tx.send(event)
     }
 }
@@ -74,6 +155,8 @@ fn code_to_error(code: u32, msg: &str) -> io::Error {
     let kind = match code {
         2 => io::ErrorKind::NotFound,
         5 => io::ErrorKind::PermissionDenied,
+        // ERROR_NOT_READY, e.g. while an adapter is being set up, see RetryPolicy
+        21 => io::ErrorKind::WouldBlock,
         87 => io::ErrorKind::InvalidInput,
         5010 => io::ErrorKind::AlreadyExists,
         1168 => io::ErrorKind::NotFound,
@@ -82,48 +165,336 @@ fn code_to_error(code: u32, msg: &str) -> io::Error {
     io::Error::new(kind, format!("{}: {}", code, msg))
 }
 
+fn elevation_required() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, ElevationRequired)
+}
+
+// like `code_to_error`, for operations that need an elevated process
+fn modify_error(code: u32, msg: &str) -> io::Error {
+    if code == ERROR_ACCESS_DENIED {
+        return elevation_required();
+    }
+    code_to_error(code, msg)
+}
+
+fn elevation_error(e: io::Error) -> io::Error {
+    match e.raw_os_error() {
+        Some(code) if code as u32 == ERROR_ACCESS_DENIED => elevation_required(),
+        _ => e,
+    }
+}
+
+fn list_routes(family: u16) -> io::Result<Vec<Route>> {
+    let mut ptable: PMIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+
+    let ret = unsafe { GetIpForwardTable2(family, &mut ptable) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "Error getting table: {}"));
+    }
+
+    let prows = unsafe {
+        std::ptr::slice_from_raw_parts(
+            &(*ptable).Table as *const _ as *const MIB_IPFORWARD_ROW2,
+            (*ptable).NumEntries as usize,
+        )
+    };
+
+    let entries = unsafe { (*ptable).NumEntries };
+    let mut res = (0..entries)
+        .map(|idx| unsafe { (*prows)[idx as usize] })
+        .filter_map(|mut row| unsafe { row_to_route(&mut row) })
+        .collect::<Vec<_>>();
+    unsafe { FreeMibTable(ptable as *mut _ as *mut _) };
+
+    // reading the stored routes shouldn't make listing fail
+    let stored = persistent_routes::list().unwrap_or_default();
+    for route in &mut res {
+        route.persistent = stored
+            .iter()
+            .any(|stored| persistent_routes::same_route(stored, route));
+    }
+    Ok(res)
+}
+
+// NDIS_IF_MAX_STRING_SIZE + 1
+const IF_MAX_ALIAS_LEN: usize = 257;
+
+fn interface_alias(luid: &NET_LUID) -> io::Result<String> {
+    let mut alias = [0u16; IF_MAX_ALIAS_LEN];
+    let ret = unsafe { ConvertInterfaceLuidToAlias(luid, alias.as_mut_ptr(), alias.len()) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "error getting interface alias"));
+    }
+    let len = alias.iter().position(|c| *c == 0).unwrap_or(alias.len());
+    Ok(String::from_utf16_lossy(&alias[..len]))
+}
+
+// IFTYPE
+const IF_TYPE_ETHERNET_CSMACD: u32 = 6;
+const IF_TYPE_PPP: u32 = 23;
+const IF_TYPE_SOFTWARE_LOOPBACK: u32 = 24;
+const IF_TYPE_PROP_VIRTUAL: u32 = 53;
+const IF_TYPE_IEEE80211: u32 = 71;
+const IF_TYPE_TUNNEL: u32 = 131;
+
+// IF_OPER_STATUS
+const IF_OPER_STATUS_UP: u32 = 1;
+
+fn interface_type(if_type: u32) -> InterfaceType {
+    match if_type {
+        IF_TYPE_ETHERNET_CSMACD => InterfaceType::Ethernet,
+        IF_TYPE_IEEE80211 => InterfaceType::Wifi,
+        IF_TYPE_PPP => InterfaceType::Ppp,
+        // wintun and tap adapters report themselves as proprietary virtual interfaces
+        IF_TYPE_TUNNEL | IF_TYPE_PROP_VIRTUAL => InterfaceType::Tunnel,
+        IF_TYPE_SOFTWARE_LOOPBACK => InterfaceType::Loopback,
+        other => InterfaceType::Other(other),
+    }
+}
+
+fn row_to_interface(row: &MIB_IF_ROW2) -> Interface {
+    let len = row
+        .Alias
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(row.Alias.len());
+    Interface {
+        ifindex: row.InterfaceIndex,
+        luid: unsafe { std::mem::transmute(row.InterfaceLuid) },
+        alias: String::from_utf16_lossy(&row.Alias[..len]),
+        kind: interface_type(row.Type as u32),
+        up: row.OperStatus as u32 == IF_OPER_STATUS_UP,
+    }
+}
+
+fn interface(ifindex: u32) -> io::Result<Interface> {
+    let mut row: MIB_IF_ROW2 = unsafe { std::mem::zeroed() };
+    row.InterfaceIndex = ifindex;
+    let ret = unsafe { GetIfEntry2(&mut row) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "error getting interface"));
+    }
+    Ok(row_to_interface(&row))
+}
+
+fn guid_from_u128(guid: u128) -> GUID {
+    GUID {
+        Data1: (guid >> 96) as u32,
+        Data2: (guid >> 80) as u16,
+        Data3: (guid >> 64) as u16,
+        Data4: (guid as u64).to_be_bytes(),
+    }
+}
+
+fn guid_to_luid(guid: u128) -> io::Result<NET_LUID> {
+    let mut luid: NET_LUID = unsafe { std::mem::zeroed() };
+    let ret = unsafe { ConvertInterfaceGuidToLuid(&guid_from_u128(guid), &mut luid) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "error getting interface luid"));
+    }
+    Ok(luid)
+}
+
+fn route_to_row(route: &Route) -> io::Result<MIB_IPFORWARD_ROW2> {
+    let mut row: MIB_IPFORWARD_ROW2 = route.into();
+    if let (None, Some(guid)) = (route.luid, route.adapter_guid) {
+        row.InterfaceLuid = guid_to_luid(guid)?;
+    }
+    Ok(row)
+}
+
+fn list_interfaces() -> io::Result<Vec<Interface>> {
+    let mut ptable: PMIB_IF_TABLE2 = std::ptr::null_mut();
+    let ret = unsafe { GetIfTable2(&mut ptable) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "error getting interfaces"));
+    }
+
+    let rows = unsafe {
+        std::slice::from_raw_parts(
+            &(*ptable).Table as *const _ as *const MIB_IF_ROW2,
+            (*ptable).NumEntries as usize,
+        )
+    };
+    let res = rows.iter().map(row_to_interface).collect();
+    unsafe { FreeMibTable(ptable as *mut _ as *mut _) };
+    Ok(res)
+}
+
+fn ip_interface_row(ifindex: u32, version: IpVersion) -> io::Result<MIB_IPINTERFACE_ROW> {
+    let mut row: MIB_IPINTERFACE_ROW = unsafe { std::mem::zeroed() };
+    unsafe { InitializeIpInterfaceEntry(&mut row) };
+    row.Family = match version {
+        IpVersion::V4 => AF_INET,
+        IpVersion::V6 => AF_INET6,
+    } as u16;
+    row.InterfaceIndex = ifindex;
+
+    let ret = unsafe { GetIpInterfaceEntry(&mut row) };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "error getting interface"));
+    }
+    Ok(row)
+}
+
+fn set_ip_interface_row(row: &mut MIB_IPINTERFACE_ROW) -> io::Result<()> {
+    // SetIpInterfaceEntry rejects ipv4 rows with the site prefix length returned by the getter
+    if row.Family == AF_INET as u16 {
+        row.SitePrefixLength = 0;
+    }
+    let ret = unsafe { SetIpInterfaceEntry(row) };
+    if ret != ERROR_SUCCESS {
+        return Err(modify_error(ret, "error setting interface"));
+    }
+    Ok(())
+}
+
+// not exported by winapi
+#[link(name = "iphlpapi")]
+extern "system" {
+    fn GetCurrentThreadCompartmentId() -> u32;
+    fn SetCurrentThreadCompartmentId(compartment_id: u32) -> u32;
+}
+
+// The ip helper functions operate on the network compartment of the calling thread. The guard
+// moves the thread into a compartment for the duration of a call and restores it on drop.
+struct CompartmentGuard(Option<u32>);
+
+impl CompartmentGuard {
+    fn enter(compartment: Option<u32>) -> io::Result<Self> {
+        let Some(compartment) = compartment else {
+            return Ok(Self(None));
+        };
+        let previous = unsafe { GetCurrentThreadCompartmentId() };
+        if previous == compartment {
+            return Ok(Self(None));
+        }
+        let ret = unsafe { SetCurrentThreadCompartmentId(compartment) };
+        if ret != ERROR_SUCCESS {
+            return Err(code_to_error(ret, "error entering compartment"));
+        }
+        Ok(Self(Some(previous)))
+    }
+}
+
+impl Drop for CompartmentGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0 {
+            unsafe { SetCurrentThreadCompartmentId(previous) };
+        }
+    }
+}
+
+fn notify_route_change(tx: &mut broadcast::Sender<RouteChange>) -> io::Result<HANDLE> {
+    let mut handle: HANDLE = std::ptr::null_mut();
+    let ret = unsafe {
+        NotifyRouteChange2(
+            AF_UNSPEC as u16,
+            Some(callback),
+            (tx as *mut _) as *mut _,
+            BOOLEAN::from(false),
+            &mut handle,
+        )
+    };
+    if ret != ERROR_SUCCESS {
+        return Err(code_to_error(ret, "Error creating listener: {}"));
+    }
+    Ok(handle)
+}
+
+fn is_elevated() -> io::Result<bool> {
+    let mut token: HANDLE = std::ptr::null_mut();
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut elevation: TOKEN_ELEVATION = unsafe { std::mem::zeroed() };
+    let mut len = 0;
+    let ret = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut _ as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        )
+    };
+    let res = if ret == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(elevation.TokenIsElevated != 0)
+    };
+    unsafe { CloseHandle(token) };
+    res
+}
+
 pub(crate) struct Handle {
-    handle: HANDLE,
+    // the change notification, unless the handle doesn't listen
+    handle: Option<HANDLE>,
+    compartment: Option<u32>,
+    #[cfg_attr(not(feature = "events"), allow(dead_code))]
     tx: broadcast::Sender<RouteChange>,
     _tx: Box<broadcast::Sender<RouteChange>>,
 }
 
 impl Handle {
-    pub fn new() -> io::Result<Self> {
-        let mut handle: HANDLE = std::ptr::null_mut();
-
-        let (tx, _) = broadcast::channel::<RouteChange>(16);
+    pub fn new(config: &HandleBuilder) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel::<RouteChange>(config.event_capacity);
         let mut tx_clone = Box::new(tx.clone());
 
         // TODO we could wait until `route_listen_stream` is called to initialize this
-        let ret = unsafe {
-            NotifyRouteChange2(
-                AF_UNSPEC as u16,
-                Some(callback),
-                (tx_clone.as_mut() as *mut _) as *mut _,
-                BOOLEAN::from(false),
-                &mut handle,
-            )
+        let handle = match config.listen {
+            true => {
+                // change notifications are only delivered for the compartment they were
+                // registered in
+                let _compartment = CompartmentGuard::enter(config.compartment)?;
+                Some(notify_route_change(tx_clone.as_mut())?)
+            }
+            false => None,
         };
-        if ret != ERROR_SUCCESS {
-            return Err(code_to_error(ret, "Error creating listener: {}"));
-        }
         Ok(Self {
             handle,
+            compartment: config.compartment,
             tx,
             _tx: tx_clone,
         })
     }
 
-    pub(crate) fn route_listen_stream(&self) -> impl Stream<Item = RouteChange> {
+    pub(crate) async fn close(self) -> io::Result<()> {
+        // CancelMibChangeNotify2 waits for running callbacks to return
+        drop(self);
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> io::Result<Capabilities> {
+        let elevated = is_elevated()?;
+        Ok(Capabilities {
+            modify_routes: elevated,
+            modify_rules: false,
+        })
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn route_listen_stream(
+        &self,
+        counters: Arc<Counters>,
+    ) -> impl futures::Stream<Item = RouteChange> {
         let mut rx = self.tx.subscribe();
         stream! {
             loop {
                 match rx.recv().await {
-                    Ok(ev) => yield ev,
+                    Ok(ev) => {
+                        counters.event_received();
+                        yield ev
+                    }
                     Err(e) => match e {
                         broadcast::error::RecvError::Closed => break,
-                        broadcast::error::RecvError::Lagged(_) => continue,
+                        broadcast::error::RecvError::Lagged(skipped) => {
+                            counters.events_dropped(skipped);
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(skipped, "route change stream fell behind");
+                            continue;
+                        }
                     }
                 }
             }
@@ -131,11 +502,16 @@ impl Handle {
     }
 
     pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
-        let row: MIB_IPFORWARD_ROW2 = route.into();
+        if route.persistent {
+            persistent_routes::remove(route).map_err(elevation_error)?;
+        }
+
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let row = route_to_row(route)?;
 
         let err = unsafe { DeleteIpForwardEntry2(&row) };
         if err != ERROR_SUCCESS {
-            return Err(code_to_error(err, "error deleting entry"));
+            return Err(modify_error(err, "error deleting entry"));
         }
         Ok(())
     }
@@ -155,44 +531,165 @@ impl Handle {
     }
 
     pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
-        let mut ptable: PMIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
-
-        let ret = unsafe { GetIpForwardTable2(AF_UNSPEC as u16, &mut ptable) };
-        if ret != ERROR_SUCCESS {
-            return Err(code_to_error(ret, "Error getting table: {}"));
-        }
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        list_routes(AF_UNSPEC as u16)
+    }
 
-        let prows = unsafe {
-            std::ptr::slice_from_raw_parts(
-                &(*ptable).Table as *const _ as *const MIB_IPFORWARD_ROW2,
-                (*ptable).NumEntries as usize,
-            )
+    pub(crate) async fn list_filtered(&self, filter: &DumpFilter) -> io::Result<Vec<Route>> {
+        let family = match filter.family {
+            Some(IpVersion::V4) => AF_INET,
+            Some(IpVersion::V6) => AF_INET6,
+            None => AF_UNSPEC,
         };
-
-        let entries = unsafe { (*ptable).NumEntries };
-        let res = (0..entries)
-            .map(|idx| unsafe { (*prows)[idx as usize] })
-            .filter_map(|mut row| unsafe { row_to_route(&mut row) })
-            .collect::<Vec<_>>();
-        unsafe { FreeMibTable(ptable as *mut _ as *mut _) };
-        Ok(res)
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let mut routes = list_routes(family as u16)?;
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
     }
 
     pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
-        let row: MIB_IPFORWARD_ROW2 = route.into();
+        if route.kind != RouteKind::Unicast {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "routes that drop packets are not supported on Windows",
+            ));
+        }
+
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let row = route_to_row(route)?;
 
         let err = unsafe { CreateIpForwardEntry2(&row) };
         if err != ERROR_SUCCESS {
-            return Err(code_to_error(err, "error creating entry"));
+            return Err(modify_error(err, "error creating entry"));
+        }
+
+        if route.persistent {
+            if let Err(e) = persistent_routes::store(route) {
+                unsafe { DeleteIpForwardEntry2(&row) };
+                return Err(elevation_error(e));
+            }
         }
         Ok(())
     }
+
+    pub(crate) async fn best_interface(&self, destination: IpAddr) -> io::Result<Interface> {
+        let mut addr: SOCKADDR_INET = unsafe { std::mem::zeroed() };
+        match destination {
+            IpAddr::V4(ip) => unsafe {
+                *addr.si_family_mut() = AF_INET as u16;
+                addr.Ipv4_mut().sin_addr = std::mem::transmute(ip.octets());
+            },
+            IpAddr::V6(ip) => unsafe {
+                *addr.si_family_mut() = AF_INET6 as u16;
+                addr.Ipv6_mut().sin6_addr = std::mem::transmute(ip.octets());
+            },
+        }
+
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let mut ifindex = 0;
+        let ret = unsafe { GetBestInterfaceEx(&mut addr as *mut _ as *mut _, &mut ifindex) };
+        if ret != ERROR_SUCCESS {
+            return Err(code_to_error(ret, "error getting best interface"));
+        }
+        interface(ifindex)
+    }
+
+    pub(crate) async fn interface(&self, ifindex: u32) -> io::Result<Interface> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        interface(ifindex)
+    }
+
+    pub(crate) async fn interface_by_guid(&self, guid: u128) -> io::Result<Interface> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let mut row: MIB_IF_ROW2 = unsafe { std::mem::zeroed() };
+        row.InterfaceLuid = guid_to_luid(guid)?;
+        let ret = unsafe { GetIfEntry2(&mut row) };
+        if ret != ERROR_SUCCESS {
+            return Err(code_to_error(ret, "error getting interface"));
+        }
+        Ok(row_to_interface(&row))
+    }
+
+    pub(crate) async fn list_interfaces(&self) -> io::Result<Vec<Interface>> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        list_interfaces()
+    }
+
+    pub(crate) async fn interface_metric(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+    ) -> io::Result<u32> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        Ok(ip_interface_row(ifindex, version)?.Metric)
+    }
+
+    // The metric set on the interface, `None` if the system picks one.
+    pub(crate) async fn interface_metric_setting(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+    ) -> io::Result<Option<u32>> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let row = ip_interface_row(ifindex, version)?;
+        Ok((row.UseAutomaticMetric == 0).then_some(row.Metric))
+    }
+
+    pub(crate) async fn set_interface_metric(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+        metric: Option<u32>,
+    ) -> io::Result<()> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let mut row = ip_interface_row(ifindex, version)?;
+        match metric {
+            Some(metric) => {
+                row.UseAutomaticMetric = 0;
+                row.Metric = metric;
+            }
+            None => row.UseAutomaticMetric = 1,
+        }
+        set_ip_interface_row(&mut row)
+    }
+
+    pub(crate) async fn interface_forwarding(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+    ) -> io::Result<InterfaceForwarding> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let row = ip_interface_row(ifindex, version)?;
+        Ok(InterfaceForwarding {
+            forwarding: row.ForwardingEnabled != 0,
+            weak_host_send: row.WeakHostSend != 0,
+            weak_host_receive: row.WeakHostReceive != 0,
+        })
+    }
+
+    pub(crate) async fn set_interface_forwarding(
+        &self,
+        ifindex: u32,
+        version: IpVersion,
+        config: &InterfaceForwarding,
+    ) -> io::Result<()> {
+        let _compartment = CompartmentGuard::enter(self.compartment)?;
+        let mut row = ip_interface_row(ifindex, version)?;
+        row.ForwardingEnabled = config.forwarding.into();
+        row.WeakHostSend = config.weak_host_send.into();
+        row.WeakHostReceive = config.weak_host_receive.into();
+        set_ip_interface_row(&mut row)
+    }
+
+    pub(crate) async fn list_persistent(&self) -> io::Result<Vec<Route>> {
+        persistent_routes::list()
+    }
 }
 
 impl Drop for Handle {
     fn drop(&mut self) {
-        unsafe {
-            CancelMibChangeNotify2(self.handle);
+        if let Some(handle) = self.handle {
+            unsafe { CancelMibChangeNotify2(handle) };
         }
     }
 }
@@ -257,6 +754,27 @@ impl From<&Route> for MIB_IPFORWARD_ROW2 {
             row.Metric = metric;
         }
 
+        row.Publish = route.publish.into();
+        row.Immortal = route.immortal.into();
+        row.Loopback = route.loopback.into();
+        row.ValidLifetime = lifetime_to_row(route.valid_lifetime);
+        row.PreferredLifetime = lifetime_to_row(route.preferred_lifetime);
+
         row
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_guids() {
+        // {6BA7B810-9DAD-11D1-80B4-00C04FD430C8}
+        let guid = guid_from_u128(0x6ba7b8109dad11d180b400c04fd430c8);
+        assert_eq!(guid.Data1, 0x6ba7b810);
+        assert_eq!(guid.Data2, 0x9dad);
+        assert_eq!(guid.Data3, 0x11d1);
+        assert_eq!(guid.Data4, [0x80, 0xb4, 0x00, 0xc0, 0x4f, 0xd4, 0x30, 0xc8]);
+    }
+}
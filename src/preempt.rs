@@ -0,0 +1,133 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{DumpFilter, Handle, IpVersion, Result, Route};
+
+// OpenBSD reserves priority 0 for the kernel.
+#[cfg(target_os = "openbsd")]
+const MIN_METRIC: u32 = 1;
+#[cfg(not(target_os = "openbsd"))]
+const MIN_METRIC: u32 = 0;
+
+fn no_lower_metric() -> crate::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "a default route already has the lowest possible metric",
+    )
+    .into()
+}
+
+/// The default route added by [`Handle::preempt_default_route`], removed again with
+/// [`Handle::remove_preempting_route`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreemptingRoute {
+    /// The added default route.
+    pub route: Route,
+    /// The setting the interface metric of the route had before it was lowered to 1, `None`
+    /// inside if the system picked the metric. Only set on Windows, if the metric was lowered.
+    pub previous_metric: Option<Option<u32>>,
+}
+
+impl Handle {
+    /// Add a default route through `gateway` and the interface `ifindex` that is preferred over
+    /// every existing default route of the same ip version, which is left in place, e.g. to
+    /// switch to a backup uplink and back with [`Handle::remove_preempting_route`].
+    ///
+    /// The route metric is one lower than the lowest metric of the existing default routes. On
+    /// Windows the interface metrics are added to the route metrics before comparing them, and
+    /// the interface metric of `ifindex` is lowered to 1 if it is too high to win with any route
+    /// metric. It is restored if the route can't be added. On Linux only the main table is
+    /// considered.
+    pub async fn preempt_default_route(
+        &self,
+        gateway: IpAddr,
+        ifindex: u32,
+    ) -> Result<PreemptingRoute> {
+        let version = IpVersion::of(&gateway);
+        let destination = match version {
+            IpVersion::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpVersion::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let filter = DumpFilter::new().with_family(version);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let filter = filter.with_table(254);
+
+        let mut best: Option<u32> = None;
+        for route in self.list_filtered(&filter).await? {
+            if route.prefix != 0 {
+                continue;
+            }
+            let metric = route.metric.unwrap_or(MIN_METRIC);
+            #[cfg(target_os = "windows")]
+            let metric = match route.ifindex {
+                Some(ifindex) => {
+                    metric.saturating_add(self.interface_metric(ifindex, version).await?)
+                }
+                None => metric,
+            };
+            best = Some(best.map_or(metric, |best| best.min(metric)));
+        }
+
+        let route = Route::new(destination, 0)
+            .with_gateway(gateway)
+            .with_ifindex(ifindex);
+        #[cfg_attr(not(target_os = "windows"), allow(unused_mut))]
+        let mut previous_metric = None;
+        let route = match best {
+            None => route,
+            Some(best) if best <= MIN_METRIC => return Err(no_lower_metric()),
+            #[cfg(not(target_os = "windows"))]
+            Some(best) => route.with_metric(best - 1),
+            #[cfg(target_os = "windows")]
+            Some(best) => {
+                let mut interface_metric = self.interface_metric(ifindex, version).await?;
+                if interface_metric >= best {
+                    // a route metric of 0 is needed on top of an interface metric of 1
+                    if best <= 1 {
+                        return Err(no_lower_metric());
+                    }
+                    let previous = self.interface_metric_setting(ifindex, version).await?;
+                    self.set_interface_metric(ifindex, version, Some(1)).await?;
+                    previous_metric = Some(previous);
+                    interface_metric = 1;
+                }
+                route.with_metric(best - 1 - interface_metric)
+            }
+        };
+        let preempting = PreemptingRoute {
+            route,
+            previous_metric,
+        };
+        if let Err(e) = self.add(&preempting.route).await {
+            // errors while restoring are ignored in favor of the one adding the route
+            let _ = self.restore_preempted_metric(&preempting).await;
+            return Err(e);
+        }
+        Ok(preempting)
+    }
+
+    /// Remove a route added by [`Handle::preempt_default_route`], letting the default routes it
+    /// preempted win again, and restore the interface metric it lowered.
+    pub async fn remove_preempting_route(&self, preempting: &PreemptingRoute) -> Result<()> {
+        self.delete(&preempting.route).await?;
+        self.restore_preempted_metric(preempting).await
+    }
+
+    #[cfg(target_os = "windows")]
+    async fn restore_preempted_metric(&self, preempting: &PreemptingRoute) -> Result<()> {
+        match (preempting.previous_metric, preempting.route.ifindex) {
+            (Some(metric), Some(ifindex)) => {
+                let version = IpVersion::of(&preempting.route.destination);
+                self.set_interface_metric(ifindex, version, metric).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn restore_preempted_metric(&self, _preempting: &PreemptingRoute) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+//! A Python extension module built with pyo3, for network automation scripts.
+//!
+//! The methods of `Handle` return awaitables for asyncio and the route changes are an async
+//! iterator. The background tasks run on the tokio runtime of `pyo3-async-runtimes`. Build the
+//! module with `maturin build --features python`.
+//!
+//! ```python
+//! import asyncio, net_route
+//!
+//! async def main():
+//!     handle = net_route.Handle()
+//!     for route in await handle.list():
+//!         print(route)
+//!     async for change in handle.listen():
+//!         print(change.kind, change.route)
+//!
+//! asyncio.run(main())
+//! ```
+
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::{lock::Mutex, Stream, StreamExt};
+use pyo3::exceptions::{
+    PyFileExistsError, PyLookupError, PyNotImplementedError, PyOSError, PyPermissionError,
+    PyStopAsyncIteration, PyTimeoutError, PyValueError,
+};
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::{future_into_py, get_runtime};
+
+use crate::{Error, HandleBuilder, Route, RouteChange};
+
+fn to_py_err(e: Error) -> PyErr {
+    let message = match &e {
+        Error::Unsupported(reason) | Error::InvalidRoute(reason) => reason.clone(),
+        e => e.to_string(),
+    };
+    match e.root() {
+        Error::NotFound(_) => PyLookupError::new_err(message),
+        Error::AlreadyExists(_) => PyFileExistsError::new_err(message),
+        Error::PermissionDenied(_) => PyPermissionError::new_err(message),
+        Error::Unsupported(_) => PyNotImplementedError::new_err(message),
+        Error::InvalidRoute(_) => PyValueError::new_err(message),
+        Error::TimedOut(_) => PyTimeoutError::new_err(message),
+        _ => PyOSError::new_err(message),
+    }
+}
+
+/// A route, see [`Route`]. The addresses are `ipaddress` objects.
+#[pyclass(name = "Route", module = "net_route")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyRoute {
+    #[pyo3(get, set)]
+    pub destination: IpAddr,
+    #[pyo3(get, set)]
+    pub prefix: u8,
+    #[pyo3(get, set)]
+    pub gateway: Option<IpAddr>,
+    #[pyo3(get, set)]
+    pub ifindex: Option<u32>,
+    /// The routing table on Linux, ignored elsewhere.
+    #[pyo3(get, set)]
+    pub table: Option<u32>,
+}
+
+#[pymethods]
+impl PyRoute {
+    #[new]
+    #[pyo3(signature = (destination, prefix, gateway = None, ifindex = None, table = None))]
+    fn new(
+        destination: IpAddr,
+        prefix: u8,
+        gateway: Option<IpAddr>,
+        ifindex: Option<u32>,
+        table: Option<u32>,
+    ) -> Self {
+        Self {
+            destination,
+            prefix,
+            gateway,
+            ifindex,
+            table,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Route({}/{}, gateway={:?}, ifindex={:?}, table={:?})",
+            self.destination, self.prefix, self.gateway, self.ifindex, self.table
+        )
+    }
+}
+
+impl From<&Route> for PyRoute {
+    fn from(route: &Route) -> Self {
+        Self {
+            destination: route.destination,
+            prefix: route.prefix,
+            gateway: route.gateway,
+            ifindex: route.ifindex,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            table: Some(route.table),
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            table: None,
+        }
+    }
+}
+
+impl From<&PyRoute> for Route {
+    fn from(route: &PyRoute) -> Self {
+        let mut out = Route::new(route.destination, route.prefix);
+        out.gateway = route.gateway;
+        out.ifindex = route.ifindex;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(table) = route.table {
+            out.table = table;
+        }
+        out
+    }
+}
+
+/// A policy routing rule, see [`Rule`](crate::Rule). Networks are `(address, prefix)` tuples.
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+#[pyclass(name = "Rule", module = "net_route")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PyRule {
+    #[pyo3(get, set)]
+    pub src: Option<(IpAddr, u8)>,
+    #[pyo3(get, set)]
+    pub dst: Option<(IpAddr, u8)>,
+    #[pyo3(get, set)]
+    pub input_interface: Option<String>,
+    #[pyo3(get, set)]
+    pub output_interface: Option<String>,
+    #[pyo3(get, set)]
+    pub table_id: Option<u32>,
+    #[pyo3(get, set)]
+    pub priority: Option<u32>,
+    #[pyo3(get, set)]
+    pub fw_mark: Option<u32>,
+    #[pyo3(get, set)]
+    pub fw_mask: Option<u32>,
+    #[pyo3(get, set)]
+    pub v6: bool,
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+#[pymethods]
+impl PyRule {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl From<&crate::Rule> for PyRule {
+    fn from(rule: &crate::Rule) -> Self {
+        Self {
+            src: rule.src,
+            dst: rule.dst,
+            input_interface: rule.input_interface.clone(),
+            output_interface: rule.output_interface.clone(),
+            table_id: rule.table_id,
+            priority: rule.priority,
+            fw_mark: rule.fw_mark_mask.map(|(mark, _)| mark),
+            fw_mask: rule.fw_mark_mask.map(|(_, mask)| mask),
+            v6: rule.v6,
+        }
+    }
+}
+
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+impl From<&PyRule> for crate::Rule {
+    fn from(rule: &PyRule) -> Self {
+        crate::Rule {
+            src: rule.src,
+            dst: rule.dst,
+            input_interface: rule.input_interface.clone(),
+            output_interface: rule.output_interface.clone(),
+            table_id: rule.table_id,
+            priority: rule.priority,
+            fw_mark_mask: rule
+                .fw_mark
+                .map(|mark| (mark, rule.fw_mask.unwrap_or(u32::MAX))),
+            v6: rule.v6,
+            ..Default::default()
+        }
+    }
+}
+
+/// A change of the routing table, `kind` is one of `"add"`, `"delete"` and `"change"`.
+#[pyclass(name = "RouteChange", module = "net_route", get_all)]
+#[derive(Debug, Clone)]
+pub struct PyRouteChange {
+    pub kind: &'static str,
+    pub route: PyRoute,
+}
+
+#[pymethods]
+impl PyRouteChange {
+    fn __repr__(&self) -> String {
+        format!("RouteChange({}, {})", self.kind, self.route.__repr__())
+    }
+}
+
+impl From<RouteChange> for PyRouteChange {
+    fn from(change: RouteChange) -> Self {
+        let (kind, route) = match &change {
+            RouteChange::Add(route) => ("add", route),
+            RouteChange::Delete(route) => ("delete", route),
+            RouteChange::Change(route) => ("change", route),
+        };
+        Self {
+            kind,
+            route: route.into(),
+        }
+    }
+}
+
+type ChangeStream = Pin<Box<dyn Stream<Item = RouteChange> + Send>>;
+
+/// The async iterator returned by `Handle.listen`.
+#[pyclass(module = "net_route")]
+pub struct RouteChanges(Arc<Mutex<ChangeStream>>);
+
+#[pymethods]
+impl RouteChanges {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let stream = self.0.clone();
+        future_into_py(py, async move {
+            match stream.lock().await.next().await {
+                Some(change) => Ok(PyRouteChange::from(change)),
+                None => Err(PyStopAsyncIteration::new_err(())),
+            }
+        })
+    }
+}
+
+/// A handle to the routing table, see [`Handle`](crate::Handle).
+#[pyclass(name = "Handle", module = "net_route")]
+pub struct PyHandle(crate::Handle);
+
+#[pymethods]
+impl PyHandle {
+    #[new]
+    fn new() -> PyResult<Self> {
+        let handle = HandleBuilder::new()
+            .with_runtime(get_runtime().handle().clone())
+            .build()
+            .map_err(to_py_err)?;
+        Ok(Self(handle))
+    }
+
+    fn list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            let routes = handle.list().await.map_err(to_py_err)?;
+            Ok(routes.iter().map(PyRoute::from).collect::<Vec<_>>())
+        })
+    }
+
+    fn default_route<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            let route = handle.default_route().await.map_err(to_py_err)?;
+            Ok(route.as_ref().map(PyRoute::from))
+        })
+    }
+
+    fn add<'py>(&self, py: Python<'py>, route: PyRoute) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            handle.add(&Route::from(&route)).await.map_err(to_py_err)
+        })
+    }
+
+    fn delete<'py>(&self, py: Python<'py>, route: PyRoute) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            handle.delete(&Route::from(&route)).await.map_err(to_py_err)
+        })
+    }
+
+    /// Iterate over the changes of the routing table with `async for`.
+    fn listen(&self) -> RouteChanges {
+        RouteChanges(Arc::new(Mutex::new(Box::pin(self.0.route_listen_stream()))))
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    fn list_rules<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            let rules = handle.list_rules().await.map_err(to_py_err)?;
+            Ok(rules.iter().map(PyRule::from).collect::<Vec<_>>())
+        })
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    fn add_rule<'py>(&self, py: Python<'py>, rule: PyRule) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            let rules = vec![crate::Rule::from(&rule)];
+            handle.add_rules(rules).await.map_err(to_py_err)
+        })
+    }
+
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    fn delete_rule<'py>(&self, py: Python<'py>, rule: PyRule) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.0.clone();
+        future_into_py(py, async move {
+            let rules = vec![crate::Rule::from(&rule)];
+            handle.delete_rules(rules).await.map_err(to_py_err)
+        })
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "net_route")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHandle>()?;
+    m.add_class::<PyRoute>()?;
+    m.add_class::<PyRouteChange>()?;
+    m.add_class::<RouteChanges>()?;
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    m.add_class::<PyRule>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_routes() {
+        let route = Route::new("10.14.0.0".parse().unwrap(), 24)
+            .with_gateway("192.1.2.1".parse().unwrap())
+            .with_ifindex(9);
+        let py_route = PyRoute::from(&route);
+        assert_eq!(py_route.gateway, route.gateway);
+
+        let back = Route::from(&py_route);
+        assert_eq!(back.destination, route.destination);
+        assert_eq!(back.gateway, route.gateway);
+        assert_eq!(back.ifindex, Some(9));
+    }
+}
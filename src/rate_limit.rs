@@ -0,0 +1,58 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::timeout::sleep;
+
+// Spaces the changes made through a handle and its clones evenly, see
+// `HandleBuilder::with_rate_limit`.
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    // when the next change may be made
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    // Reserve the time slots of `n` changes, returning when the first one starts.
+    fn reserve(&self, n: usize, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let start = next.map_or(now, |next| next.max(now));
+        *next = Some(start + self.interval * n.try_into().unwrap_or(u32::MAX));
+        start
+    }
+
+    // Wait until `n` more changes may be made.
+    pub(crate) async fn acquire(&self, n: usize) {
+        let now = Instant::now();
+        let start = self.reserve(n, now);
+        if start > now {
+            sleep(start - now).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_spaces_changes() {
+        let limiter = RateLimiter::new(10);
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(1, now), now);
+        assert_eq!(limiter.reserve(3, now), now + Duration::from_millis(100));
+        assert_eq!(limiter.reserve(1, now), now + Duration::from_millis(400));
+
+        // the limit doesn't carry idle time over into bursts
+        let later = now + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(1, later), later);
+    }
+}
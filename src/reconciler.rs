@@ -0,0 +1,122 @@
+use futures::{FutureExt, StreamExt};
+
+use crate::{Error, Handle, PlanOp, Result, RoutePlan};
+
+type DriftHook = Box<dyn FnMut(&[PlanOp]) + Send>;
+type ConflictHook = Box<dyn FnMut(&[PlanOp], &Error) + Send>;
+
+/// Keeps the routes and rules of a [`RoutePlan`] in place, applying the plan again whenever the
+/// routing table changes and the installed state drifted away from it, e.g. because another
+/// program removed a route or the interface was reset.
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> net_route::Result<()> {
+/// use net_route::{Handle, Reconciler, Route, RoutePlan};
+///
+/// let handle = Handle::new()?;
+/// let route = Route::new("10.14.0.0".parse().unwrap(), 24).with_ifindex(9);
+/// let plan = RoutePlan::new().with_route(route);
+/// Reconciler::new(handle, plan)
+///     .with_drift_hook(|ops| println!("repairing {:?}", ops))
+///     .with_conflict_hook(|ops, e| eprintln!("can't apply {:?}: {}", ops, e))
+///     .run()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Reconciler {
+    handle: Handle,
+    plan: RoutePlan,
+    on_drift: Option<DriftHook>,
+    on_conflict: Option<ConflictHook>,
+}
+
+impl Reconciler {
+    pub fn new(handle: Handle, plan: RoutePlan) -> Self {
+        Self {
+            handle,
+            plan,
+            on_drift: None,
+            on_conflict: None,
+        }
+    }
+
+    /// Call `hook` with the changes about to be applied whenever the installed state drifted
+    /// away from the plan.
+    pub fn with_drift_hook(mut self, hook: impl FnMut(&[PlanOp]) + Send + 'static) -> Self {
+        self.on_drift = Some(Box::new(hook));
+        self
+    }
+
+    /// Call `hook` with the changes and the error when applying them fails, e.g. because another
+    /// program keeps installing a conflicting route. The reconciler keeps running and tries
+    /// again on the next change of the routing table.
+    pub fn with_conflict_hook(
+        mut self,
+        hook: impl FnMut(&[PlanOp], &Error) + Send + 'static,
+    ) -> Self {
+        self.on_conflict = Some(Box::new(hook));
+        self
+    }
+
+    /// The desired state.
+    pub fn plan(&self) -> &RoutePlan {
+        &self.plan
+    }
+
+    /// Replace the desired state, which is applied on the next change of the routing table or
+    /// call to [`Reconciler::reconcile`].
+    pub fn set_plan(&mut self, plan: RoutePlan) {
+        self.plan = plan;
+    }
+
+    /// Apply the plan if the installed state differs from it, returning the applied changes.
+    ///
+    /// Failing to apply the changes is reported to the conflict hook and returns the error.
+    pub async fn reconcile(&mut self) -> Result<Vec<PlanOp>> {
+        let ops = self.plan.plan(&self.handle).await?;
+        self.apply(ops).await
+    }
+
+    async fn apply(&mut self, ops: Vec<PlanOp>) -> Result<Vec<PlanOp>> {
+        if ops.is_empty() {
+            return Ok(ops);
+        }
+        if let Some(hook) = &mut self.on_drift {
+            hook(&ops);
+        }
+        // apply exactly the reported changes instead of planning again
+        let mut transaction = self.handle.transaction();
+        for op in &ops {
+            transaction = op.stage(transaction);
+        }
+        if let Err(e) = transaction.commit().await {
+            if let Some(hook) = &mut self.on_conflict {
+                hook(&ops, &e);
+            }
+            return Err(e);
+        }
+        Ok(ops)
+    }
+
+    /// Apply the plan and keep applying it whenever the routing table changes, until the route
+    /// change stream of the handle ends.
+    ///
+    /// Bursts of changes are handled together. Changes that fail to apply are only reported to
+    /// the conflict hook, errors reading the installed state are returned. The future has to be
+    /// polled to follow changes, e.g. by spawning it.
+    pub async fn run(&mut self) -> Result<()> {
+        let changes = self.handle.route_listen_stream();
+        futures::pin_mut!(changes);
+        loop {
+            let ops = self.plan.plan(&self.handle).await?;
+            // already reported to the conflict hook
+            let _ = self.apply(ops).await;
+            if changes.next().await.is_none() {
+                return Ok(());
+            }
+            while let Some(Some(_)) = changes.next().now_or_never() {}
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::net::IpAddr;
+
+use futures::StreamExt;
+
+use crate::{DumpFilter, Error, Handle, IpVersion, Result, Route, RouteChange, RouteKind};
+
+// The default route of `version` the system prefers, the one with the lowest metric on
+// platforms with metrics.
+async fn best_default_route(handle: &Handle, version: IpVersion) -> Result<Option<Route>> {
+    let filter = DumpFilter::new().with_family(version);
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let filter = filter.with_table(254);
+    Ok(handle
+        .list_filtered(&filter)
+        .await?
+        .into_iter()
+        .filter(|route| {
+            route.prefix == 0
+                && route.kind == RouteKind::Unicast
+                && (route.gateway.is_some() || route.ifindex.is_some())
+        })
+        .min_by_key(metric))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd"
+))]
+fn metric(route: &Route) -> u32 {
+    route.metric.unwrap_or(0)
+}
+
+// without metrics the first default route is as good as any
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd"
+)))]
+fn metric(_: &Route) -> u32 {
+    0
+}
+
+// `pin` going out through the gateway and interface of `default`.
+fn through(pin: &Route, default: &Route) -> Route {
+    let mut pin = pin.clone();
+    pin.gateway = default.gateway;
+    pin.ifindex = default.ifindex;
+    #[cfg(target_os = "macos")]
+    {
+        pin.ifname = default.ifname.clone();
+    }
+    #[cfg(target_os = "windows")]
+    {
+        pin.luid = default.luid;
+    }
+    pin
+}
+
+fn same_next_hop(a: &Route, b: &Route) -> bool {
+    a.gateway == b.gateway && a.ifindex == b.ifindex
+}
+
+/// Host routes for DNS resolvers installed by [`Handle::pin_resolver_routes`].
+///
+/// The routes stay installed until [`ResolverRoutes::release`] is called, dropping them leaves
+/// the routes in place.
+pub struct ResolverRoutes {
+    handle: Handle,
+    routes: Vec<Route>,
+}
+
+impl ResolverRoutes {
+    /// The installed routes.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Move the routes to the gateway and interface of the preferred default route of their ip
+    /// version whenever it changes, e.g. when switching from Wi-Fi to Ethernet, until the route
+    /// change stream of the handle ends or updating the routes fails.
+    ///
+    /// The routes are moved right away if they don't go through the current default route. The
+    /// future has to be polled to follow changes, e.g. by spawning it or selecting on it
+    /// together with the tunnel's own work. Routes of an ip version without a default route are
+    /// left where they are.
+    pub async fn follow_default_route(&mut self) -> Result<()> {
+        let changes = self.handle.route_listen_stream();
+        futures::pin_mut!(changes);
+        self.update(None).await?;
+        while let Some(change) = changes.next().await {
+            let route = match change {
+                RouteChange::Add(route)
+                | RouteChange::Delete(route)
+                | RouteChange::Change(route) => route,
+            };
+            if route.prefix == 0 {
+                self.update(Some(IpVersion::of(&route.destination))).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Move the routes of `version`, or of both versions if `None`, to the preferred default
+    // route.
+    async fn update(&mut self, version: Option<IpVersion>) -> Result<()> {
+        for current in [IpVersion::V4, IpVersion::V6] {
+            if version.map_or(false, |version| version != current) {
+                continue;
+            }
+            let Some(default) = best_default_route(&self.handle, current).await? else {
+                continue;
+            };
+
+            let mut transaction = self.handle.transaction();
+            let mut routes = self.routes.clone();
+            for route in &mut routes {
+                if IpVersion::of(&route.destination) != current || same_next_hop(route, &default) {
+                    continue;
+                }
+                let moved = through(route, &default);
+                transaction = transaction
+                    .delete_route(route.clone())
+                    .add_route(moved.clone());
+                *route = moved;
+            }
+            transaction.commit().await?;
+            self.routes = routes;
+        }
+        Ok(())
+    }
+
+    /// Remove the routes.
+    pub async fn release(self) -> Result<()> {
+        self.handle
+            .transaction()
+            .delete_routes(self.routes)
+            .commit()
+            .await
+    }
+}
+
+impl Handle {
+    /// Install a host route for every address of `resolvers` with the gateway, interface and
+    /// other properties of the route in `routes` of the same ip version, so DNS queries keep
+    /// leaving through that interface while a tunnel takes over the default route.
+    ///
+    /// Pass one route per ip version, its destination and prefix are ignored. Use
+    /// [`ResolverRoutes::follow_default_route`] to keep the routes on the default route when the
+    /// uplink changes, which works together with [`Handle::capture_default_route`] as its routes
+    /// aren't default routes.
+    pub async fn pin_resolver_routes(
+        &self,
+        resolvers: &[IpAddr],
+        routes: &[Route],
+    ) -> Result<ResolverRoutes> {
+        let routes = resolvers
+            .iter()
+            .map(|resolver| {
+                let route = routes
+                    .iter()
+                    .find(|route| route.destination.is_ipv4() == resolver.is_ipv4())
+                    .ok_or_else(|| {
+                        Error::InvalidRoute(format!("no route given for the resolver {}", resolver))
+                    })?;
+                let prefix = if resolver.is_ipv4() { 32 } else { 128 };
+                Ok(Route {
+                    destination: *resolver,
+                    prefix,
+                    ..route.clone()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        Ok(ResolverRoutes {
+            handle: self.clone(),
+            routes,
+        })
+    }
+}
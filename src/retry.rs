@@ -0,0 +1,132 @@
+use std::{io, time::Duration};
+
+/// How operations failing with a transient error are retried, see
+/// [`HandleBuilder::with_retry`](crate::HandleBuilder::with_retry).
+///
+/// Transient errors are `ENOBUFS`, `EBUSY` and `EAGAIN` on Unix and `ERROR_NOT_READY` on Windows,
+/// which are common right after an interface was created. The delay before a retry starts at the
+/// backoff and doubles with every retry up to the maximum backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy retrying 3 times, after 50ms, 100ms and 200ms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how often an operation is retried after it failed the first time.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the delay before the first retry.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the longest delay between two retries.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    // The delay before retry number `retry`, `None` once the retries are used up.
+    pub(crate) fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry >= self.retries {
+            return None;
+        }
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        Some(
+            self.backoff
+                .checked_mul(factor)
+                .unwrap_or(self.max_backoff)
+                .min(self.max_backoff),
+        )
+    }
+}
+
+// Whether the operation that failed with `e` may succeed when tried again.
+pub(crate) fn is_transient(e: &io::Error) -> bool {
+    if e.kind() == io::ErrorKind::WouldBlock {
+        return true;
+    }
+    #[cfg(all(any(target_os = "linux", target_os = "android"), not(doc)))]
+    let errno = crate::platform_impl::netlink_errno(e).or_else(|| e.raw_os_error());
+    #[cfg(not(all(any(target_os = "linux", target_os = "android"), not(doc))))]
+    let errno = e.raw_os_error();
+    errno.map_or(false, is_transient_errno)
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+fn is_transient_errno(errno: i32) -> bool {
+    matches!(errno, libc::ENOBUFS | libc::EBUSY | libc::EAGAIN)
+}
+
+// `ERROR_NOT_READY` is reported as `WouldBlock` by the Windows backend
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+)))]
+fn is_transient_errno(_: i32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_backs_off_exponentially() {
+        let policy = RetryPolicy::new()
+            .with_retries(5)
+            .with_backoff(Duration::from_millis(100))
+            .with_max_backoff(Duration::from_millis(500));
+        let delays: Vec<_> = (0..6).map(|retry| policy.delay(retry)).collect();
+        assert_eq!(
+            delays,
+            [
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(400)),
+                Some(Duration::from_millis(500)),
+                Some(Duration::from_millis(500)),
+                None,
+            ]
+        );
+        assert_eq!(RetryPolicy::new().with_retries(0).delay(0), None);
+    }
+}
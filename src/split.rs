@@ -0,0 +1,205 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{Handle, Result, Route};
+
+// A prefix of either family as an integer, ipv4 addresses use the low 32 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Prefix {
+    addr: u128,
+    len: u8,
+}
+
+impl Prefix {
+    fn new(addr: u128, len: u8, bits: u8) -> Self {
+        let mask = match len {
+            0 => 0,
+            len => (u128::MAX << (128 - len)) >> (128 - bits),
+        };
+        Self {
+            addr: addr & mask,
+            len,
+        }
+    }
+
+    fn contains(&self, other: &Prefix, bits: u8) -> bool {
+        self.len <= other.len && Prefix::new(other.addr, self.len, bits).addr == self.addr
+    }
+
+    // the two prefixes one bit longer covering this one
+    fn halves(&self, bits: u8) -> [Prefix; 2] {
+        let bit = 1 << (bits - self.len - 1);
+        [
+            Prefix {
+                addr: self.addr,
+                len: self.len + 1,
+            },
+            Prefix {
+                addr: self.addr | bit,
+                len: self.len + 1,
+            },
+        ]
+    }
+}
+
+// Remove `exclude` from `prefix`, splitting it into the halves that don't overlap.
+fn subtract(prefix: Prefix, exclude: &Prefix, bits: u8, out: &mut Vec<Prefix>) {
+    if exclude.contains(&prefix, bits) {
+        return;
+    }
+    if !prefix.contains(exclude, bits) {
+        out.push(prefix);
+        return;
+    }
+    for half in prefix.halves(bits) {
+        subtract(half, exclude, bits, out);
+    }
+}
+
+// Drop prefixes covered by others and merge siblings into their parent.
+fn aggregate(mut prefixes: Vec<Prefix>, bits: u8) -> Vec<Prefix> {
+    prefixes.sort();
+    let mut out: Vec<Prefix> = vec![];
+    for prefix in prefixes {
+        if out
+            .last()
+            .map_or(false, |last| last.contains(&prefix, bits))
+        {
+            continue;
+        }
+        out.push(prefix);
+        while let [.., a, b] = out[..] {
+            let siblings =
+                a.len == b.len && a.len > 0 && Prefix::new(b.addr, b.len - 1, bits).addr == a.addr;
+            if !siblings {
+                break;
+            }
+            out.truncate(out.len() - 2);
+            out.push(Prefix::new(a.addr, a.len - 1, bits));
+        }
+    }
+    out
+}
+
+fn split_family(include: &[(IpAddr, u8)], exclude: &[(IpAddr, u8)], v6: bool) -> Vec<(IpAddr, u8)> {
+    let bits = if v6 { 128 } else { 32 };
+    let prefixes = |prefixes: &[(IpAddr, u8)]| -> Vec<Prefix> {
+        prefixes
+            .iter()
+            .filter(|(_, len)| *len <= bits)
+            .filter_map(|(addr, len)| match addr {
+                IpAddr::V4(addr) if !v6 => Some(Prefix::new(u32::from(*addr).into(), *len, bits)),
+                IpAddr::V6(addr) if v6 => Some(Prefix::new(u128::from(*addr), *len, bits)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut remaining = aggregate(prefixes(include), bits);
+    for exclude in prefixes(exclude) {
+        let mut out = vec![];
+        for prefix in remaining {
+            subtract(prefix, &exclude, bits, &mut out);
+        }
+        remaining = out;
+    }
+    aggregate(remaining, bits)
+        .into_iter()
+        .map(|prefix| {
+            let addr = match v6 {
+                true => IpAddr::V6(Ipv6Addr::from(prefix.addr)),
+                false => IpAddr::V4(Ipv4Addr::from(prefix.addr as u32)),
+            };
+            (addr, prefix.len)
+        })
+        .collect()
+}
+
+/// Compute the smallest set of prefixes covering every address of `include` that isn't in
+/// `exclude`, e.g. everything but the local network for a VPN.
+///
+/// Prefixes of both ip versions can be mixed, invalid prefix lengths are ignored. The result is
+/// sorted with the ipv4 prefixes first.
+pub fn split_prefixes(include: &[(IpAddr, u8)], exclude: &[(IpAddr, u8)]) -> Vec<(IpAddr, u8)> {
+    let mut prefixes = split_family(include, exclude, false);
+    prefixes.extend(split_family(include, exclude, true));
+    prefixes
+}
+
+impl Handle {
+    /// Install a copy of `route` for every prefix of [`split_prefixes`]`(include, exclude)`,
+    /// with the gateway, interface and other properties of `route`.
+    ///
+    /// The added routes are returned to remove them later.
+    pub async fn add_split_routes(
+        &self,
+        route: &Route,
+        include: &[(IpAddr, u8)],
+        exclude: &[(IpAddr, u8)],
+    ) -> Result<Vec<Route>> {
+        let routes: Vec<_> = split_prefixes(include, exclude)
+            .into_iter()
+            .map(|(destination, prefix)| Route {
+                destination,
+                prefix,
+                ..route.clone()
+            })
+            .collect();
+
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        Ok(routes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(prefixes: &[&str]) -> Vec<(IpAddr, u8)> {
+        prefixes
+            .iter()
+            .map(|prefix| {
+                let (addr, len) = prefix.split_once('/').unwrap();
+                (addr.parse().unwrap(), len.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_splits_around_exclusions() {
+        let prefixes = split_prefixes(&parse(&["0.0.0.0/0"]), &parse(&["10.0.0.0/8"]));
+        assert_eq!(
+            prefixes,
+            parse(&[
+                "0.0.0.0/5",
+                "8.0.0.0/7",
+                "11.0.0.0/8",
+                "12.0.0.0/6",
+                "16.0.0.0/4",
+                "32.0.0.0/3",
+                "64.0.0.0/2",
+                "128.0.0.0/1",
+            ])
+        );
+    }
+
+    #[test]
+    fn it_merges_adjacent_prefixes() {
+        let prefixes = split_prefixes(
+            &parse(&[
+                "10.0.0.0/9",
+                "10.128.0.0/9",
+                "10.1.0.0/16",
+                "::/1",
+                "8000::/1",
+            ]),
+            &[],
+        );
+        assert_eq!(prefixes, parse(&["10.0.0.0/8", "::/0"]));
+
+        let prefixes = split_prefixes(&parse(&["192.168.1.0/24"]), &parse(&["192.168.0.0/16"]));
+        assert!(prefixes.is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::HashMap, io, net::IpAddr};
+
+use crate::{DumpFilter, Handle, Interface, IpVersion, Result, Route};
+
+/// The routes added by [`Handle::add_split_tunnel`] and the interface metrics it changed,
+/// removed and restored with [`Handle::remove_split_tunnel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitTunnel {
+    /// The interface the routes go through.
+    pub ifindex: u32,
+    /// The added routes.
+    pub routes: Vec<Route>,
+    /// The interface metrics that were lowered, with the setting they had before: `None` if
+    /// the system picked the metric, as taken by [`Handle::set_interface_metric`].
+    pub previous_metrics: Vec<(IpVersion, Option<u32>)>,
+}
+
+// Windows picks between routes with the same prefix by the sum of the route metric and the
+// metric of the outgoing interface. Adding a route with a low route metric isn't enough to beat
+// the physical default route when the tunnel adapter got a high automatic interface metric, and
+// changing only the interface metric affects every route of the adapter.
+impl Handle {
+    /// Route `prefixes` through `interface` with priority over the routes of other interfaces
+    /// for the same prefixes, e.g. `0.0.0.0/0` through a VPN adapter.
+    ///
+    /// The routes are added on-link with a route metric of 0. If the interface metric of
+    /// `interface` doesn't already rank it above every competing route, it is lowered to 1.
+    /// The metrics are restored if the routes can't be added. The returned [`SplitTunnel`]
+    /// undoes both with [`Handle::remove_split_tunnel`].
+    pub async fn add_split_tunnel(
+        &self,
+        interface: &Interface,
+        prefixes: &[(IpAddr, u8)],
+    ) -> Result<SplitTunnel> {
+        let mut tunnel = SplitTunnel {
+            ifindex: interface.ifindex,
+            routes: vec![],
+            previous_metrics: vec![],
+        };
+        match self
+            .install_split_tunnel(interface, prefixes, &mut tunnel)
+            .await
+        {
+            Ok(()) => Ok(tunnel),
+            Err(e) => {
+                // the transaction already removed the routes, errors while restoring are ignored
+                let _ = self.restore_interface_metrics(&tunnel).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove the routes of a split tunnel and restore the interface metrics it changed.
+    ///
+    /// The metrics are only restored once all routes are gone.
+    pub async fn remove_split_tunnel(&self, tunnel: &SplitTunnel) -> Result<()> {
+        self.transaction()
+            .delete_routes(tunnel.routes.iter().cloned())
+            .commit()
+            .await?;
+        self.restore_interface_metrics(tunnel).await
+    }
+
+    async fn install_split_tunnel(
+        &self,
+        interface: &Interface,
+        prefixes: &[(IpAddr, u8)],
+        tunnel: &mut SplitTunnel,
+    ) -> Result<()> {
+        for version in [IpVersion::V4, IpVersion::V6] {
+            let prefixes: Vec<_> = prefixes
+                .iter()
+                .filter(|(destination, _)| IpVersion::of(destination) == version)
+                .collect();
+            if prefixes.is_empty() {
+                continue;
+            }
+            if let Some(previous) = self
+                .raise_interface_priority(interface, version, &prefixes)
+                .await?
+            {
+                tunnel.previous_metrics.push((version, previous));
+            }
+        }
+
+        let routes: Vec<_> = prefixes
+            .iter()
+            .map(|(destination, prefix)| {
+                Route::new(*destination, *prefix)
+                    .with_interface(interface)
+                    .with_metric(0)
+            })
+            .collect();
+        self.transaction()
+            .add_routes(routes.iter().cloned())
+            .commit()
+            .await?;
+        tunnel.routes = routes;
+        Ok(())
+    }
+
+    async fn restore_interface_metrics(&self, tunnel: &SplitTunnel) -> Result<()> {
+        for (version, metric) in &tunnel.previous_metrics {
+            self.set_interface_metric(tunnel.ifindex, *version, *metric)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Lower the interface metric of `interface` if needed, returning the previous setting if it
+    // was changed.
+    async fn raise_interface_priority(
+        &self,
+        interface: &Interface,
+        version: IpVersion,
+        prefixes: &[&(IpAddr, u8)],
+    ) -> Result<Option<Option<u32>>> {
+        let mut interface_metrics = HashMap::new();
+        let mut best = None;
+        for route in self
+            .list_filtered(&DumpFilter::new().with_family(version))
+            .await?
+        {
+            let Some(ifindex) = route.ifindex else {
+                continue;
+            };
+            if ifindex == interface.ifindex
+                || !prefixes.contains(&&(route.destination, route.prefix))
+            {
+                continue;
+            }
+            let interface_metric = match interface_metrics.get(&ifindex) {
+                Some(metric) => *metric,
+                None => {
+                    let metric = self.interface_metric(ifindex, version).await?;
+                    interface_metrics.insert(ifindex, metric);
+                    metric
+                }
+            };
+            let metric = route.metric.unwrap_or(0).saturating_add(interface_metric);
+            best = Some(best.map_or(metric, |best: u32| best.min(metric)));
+        }
+        let Some(best) = best else {
+            return Ok(None);
+        };
+
+        // the added routes have a route metric of 0, so the interface metric alone decides
+        if self.interface_metric(interface.ifindex, version).await? < best {
+            return Ok(None);
+        }
+        if best <= 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "a competing route already has the lowest possible metric",
+            )
+            .into());
+        }
+        let previous = self
+            .interface_metric_setting(interface.ifindex, version)
+            .await?;
+        self.set_interface_metric(interface.ifindex, version, Some(1))
+            .await?;
+        Ok(Some(previous))
+    }
+}
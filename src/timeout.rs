@@ -0,0 +1,193 @@
+#[cfg(not(feature = "runtime-smol"))]
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    pin::Pin,
+    sync::{Condvar, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+use std::{future::Future, io, time::Duration};
+
+use futures::future::{self, Either};
+
+// Fail with `io::ErrorKind::TimedOut` if `future` doesn't finish within `duration`, which drops
+// it. Without a duration the future runs to completion.
+pub(crate) async fn timeout<T>(
+    duration: Option<Duration>,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    let Some(duration) = duration else {
+        return future.await;
+    };
+    futures::pin_mut!(future);
+    let timer = sleep(duration);
+    futures::pin_mut!(timer);
+    match future::select(future, timer).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("the operation didn't finish within {:?}", duration),
+        )),
+    }
+}
+
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(feature = "runtime-tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        tokio::time::sleep(duration).await;
+        return;
+    }
+    #[cfg(feature = "runtime-smol")]
+    smol::Timer::after(duration).await;
+    // outside of a runtime with timers, e.g. on Windows, which needs none
+    #[cfg(not(feature = "runtime-smol"))]
+    Sleep {
+        deadline: Instant::now() + duration,
+        id: None,
+    }
+    .await;
+}
+
+// A sleep woken by the timer thread, shared by every sleep outside of a runtime with timers.
+#[cfg(not(feature = "runtime-smol"))]
+struct Sleep {
+    deadline: Instant,
+    // registered with the timer thread on the first poll
+    id: Option<u64>,
+}
+
+#[cfg(not(feature = "runtime-smol"))]
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let deadline = self.deadline;
+        let id = timers().register(self.id, deadline, cx.waker());
+        self.id = Some(id);
+        Poll::Pending
+    }
+}
+
+#[cfg(not(feature = "runtime-smol"))]
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            timers().cancel(id);
+        }
+    }
+}
+
+// The deadlines of the pending sleeps, waited for by a single thread started with the first.
+#[cfg(not(feature = "runtime-smol"))]
+#[derive(Default)]
+struct Timers {
+    state: Mutex<TimerState>,
+    changed: Condvar,
+}
+
+#[cfg(not(feature = "runtime-smol"))]
+#[derive(Default)]
+struct TimerState {
+    // earliest first, including those of sleeps dropped since
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+    next_id: u64,
+}
+
+#[cfg(not(feature = "runtime-smol"))]
+fn timers() -> &'static Timers {
+    static TIMERS: OnceLock<Timers> = OnceLock::new();
+    let mut first = false;
+    let timers = TIMERS.get_or_init(|| {
+        first = true;
+        Timers::default()
+    });
+    if first {
+        std::thread::Builder::new()
+            .name("net-route-timer".into())
+            .spawn(|| timers.run())
+            .expect("failed to spawn the timer thread");
+    }
+    timers
+}
+
+#[cfg(not(feature = "runtime-smol"))]
+impl Timers {
+    // Wake `waker` at `deadline`, returning the id of the sleep.
+    fn register(&self, id: Option<u64>, deadline: Instant, waker: &Waker) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = id.unwrap_or_else(|| {
+            state.next_id += 1;
+            state.next_id
+        });
+        // new, or the thread woke it just before the deadline was reached
+        if state.wakers.insert(id, waker.clone()).is_none() {
+            state.deadlines.push(Reverse((deadline, id)));
+            // the thread might be waiting for a later deadline
+            self.changed.notify_one();
+        }
+        id
+    }
+
+    fn cancel(&self, id: u64) {
+        self.state.lock().unwrap().wakers.remove(&id);
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            while let Some(&Reverse((deadline, id))) = state.deadlines.peek() {
+                if deadline > now {
+                    break;
+                }
+                state.deadlines.pop();
+                if let Some(waker) = state.wakers.remove(&id) {
+                    waker.wake();
+                }
+            }
+            state = match state.deadlines.peek() {
+                Some(&Reverse((deadline, _))) => {
+                    self.changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_times_out() {
+        let result = futures::executor::block_on(timeout(
+            Some(Duration::from_millis(10)),
+            future::pending::<io::Result<()>>(),
+        ));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        let result = futures::executor::block_on(timeout(None, async { Ok(1) }));
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[cfg(not(feature = "runtime-smol"))]
+    #[test]
+    fn it_sleeps_on_one_timer_thread() {
+        futures::executor::block_on(async {
+            // a shorter sleep registered after a longer one still wakes first
+            let long = sleep(Duration::from_secs(5));
+            futures::pin_mut!(long);
+            assert!(futures::poll!(long.as_mut()).is_pending());
+            let started = Instant::now();
+            sleep(Duration::from_millis(10)).await;
+            assert!(started.elapsed() >= Duration::from_millis(10));
+            assert!(futures::poll!(long.as_mut()).is_pending());
+        });
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+use crate::Rule;
+use crate::{Error, Handle, Result, Route};
+
+#[derive(Debug, Clone)]
+enum Op {
+    AddRoute(Route),
+    DeleteRoute(Route),
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    AddRule(Rule),
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    DeleteRule(Rule),
+}
+
+impl Op {
+    fn inverse(&self) -> Op {
+        match self {
+            Op::AddRoute(route) => Op::DeleteRoute(route.clone()),
+            Op::DeleteRoute(route) => Op::AddRoute(route.clone()),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::AddRule(rule) => Op::DeleteRule(rule.clone()),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::DeleteRule(rule) => Op::AddRule(rule.clone()),
+        }
+    }
+
+    // Tell which change `e` is about.
+    fn context(&self, e: Error) -> Error {
+        match self {
+            Op::AddRoute(route) => e.context("add route", route),
+            Op::DeleteRoute(route) => e.context("delete route", route),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::AddRule(rule) => e.context("add rule", rule),
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::DeleteRule(rule) => e.context("delete rule", rule),
+        }
+    }
+
+    async fn apply(&self, handle: &Handle) -> Result<()> {
+        match self {
+            Op::AddRoute(route) => handle.add(route).await,
+            Op::DeleteRoute(route) => handle.delete(route).await,
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::AddRule(rule) => handle.add_rules(vec![rule.clone()]).await,
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            Op::DeleteRule(rule) => handle.delete_rules(vec![rule.clone()]).await,
+        }
+    }
+}
+
+/// A list of route and rule changes that are applied in order and rolled back together if one
+/// of them fails, created by [`Handle::transaction`].
+///
+/// Rolling back a deletion adds the staged route or rule again, so deletions should be staged
+/// with all the fields of the entry they remove.
+#[must_use = "a transaction does nothing until it is committed"]
+pub struct Transaction<'a> {
+    handle: &'a Handle,
+    ops: Vec<Op>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(handle: &'a Handle) -> Self {
+        Self {
+            handle,
+            ops: vec![],
+        }
+    }
+
+    /// Stage adding a route.
+    pub fn add_route(mut self, route: Route) -> Self {
+        self.ops.push(Op::AddRoute(route));
+        self
+    }
+
+    /// Stage removing a route.
+    pub fn delete_route(mut self, route: Route) -> Self {
+        self.ops.push(Op::DeleteRoute(route));
+        self
+    }
+
+    /// Stage adding each of `routes`.
+    ///
+    /// The helpers that install or remove several routes, e.g. [`Handle::capture_default_route`]
+    /// or [`KillSwitch::disable`](crate::KillSwitch::disable), stage them this way, so either
+    /// all of them are changed or none.
+    pub fn add_routes(mut self, routes: impl IntoIterator<Item = Route>) -> Self {
+        self.ops.extend(routes.into_iter().map(Op::AddRoute));
+        self
+    }
+
+    /// Stage removing each of `routes`, the counterpart of [`Transaction::add_routes`].
+    pub fn delete_routes(mut self, routes: impl IntoIterator<Item = Route>) -> Self {
+        self.ops.extend(routes.into_iter().map(Op::DeleteRoute));
+        self
+    }
+
+    /// Stage adding a rule.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn add_rule(mut self, rule: Rule) -> Self {
+        self.ops.push(Op::AddRule(rule));
+        self
+    }
+
+    /// Stage removing a rule.
+    #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+    pub fn delete_rule(mut self, rule: Rule) -> Self {
+        self.ops.push(Op::DeleteRule(rule));
+        self
+    }
+
+    /// Apply the staged changes in order.
+    ///
+    /// If a change fails, every change applied before it is reverted in reverse order and the
+    /// error of the failed change is returned as an [`Error::Context`] naming the change. Errors
+    /// while reverting are ignored.
+    pub async fn commit(self) -> Result<()> {
+        let mut applied = vec![];
+        for op in &self.ops {
+            if let Err(e) = op.apply(self.handle).await {
+                for op in applied.iter().rev() {
+                    let _ = op.inverse().apply(self.handle).await;
+                }
+                return Err(op.context(e));
+            }
+            applied.push(op);
+        }
+        Ok(())
+    }
+}
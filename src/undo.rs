@@ -0,0 +1,165 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::{Error, Handle, JournalOp, Result};
+
+// The operation reverting `op`, `None` for operations that can't be reverted.
+fn inverse(op: &JournalOp) -> Option<JournalOp> {
+    match op {
+        JournalOp::AddRoute(route) => Some(JournalOp::DeleteRoute(route.clone())),
+        JournalOp::DeleteRoute(route) => Some(JournalOp::AddRoute(route.clone())),
+        #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+        JournalOp::AddRules(rules) => Some(JournalOp::DeleteRules(rules.clone())),
+        #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+        JournalOp::DeleteRules(rules) => Some(JournalOp::AddRules(rules.clone())),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+// The inverses of the latest changes made through a handle, the most recent last.
+pub(crate) struct UndoStack {
+    limit: usize,
+    ops: Mutex<VecDeque<JournalOp>>,
+}
+
+impl UndoStack {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ops: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub(crate) fn push(&self, op: &JournalOp) {
+        let Some(inverse) = inverse(op) else {
+            return;
+        };
+        if self.limit == 0 {
+            return;
+        }
+        let mut ops = self.ops.lock().unwrap();
+        if ops.len() == self.limit {
+            ops.pop_front();
+        }
+        ops.push_back(inverse);
+    }
+
+    fn pop(&self) -> Option<JournalOp> {
+        self.ops.lock().unwrap().pop_back()
+    }
+
+    // put back an inverse that failed to apply
+    fn restore(&self, inverse: JournalOp) {
+        self.ops.lock().unwrap().push_back(inverse);
+    }
+
+    fn len(&self) -> usize {
+        self.ops.lock().unwrap().len()
+    }
+}
+
+impl Handle {
+    /// Revert the last `n` changes made through this handle and its clones, the most recent
+    /// first, and return how many were reverted. Fewer than `n` are reverted if the history is
+    /// shorter, which is empty unless enabled with
+    /// [`HandleBuilder::with_undo`](crate::HandleBuilder::with_undo).
+    ///
+    /// Added routes and rules are removed and removed ones are added again. Other changes, e.g.
+    /// routes added in a VRF, aren't tracked. Removals are reverted with the route or rule that
+    /// was passed to remove it, so it should have had all the fields of the removed entry.
+    ///
+    /// If reverting a change fails, it stays in the history and the error is returned.
+    pub async fn undo(&self, n: usize) -> Result<usize> {
+        let Some(stack) = &self.undo else {
+            return Ok(0);
+        };
+        for undone in 0..n {
+            let Some(op) = stack.pop() else {
+                return Ok(undone);
+            };
+            if let Err(e) = self.revert(&op).await {
+                stack.restore(op);
+                return Err(e);
+            }
+        }
+        Ok(n)
+    }
+
+    /// Revert every change in the history, see [`Handle::undo`].
+    pub async fn undo_all(&self) -> Result<usize> {
+        let len = self.undo.as_ref().map_or(0, |stack| stack.len());
+        self.undo(len).await
+    }
+
+    // Apply `op` without adding it to the history, it is still journaled.
+    async fn revert(&self, op: &JournalOp) -> Result<()> {
+        let result = match op {
+            JournalOp::AddRoute(route) => {
+                self.throttle(1).await;
+                let result = self.run(|| self.platform.add(route)).await;
+                if result.is_ok() {
+                    self.counters.route_added();
+                }
+                result
+            }
+            JournalOp::DeleteRoute(route) => {
+                self.throttle(1).await;
+                let result = self.run(|| self.platform.delete(route)).await;
+                if result.is_ok() {
+                    self.counters.route_deleted();
+                }
+                result
+            }
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            JournalOp::AddRules(rules) => {
+                self.throttle(rules.len()).await;
+                self.run(|| self.platform.add_rules(rules.clone())).await
+            }
+            #[cfg(all(feature = "rules", any(target_os = "linux", target_os = "android")))]
+            JournalOp::DeleteRules(rules) => {
+                self.throttle(rules.len()).await;
+                self.run(|| self.platform.delete_rules(rules.clone())).await
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::Unsupported(format!("can't revert {:?}", op))),
+        };
+        let result = self.counted(result);
+        if let Some(journal) = &self.journal {
+            journal.record(op.clone(), &result);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Route;
+
+    #[test]
+    fn it_keeps_the_latest_inverses() {
+        let stack = UndoStack::new(2);
+        for prefix in [8, 16, 24] {
+            stack.push(&JournalOp::AddRoute(Route::new(
+                "10.0.0.0".parse().unwrap(),
+                prefix,
+            )));
+        }
+        assert_eq!(stack.len(), 2);
+        assert_eq!(
+            stack.pop(),
+            Some(JournalOp::DeleteRoute(Route::new(
+                "10.0.0.0".parse().unwrap(),
+                24
+            )))
+        );
+        assert_eq!(
+            stack.pop(),
+            Some(JournalOp::DeleteRoute(Route::new(
+                "10.0.0.0".parse().unwrap(),
+                16
+            )))
+        );
+        assert_eq!(stack.pop(), None);
+    }
+}
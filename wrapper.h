@@ -5,4 +5,5 @@
 #include <arpa/inet.h>
 #include <net/route.h>
 #include <net/if_dl.h>
-#include <net/if.h>
\ No newline at end of file
+#include <net/if.h>
+#include <unistd.h>
\ No newline at end of file